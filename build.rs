@@ -0,0 +1,4 @@
+// the migrations are embedded with sqlx::migrate!, so adding one has to trigger a rebuild
+fn main() {
+	println!("cargo:rerun-if-changed=migrations");
+}
@@ -1 +0,0 @@
-pg_dump -U twitter -d twitter --schema-only --no-owner --no-privileges > init.sql
@@ -0,0 +1,436 @@
+//! Drives a running instance like a crowd of signed in users and reports the latency per route.
+//!
+//! Seed the database with `my-twitter seed`, start the server, then
+//! `cargo run --release --example loadtest -- --concurrency 20 --duration 30`
+
+use std::{
+	collections::HashMap,
+	process::ExitCode,
+	time::{Duration, Instant},
+};
+
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use reqwest::{header, redirect, Client, StatusCode};
+
+const USAGE: &str = "usage: loadtest [--url URL] [--concurrency N] [--duration SECONDS] [--password PASSWORD] [--seed N] [--budget ROUTE=MS]...";
+
+// what a user does next and how often, out of the total weight
+const ACTIONS: &[(Action, u32)] = &[
+	(Action::Feed, 40),
+	(Action::Thread, 30),
+	(Action::Likes, 10),
+	(Action::Like, 15),
+	(Action::Post, 5),
+];
+
+// p95 in milliseconds each route has to stay under, `--budget` overrides them
+const BUDGETS: &[(&str, u64)] = &[
+	("feed", 300),
+	("thread", 150),
+	("likes", 100),
+	("like", 100),
+	("post", 100),
+	("login", 100),
+];
+
+#[derive(Debug, Clone, Copy)]
+enum Action {
+	Feed,
+	Thread,
+	Likes,
+	Like,
+	Post,
+}
+
+struct Options {
+	url: String,
+	concurrency: usize,
+	duration: Duration,
+	password: String,
+	seed: u64,
+	budgets: HashMap<String, u64>,
+}
+impl Options {
+	fn parse(args: &[String]) -> Result<Self, String> {
+		let mut options = Self {
+			// where Rocket.toml has the server listen, unless ROCKET_PORT moves it
+			url: format!(
+				"http://127.0.0.1:{}",
+				std::env::var("ROCKET_PORT").unwrap_or_else(|_| "8080".to_string())
+			),
+			concurrency: 10,
+			duration: Duration::from_secs(30),
+			password: "Seed-pass1".to_string(),
+			seed: 1,
+			budgets: BUDGETS
+				.iter()
+				.map(|&(route, ms)| (route.to_string(), ms))
+				.collect(),
+		};
+		for pair in args.chunks(2) {
+			let [flag, value] = pair else {
+				return Err(USAGE.to_string());
+			};
+			let number = || {
+				value
+					.parse::<u64>()
+					.map_err(|_| format!("{value} isn't a number for {flag}"))
+			};
+			match flag.as_str() {
+				"--url" => options.url = value.trim_end_matches('/').to_string(),
+				"--concurrency" => options.concurrency = number()?.max(1) as usize,
+				"--duration" => options.duration = Duration::from_secs(number()?),
+				"--password" => options.password = value.clone(),
+				"--seed" => options.seed = number()?,
+				"--budget" => {
+					let (route, ms) = value
+						.split_once('=')
+						.ok_or(format!("{value} isn't ROUTE=MS"))?;
+					let ms = ms.parse().map_err(|_| format!("{ms} isn't a number"))?;
+					options.budgets.insert(route.to_string(), ms);
+				}
+				_ => return Err(USAGE.to_string()),
+			}
+		}
+		Ok(options)
+	}
+}
+
+/// The latencies of one route, and how many requests got an unexpected status
+#[derive(Default)]
+struct Samples {
+	latencies: Vec<Duration>,
+	errors: usize,
+}
+
+#[derive(Default)]
+struct Report {
+	routes: HashMap<&'static str, Samples>,
+}
+impl Report {
+	fn record(&mut self, route: &'static str, start: Instant, ok: bool) {
+		let samples = self.routes.entry(route).or_default();
+		samples.latencies.push(start.elapsed());
+		if !ok {
+			samples.errors += 1;
+		}
+	}
+	fn merge(&mut self, other: Report) {
+		for (route, samples) in other.routes {
+			let merged = self.routes.entry(route).or_default();
+			merged.latencies.extend(samples.latencies);
+			merged.errors += samples.errors;
+		}
+	}
+}
+
+// the latency below which the given share of the requests finished
+fn percentile(sorted: &[Duration], share: f64) -> Duration {
+	let rank = ((sorted.len() as f64 * share).ceil() as usize).clamp(1, sorted.len());
+	sorted[rank - 1]
+}
+
+fn ms(d: Duration) -> f64 {
+	d.as_secs_f64() * 1000.0
+}
+
+// every value of the attribute in the page, in order
+fn attributes<'a>(html: &'a str, prefix: &str) -> Vec<&'a str> {
+	html.split(prefix)
+		.skip(1)
+		.filter_map(|rest| rest.split('"').next())
+		.collect()
+}
+
+fn csrf_token(html: &str) -> Option<String> {
+	attributes(html, r#"name="csrf-token" content=""#)
+		.first()
+		.map(|token| token.to_string())
+}
+
+fn encode(value: &str) -> String {
+	value
+		.bytes()
+		.map(|b| match b {
+			b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'_' | b'.' => (b as char).to_string(),
+			b' ' => "+".to_string(),
+			b => format!("%{b:02X}"),
+		})
+		.collect()
+}
+
+/// One signed in user clicking through the site until the time is up
+struct User {
+	client: Client,
+	url: String,
+	csrf_token: String,
+	posts: Vec<u64>,
+	rng: StdRng,
+}
+impl User {
+	async fn sign_in(
+		options: &Options,
+		handle: &str,
+		rng: StdRng,
+		report: &mut Report,
+	) -> Result<Self, String> {
+		// redirects are left alone, so every request is timed on its own
+		let client = Client::builder()
+			.cookie_store(true)
+			.redirect(redirect::Policy::none())
+			.build()
+			.map_err(|e| e.to_string())?;
+		let url = &options.url;
+
+		let page = client
+			.get(format!("{url}/login"))
+			.send()
+			.await
+			.map_err(|e| e.to_string())?
+			.text()
+			.await
+			.map_err(|e| e.to_string())?;
+		let token = attributes(&page, r#"name="csrf_token" value=""#)
+			.first()
+			.map(|t| t.to_string())
+			.ok_or("no csrf token on the login page")?;
+
+		let start = Instant::now();
+		let res = client
+			.post(format!("{url}/login"))
+			.header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+			.body(format!(
+				"csrf_token={}&username={}&password={}",
+				encode(&token),
+				encode(handle),
+				encode(&options.password)
+			))
+			.send()
+			.await
+			.map_err(|e| e.to_string())?;
+		let location = res
+			.headers()
+			.get(header::LOCATION)
+			.and_then(|l| l.to_str().ok());
+		let signed_in = location == Some("/");
+		report.record("login", start, signed_in);
+		if !signed_in {
+			return Err(format!("could not sign in as @{handle}: {location:?}"));
+		}
+
+		let mut user = Self {
+			client,
+			url: url.clone(),
+			csrf_token: String::new(),
+			posts: Vec::new(),
+			rng,
+		};
+		user.feed(report).await;
+		Ok(user)
+	}
+
+	async fn run(mut self, until: Instant, mut report: Report) -> Report {
+		let total: u32 = ACTIONS.iter().map(|(_, weight)| weight).sum();
+		while Instant::now() < until {
+			let mut pick = self.rng.gen_range(0..total);
+			let action = ACTIONS
+				.iter()
+				.find_map(|&(action, weight)| match pick < weight {
+					true => Some(action),
+					false => {
+						pick -= weight;
+						None
+					}
+				})
+				.unwrap_or(Action::Feed);
+
+			match action {
+				Action::Feed => self.feed(&mut report).await,
+				Action::Thread => self.get("thread", "", &mut report).await,
+				Action::Likes => self.get("likes", "/likes", &mut report).await,
+				Action::Like => self.like(&mut report).await,
+				Action::Post => self.post(&mut report).await,
+			}
+		}
+		report
+	}
+
+	// the feed also tells which posts there are to open and like next
+	async fn feed(&mut self, report: &mut Report) {
+		let start = Instant::now();
+		let page = match self.client.get(format!("{}/", self.url)).send().await {
+			Ok(res) if res.status() == StatusCode::OK => res.text().await.ok(),
+			_ => None,
+		};
+		report.record("feed", start, page.is_some());
+
+		if let Some(page) = page {
+			if let Some(token) = csrf_token(&page) {
+				self.csrf_token = token;
+			}
+			self.posts = attributes(&page, r#"data-id=""#)
+				.into_iter()
+				.filter_map(|id| id.parse().ok())
+				.collect();
+		}
+	}
+
+	async fn get(&mut self, route: &'static str, suffix: &str, report: &mut Report) {
+		let Some(id) = self.posts.choose(&mut self.rng) else {
+			return self.feed(report).await;
+		};
+		let start = Instant::now();
+		let res = self
+			.client
+			.get(format!("{}/post/{id}{suffix}", self.url))
+			.send()
+			.await;
+		// the body is part of the time it takes
+		let ok = match res {
+			Ok(res) => res.status() == StatusCode::OK && res.bytes().await.is_ok(),
+			Err(_) => false,
+		};
+		report.record(route, start, ok);
+	}
+
+	async fn like(&mut self, report: &mut Report) {
+		let Some(id) = self.posts.choose(&mut self.rng) else {
+			return self.feed(report).await;
+		};
+		let start = Instant::now();
+		let res = self
+			.client
+			.post(format!("{}/like_post/{id}", self.url))
+			.header("X-CSRF-Token", &self.csrf_token)
+			.send()
+			.await;
+		// the feed doesn't say who wrote what, so some of the likes are on the user's own posts
+		let ok = res.is_ok_and(|r| r.status().is_success() || r.status() == StatusCode::FORBIDDEN);
+		report.record("like", start, ok);
+	}
+
+	async fn post(&mut self, report: &mut Report) {
+		let body = format!("Load test post {}", self.rng.gen::<u32>());
+		let start = Instant::now();
+		let res = self
+			.client
+			.post(format!("{}/create_post", self.url))
+			.header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+			.header("X-CSRF-Token", &self.csrf_token)
+			.body(format!("body={}", encode(&body)))
+			.send()
+			.await;
+		report.record(
+			"post",
+			start,
+			res.is_ok_and(|r| r.status() == StatusCode::SEE_OTHER),
+		);
+	}
+}
+
+// the accounts whose posts are on the feed, they are the ones the seed created
+async fn handles(url: &str) -> Result<Vec<String>, String> {
+	let page = reqwest::get(format!("{url}/"))
+		.await
+		.map_err(|e| format!("could not reach {url}: {e}"))?
+		.text()
+		.await
+		.map_err(|e| e.to_string())?;
+	let mut handles: Vec<String> = attributes(&page, r#"href="/user/"#)
+		.into_iter()
+		.map(str::to_string)
+		.collect();
+	handles.sort();
+	handles.dedup();
+	match handles.is_empty() {
+		true => Err("nobody has posted yet, seed the database first".to_string()),
+		false => Ok(handles),
+	}
+}
+
+#[rocket::main]
+async fn main() -> ExitCode {
+	let args: Vec<String> = std::env::args().skip(1).collect();
+	let options = match Options::parse(&args) {
+		Ok(options) => options,
+		Err(e) => {
+			eprintln!("{e}");
+			return ExitCode::FAILURE;
+		}
+	};
+	let handles = match handles(&options.url).await {
+		Ok(handles) => handles,
+		Err(e) => {
+			eprintln!("{e}");
+			return ExitCode::FAILURE;
+		}
+	};
+
+	// signing in first, so the logins don't count against the time
+	let mut report = Report::default();
+	let mut users = Vec::new();
+	for i in 0..options.concurrency {
+		let handle = &handles[i % handles.len()];
+		let rng = StdRng::seed_from_u64(options.seed.wrapping_add(i as u64));
+		match User::sign_in(&options, handle, rng, &mut report).await {
+			Ok(user) => users.push(user),
+			Err(e) => {
+				eprintln!("{e}");
+				return ExitCode::FAILURE;
+			}
+		}
+	}
+
+	println!(
+		"{} users against {} for {}s",
+		users.len(),
+		options.url,
+		options.duration.as_secs()
+	);
+	let until = Instant::now() + options.duration;
+	let workers: Vec<_> = users
+		.into_iter()
+		.map(|user| rocket::tokio::spawn(user.run(until, Report::default())))
+		.collect();
+	for worker in workers {
+		match worker.await {
+			Ok(user_report) => report.merge(user_report),
+			Err(e) => eprintln!("a user stopped early: {e}"),
+		}
+	}
+
+	println!(
+		"\n{:<8} {:>8} {:>7} {:>9} {:>9} {:>9} {:>9} {:>8}",
+		"route", "requests", "errors", "p50 ms", "p95 ms", "p99 ms", "max ms", "budget"
+	);
+	let mut over_budget = Vec::new();
+	let mut routes: Vec<_> = report.routes.into_iter().collect();
+	routes.sort_by_key(|(route, _)| *route);
+	for (route, mut samples) in routes {
+		samples.latencies.sort();
+		let p95 = percentile(&samples.latencies, 0.95);
+		let budget = options.budgets.get(route);
+		let within = budget.is_none_or(|&budget| ms(p95) <= budget as f64);
+		if !within || samples.errors > 0 {
+			over_budget.push(route);
+		}
+		println!(
+			"{route:<8} {:>8} {:>7} {:>9.1} {:>9.1} {:>9.1} {:>9.1} {:>8}",
+			samples.latencies.len(),
+			samples.errors,
+			ms(percentile(&samples.latencies, 0.5)),
+			ms(p95),
+			ms(percentile(&samples.latencies, 0.99)),
+			ms(*samples.latencies.last().expect("at least one request")),
+			budget.map_or("-".to_string(), |b| format!("{b}ms")),
+		);
+	}
+
+	match over_budget.is_empty() {
+		true => ExitCode::SUCCESS,
+		false => {
+			eprintln!("\nover budget or failing: {}", over_budget.join(", "));
+			ExitCode::FAILURE
+		}
+	}
+}
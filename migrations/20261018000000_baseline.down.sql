@@ -0,0 +1,9 @@
+-- dropping the tables also drops their sequences, indexes and constraints
+
+DROP TABLE IF EXISTS
+    public.account,
+    public.block,
+    public.follow,
+    public.post,
+    public.vote
+    CASCADE;
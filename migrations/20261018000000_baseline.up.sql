@@ -1,24 +1,4 @@
---
--- PostgreSQL database dump
---
-
--- Dumped from database version 14.12 (Ubuntu 14.12-0ubuntu0.22.04.1)
--- Dumped by pg_dump version 14.12 (Ubuntu 14.12-0ubuntu0.22.04.1)
-
-SET statement_timeout = 0;
-SET lock_timeout = 0;
-SET idle_in_transaction_session_timeout = 0;
-SET client_encoding = 'UTF8';
-SET standard_conforming_strings = on;
-SELECT pg_catalog.set_config('search_path', '', false);
-SET check_function_bodies = false;
-SET xmloption = content;
-SET client_min_messages = warning;
-SET row_security = off;
-
-SET default_tablespace = '';
-
-SET default_table_access_method = heap;
+-- the schema as it was before migrations, taken from the old init.sql
 
 --
 -- Name: account; Type: TABLE; Schema: public; Owner: -
@@ -250,9 +230,3 @@ ALTER TABLE ONLY public.post
 
 ALTER TABLE ONLY public.post
     ADD CONSTRAINT post_parent_id_fkey FOREIGN KEY (parent_id) REFERENCES public.post(id) ON UPDATE CASCADE ON DELETE SET NULL;
-
-
---
--- PostgreSQL database dump complete
---
-
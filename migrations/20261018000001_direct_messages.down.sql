@@ -0,0 +1,3 @@
+DROP TABLE public.message_deletion, public.message, public.conversation_member, public.conversation;
+
+ALTER TABLE public.account DROP COLUMN dm_permission;
@@ -0,0 +1,32 @@
+-- conversations between accounts, a message deleted by one member stays visible to the others
+ALTER TABLE public.account
+    ADD COLUMN dm_permission character varying(10) DEFAULT 'everyone' NOT NULL
+    CONSTRAINT account_dm_permission_check CHECK (dm_permission IN ('everyone', 'following', 'nobody'));
+
+CREATE TABLE public.conversation (
+    id serial PRIMARY KEY,
+    create_time timestamp without time zone DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE TABLE public.conversation_member (
+    conversation_id integer NOT NULL REFERENCES public.conversation(id) ON UPDATE CASCADE ON DELETE CASCADE,
+    account_id integer NOT NULL REFERENCES public.account(id) ON UPDATE CASCADE ON DELETE CASCADE,
+    last_read_time timestamp without time zone DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    PRIMARY KEY (conversation_id, account_id)
+);
+CREATE INDEX conversation_member_account_id_idx ON public.conversation_member USING btree (account_id);
+
+CREATE TABLE public.message (
+    id bigserial PRIMARY KEY,
+    conversation_id integer NOT NULL REFERENCES public.conversation(id) ON UPDATE CASCADE ON DELETE CASCADE,
+    author_id integer NOT NULL REFERENCES public.account(id) ON UPDATE CASCADE ON DELETE CASCADE,
+    body text NOT NULL,
+    create_time timestamp without time zone DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+CREATE INDEX message_conversation_id_idx ON public.message USING btree (conversation_id, create_time);
+
+CREATE TABLE public.message_deletion (
+    message_id bigint NOT NULL REFERENCES public.message(id) ON UPDATE CASCADE ON DELETE CASCADE,
+    account_id integer NOT NULL REFERENCES public.account(id) ON UPDATE CASCADE ON DELETE CASCADE,
+    PRIMARY KEY (message_id, account_id)
+);
@@ -0,0 +1,7 @@
+DROP TRIGGER vote_notify ON public.vote;
+DROP TRIGGER post_notify ON public.post;
+DROP TABLE public.notification;
+
+DROP FUNCTION public.notify_new_notification();
+DROP FUNCTION public.notify_new_post();
+DROP FUNCTION public.notify_vote_change();
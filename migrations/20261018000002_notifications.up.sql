@@ -0,0 +1,53 @@
+-- the triggers announce new rows on postgres channels, the server passes them on as server-sent events
+CREATE FUNCTION public.notify_new_notification() RETURNS trigger
+    LANGUAGE plpgsql
+    AS $$
+BEGIN
+	PERFORM pg_notify('new_notification', json_build_object('id', NEW.id, 'account_id', NEW.account_id)::text);
+	RETURN NULL;
+END;
+$$;
+
+CREATE FUNCTION public.notify_new_post() RETURNS trigger
+    LANGUAGE plpgsql
+    AS $$
+BEGIN
+	PERFORM pg_notify('new_post', NEW.id::text);
+	RETURN NULL;
+END;
+$$;
+
+CREATE FUNCTION public.notify_vote_change() RETURNS trigger
+    LANGUAGE plpgsql
+    AS $$
+DECLARE
+	changed_post_id bigint;
+BEGIN
+	IF TG_OP = 'DELETE' THEN
+		changed_post_id := OLD.post_id;
+	ELSE
+		changed_post_id := NEW.post_id;
+	END IF;
+	PERFORM pg_notify('vote_change', json_build_object(
+		'post_id', changed_post_id,
+		'votes', (SELECT COUNT(*) FROM public.vote WHERE post_id = changed_post_id)
+	)::text);
+	RETURN NULL;
+END;
+$$;
+
+CREATE TABLE public.notification (
+    id bigserial PRIMARY KEY,
+    account_id integer NOT NULL REFERENCES public.account(id) ON UPDATE CASCADE ON DELETE CASCADE,
+    actor_id integer NOT NULL REFERENCES public.account(id) ON UPDATE CASCADE ON DELETE CASCADE,
+    kind character varying(10) NOT NULL
+        CONSTRAINT notification_kind_check CHECK (kind IN ('like', 'reply', 'follow')),
+    post_id bigint REFERENCES public.post(id) ON UPDATE CASCADE ON DELETE CASCADE,
+    create_time timestamp without time zone DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    seen boolean DEFAULT false NOT NULL
+);
+CREATE INDEX notification_account_id_idx ON public.notification USING btree (account_id, create_time);
+
+CREATE TRIGGER notification_notify AFTER INSERT ON public.notification FOR EACH ROW EXECUTE FUNCTION public.notify_new_notification();
+CREATE TRIGGER post_notify AFTER INSERT ON public.post FOR EACH ROW EXECUTE FUNCTION public.notify_new_post();
+CREATE TRIGGER vote_notify AFTER INSERT OR DELETE ON public.vote FOR EACH ROW EXECUTE FUNCTION public.notify_vote_change();
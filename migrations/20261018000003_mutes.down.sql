@@ -0,0 +1 @@
+DROP TABLE public.mute, public.mute_thread, public.mute_word;
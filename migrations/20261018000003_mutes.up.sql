@@ -0,0 +1,21 @@
+-- muted accounts, threads and phrases only hide things from the one who muted them
+CREATE TABLE public.mute (
+    user_id integer NOT NULL REFERENCES public.account(id) ON UPDATE CASCADE ON DELETE CASCADE,
+    muted_id integer NOT NULL REFERENCES public.account(id) ON UPDATE CASCADE ON DELETE CASCADE,
+    PRIMARY KEY (user_id, muted_id)
+);
+
+CREATE TABLE public.mute_thread (
+    user_id integer NOT NULL REFERENCES public.account(id) ON UPDATE CASCADE ON DELETE CASCADE,
+    post_id bigint NOT NULL REFERENCES public.post(id) ON UPDATE CASCADE ON DELETE CASCADE,
+    PRIMARY KEY (user_id, post_id)
+);
+
+CREATE TABLE public.mute_word (
+    id serial PRIMARY KEY,
+    user_id integer NOT NULL REFERENCES public.account(id) ON UPDATE CASCADE ON DELETE CASCADE,
+    phrase character varying(100) NOT NULL,
+    create_time timestamp without time zone DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    expire_time timestamp without time zone
+);
+CREATE INDEX mute_word_user_id_idx ON public.mute_word USING btree (user_id);
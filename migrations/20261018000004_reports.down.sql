@@ -0,0 +1,12 @@
+DELETE FROM public.notification WHERE kind = 'report';
+ALTER TABLE public.notification
+    DROP COLUMN report_id,
+    DROP CONSTRAINT notification_kind_check,
+    ADD CONSTRAINT notification_kind_check CHECK (kind IN ('like', 'reply', 'follow'));
+
+DROP TABLE public.report;
+
+ALTER TABLE public.account
+    DROP COLUMN moderator,
+    DROP COLUMN suspension_reason,
+    DROP COLUMN suspension_end;
@@ -0,0 +1,27 @@
+-- reports go to a moderation queue, the reporter is notified once one is handled
+ALTER TABLE public.account
+    ADD COLUMN moderator boolean DEFAULT false NOT NULL,
+    ADD COLUMN suspension_reason text,
+    ADD COLUMN suspension_end timestamp without time zone;
+
+CREATE TABLE public.report (
+    id serial PRIMARY KEY,
+    reporter_id integer NOT NULL REFERENCES public.account(id) ON UPDATE CASCADE ON DELETE CASCADE,
+    account_id integer NOT NULL REFERENCES public.account(id) ON UPDATE CASCADE ON DELETE CASCADE,
+    post_id bigint REFERENCES public.post(id) ON UPDATE CASCADE ON DELETE SET NULL,
+    reason character varying(20) NOT NULL
+        CONSTRAINT report_reason_check CHECK (reason IN ('spam', 'harassment', 'hate', 'violence', 'impersonation', 'other')),
+    details text,
+    status character varying(10) DEFAULT 'open' NOT NULL
+        CONSTRAINT report_status_check CHECK (status IN ('open', 'resolved', 'dismissed')),
+    action text,
+    moderator_id integer REFERENCES public.account(id) ON UPDATE CASCADE ON DELETE SET NULL,
+    create_time timestamp without time zone DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    resolve_time timestamp without time zone
+);
+CREATE INDEX report_status_idx ON public.report USING btree (status, create_time);
+
+ALTER TABLE public.notification
+    ADD COLUMN report_id integer REFERENCES public.report(id) ON UPDATE CASCADE ON DELETE CASCADE,
+    DROP CONSTRAINT notification_kind_check,
+    ADD CONSTRAINT notification_kind_check CHECK (kind IN ('like', 'reply', 'follow', 'report'));
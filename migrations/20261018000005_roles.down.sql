@@ -0,0 +1,5 @@
+DROP TABLE public.moderation_log;
+
+ALTER TABLE public.account ADD COLUMN moderator boolean DEFAULT false NOT NULL;
+UPDATE public.account SET moderator = role <> 'user';
+ALTER TABLE public.account DROP COLUMN role;
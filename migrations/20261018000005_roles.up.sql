@@ -0,0 +1,16 @@
+-- the moderator flag becomes a role, and what moderators and admins do is logged
+ALTER TABLE public.account
+    ADD COLUMN role character varying(10) DEFAULT 'user' NOT NULL
+    CONSTRAINT account_role_check CHECK (role IN ('user', 'moderator', 'admin'));
+UPDATE public.account SET role = 'moderator' WHERE moderator;
+ALTER TABLE public.account DROP COLUMN moderator;
+
+CREATE TABLE public.moderation_log (
+    id serial PRIMARY KEY,
+    moderator_id integer REFERENCES public.account(id) ON UPDATE CASCADE ON DELETE SET NULL,
+    action character varying(20) NOT NULL,
+    target_account_id integer REFERENCES public.account(id) ON UPDATE CASCADE ON DELETE SET NULL,
+    target_post_id bigint,
+    details text NOT NULL,
+    create_time timestamp without time zone DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
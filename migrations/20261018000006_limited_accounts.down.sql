@@ -0,0 +1 @@
+ALTER TABLE public.account DROP COLUMN limited;
@@ -0,0 +1,2 @@
+-- posts of limited accounts only show up in the feeds of their followers
+ALTER TABLE public.account ADD COLUMN limited boolean DEFAULT false NOT NULL;
@@ -0,0 +1,10 @@
+-- failed logins and registrations per address or handle, for the backoff and lockout
+CREATE TABLE public.login_attempt (
+    kind character varying(10) NOT NULL
+        CONSTRAINT login_attempt_kind_check CHECK (kind IN ('ip', 'handle', 'register')),
+    key character varying(100) NOT NULL,
+    failures integer DEFAULT 0 NOT NULL,
+    last_failure timestamp without time zone DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    blocked_until timestamp without time zone,
+    PRIMARY KEY (kind, key)
+);
@@ -0,0 +1,5 @@
+DROP TABLE public.recovery_code;
+
+ALTER TABLE public.account
+    DROP COLUMN totp_secret,
+    DROP COLUMN totp_last_step;
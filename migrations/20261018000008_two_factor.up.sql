@@ -0,0 +1,10 @@
+-- the step of the last accepted code is kept so a code can't be used twice
+ALTER TABLE public.account
+    ADD COLUMN totp_secret bytea,
+    ADD COLUMN totp_last_step bigint;
+
+CREATE TABLE public.recovery_code (
+    account_id integer NOT NULL REFERENCES public.account(id) ON UPDATE CASCADE ON DELETE CASCADE,
+    code_hash bytea NOT NULL,
+    PRIMARY KEY (account_id, code_hash)
+);
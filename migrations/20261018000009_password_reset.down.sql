@@ -0,0 +1,10 @@
+DELETE FROM public.login_attempt WHERE kind = 'reset';
+ALTER TABLE public.login_attempt
+    DROP CONSTRAINT login_attempt_kind_check,
+    ADD CONSTRAINT login_attempt_kind_check CHECK (kind IN ('ip', 'handle', 'register'));
+
+DROP TABLE public.password_reset;
+
+ALTER TABLE public.account
+    DROP COLUMN email,
+    DROP COLUMN session_epoch;
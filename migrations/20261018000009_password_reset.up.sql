@@ -0,0 +1,15 @@
+-- bumping the session epoch signs an account out everywhere after its password changes
+ALTER TABLE public.account
+    ADD COLUMN email character varying(254),
+    ADD COLUMN session_epoch integer DEFAULT 0 NOT NULL;
+CREATE UNIQUE INDEX account_email_idx ON public.account USING btree (lower((email)::text));
+
+CREATE TABLE public.password_reset (
+    token_hash bytea PRIMARY KEY,
+    account_id integer NOT NULL REFERENCES public.account(id) ON UPDATE CASCADE ON DELETE CASCADE,
+    expire_time timestamp without time zone NOT NULL
+);
+
+ALTER TABLE public.login_attempt
+    DROP CONSTRAINT login_attempt_kind_check,
+    ADD CONSTRAINT login_attempt_kind_check CHECK (kind IN ('ip', 'handle', 'register', 'reset'));
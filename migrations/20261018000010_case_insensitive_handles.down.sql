@@ -0,0 +1 @@
+DROP INDEX public.account_handle_lower_idx;
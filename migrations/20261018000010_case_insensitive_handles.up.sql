@@ -0,0 +1,2 @@
+-- handles that only differ in case would be mistaken for each other
+CREATE UNIQUE INDEX account_handle_lower_idx ON public.account USING btree (lower((handle)::text));
@@ -0,0 +1,3 @@
+DROP TABLE public.old_handle;
+
+ALTER TABLE public.account DROP COLUMN handle_change_time;
@@ -0,0 +1,9 @@
+-- an old handle redirects to the account and can't be taken by anyone else until it's released
+ALTER TABLE public.account ADD COLUMN handle_change_time timestamp without time zone;
+
+CREATE TABLE public.old_handle (
+    handle character varying(25) NOT NULL,
+    account_id integer NOT NULL REFERENCES public.account(id) ON UPDATE CASCADE ON DELETE CASCADE,
+    release_time timestamp without time zone NOT NULL
+);
+CREATE UNIQUE INDEX old_handle_lower_idx ON public.old_handle USING btree (lower((handle)::text));
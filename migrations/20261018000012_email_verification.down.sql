@@ -0,0 +1,6 @@
+DELETE FROM public.login_attempt WHERE kind = 'verify';
+ALTER TABLE public.login_attempt
+    DROP CONSTRAINT login_attempt_kind_check,
+    ADD CONSTRAINT login_attempt_kind_check CHECK (kind IN ('ip', 'handle', 'register', 'reset'));
+
+ALTER TABLE public.account DROP COLUMN email_verified;
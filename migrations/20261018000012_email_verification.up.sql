@@ -0,0 +1,5 @@
+ALTER TABLE public.account ADD COLUMN email_verified boolean DEFAULT false NOT NULL;
+
+ALTER TABLE public.login_attempt
+    DROP CONSTRAINT login_attempt_kind_check,
+    ADD CONSTRAINT login_attempt_kind_check CHECK (kind IN ('ip', 'handle', 'register', 'reset', 'verify'));
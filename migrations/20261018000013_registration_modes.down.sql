@@ -0,0 +1,3 @@
+DROP TABLE public.invite;
+
+ALTER TABLE public.account DROP COLUMN pending;
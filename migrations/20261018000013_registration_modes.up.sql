@@ -0,0 +1,11 @@
+-- pending accounts wait for an admin to approve them, invite codes let people skip that
+ALTER TABLE public.account ADD COLUMN pending boolean DEFAULT false NOT NULL;
+
+CREATE TABLE public.invite (
+    code character varying(16) PRIMARY KEY,
+    creator_id integer NOT NULL REFERENCES public.account(id) ON UPDATE CASCADE ON DELETE CASCADE,
+    max_uses integer NOT NULL,
+    uses integer DEFAULT 0 NOT NULL,
+    expire_time timestamp without time zone,
+    create_time timestamp without time zone DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
@@ -0,0 +1,2 @@
+DROP INDEX public.account_email_idx;
+CREATE UNIQUE INDEX account_email_idx ON public.account USING btree (lower((email)::text));
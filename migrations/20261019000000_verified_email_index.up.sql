@@ -0,0 +1,3 @@
+-- only a verified address belongs to an account, anyone can claim one until it is verified
+DROP INDEX public.account_email_idx;
+CREATE UNIQUE INDEX account_email_idx ON public.account USING btree (lower((email)::text)) WHERE email_verified;
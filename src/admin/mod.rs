@@ -0,0 +1,376 @@
+mod seed;
+
+use std::io::{self, BufRead, IsTerminal, Write};
+
+use sqlx::PgPool;
+
+use crate::{
+	config::DatabaseConfig,
+	database::{
+		self,
+		account::{Account, AccountError},
+		attempt, migrate,
+		moderation::LogEntry,
+		post::Post,
+		stats::Stats,
+		types::{LogAction, Role},
+	},
+	validation,
+};
+use seed::SeedOptions;
+
+pub const USAGE: &str = "usage: my-twitter [command]
+
+without a command the server is started
+
+commands:
+  create-account <handle> [user|moderator|admin]   password read from stdin
+  reset-password <handle>                          password read from stdin
+  set-role <handle> <user|moderator|admin>
+  suspend <handle> <days|forever> <reason>
+  unsuspend <handle>
+  delete-post <id>
+  prune-attempts
+  migrate [run|revert|status]
+  stats
+  seed [--accounts N] [--follows N] [--posts N] [--replies N] [--likes N] [--seed N]";
+
+/// Something to do from the command line instead of starting the server
+#[derive(Debug, PartialEq)]
+pub enum Command {
+	CreateAccount {
+		handle: String,
+		password: String,
+		role: Role,
+	},
+	ResetPassword {
+		handle: String,
+		password: String,
+	},
+	SetRole {
+		handle: String,
+		role: Role,
+	},
+	Suspend {
+		handle: String,
+		days: Option<u32>,
+		reason: String,
+	},
+	Unsuspend {
+		handle: String,
+	},
+	DeletePost {
+		id: i64,
+	},
+	PruneAttempts,
+	Migrate,
+	RevertMigration,
+	MigrationStatus,
+	Stats,
+	Seed(SeedOptions),
+}
+impl Command {
+	/// Reads the command from the arguments, asking for a password only when the command needs one
+	pub fn parse(
+		args: &[&str],
+		read_password: impl FnOnce() -> Result<String, String>,
+	) -> Result<Self, String> {
+		let command = match args {
+			["create-account", handle] => Self::CreateAccount {
+				handle: handle.to_string(),
+				password: String::new(),
+				role: Role::User,
+			},
+			["create-account", handle, role] => Self::CreateAccount {
+				handle: handle.to_string(),
+				password: String::new(),
+				role: parse_role(role)?,
+			},
+			["reset-password", handle] => Self::ResetPassword {
+				handle: handle.to_string(),
+				password: String::new(),
+			},
+			["set-role", handle, role] => Self::SetRole {
+				handle: handle.to_string(),
+				role: parse_role(role)?,
+			},
+			["suspend", handle, days, reason @ ..] if !reason.is_empty() => Self::Suspend {
+				handle: handle.to_string(),
+				days: match *days {
+					"forever" => None,
+					days => Some(
+						days.parse()
+							.ok()
+							.filter(|&d| d > 0)
+							.ok_or(format!("{days} isn't a number of days"))?,
+					),
+				},
+				reason: reason.join(" "),
+			},
+			["unsuspend", handle] => Self::Unsuspend {
+				handle: handle.to_string(),
+			},
+			["delete-post", id] => Self::DeletePost {
+				id: id.parse().map_err(|_| format!("{id} isn't a post id"))?,
+			},
+			["prune-attempts"] => Self::PruneAttempts,
+			["migrate"] | ["migrate", "run"] => Self::Migrate,
+			["migrate", "revert"] => Self::RevertMigration,
+			["migrate", "status"] => Self::MigrationStatus,
+			["stats"] => Self::Stats,
+			["seed", options @ ..] => Self::Seed(SeedOptions::parse(options)?),
+			_ => return Err(USAGE.to_string()),
+		};
+
+		Ok(match command {
+			Self::CreateAccount { handle, role, .. } => Self::CreateAccount {
+				handle,
+				password: read_password()?,
+				role,
+			},
+			Self::ResetPassword { handle, .. } => Self::ResetPassword {
+				handle,
+				password: read_password()?,
+			},
+			command => command,
+		})
+	}
+
+	/// Runs the command and returns what to print
+	pub async fn run(self, pool: &PgPool) -> Result<String, String> {
+		match self {
+			Self::CreateAccount {
+				handle,
+				password,
+				role,
+			} => {
+				let account = Account::register(pool, &handle, &password, None, false)
+					.await
+					.map_err(account_error)?;
+				if role != Role::User {
+					account.set_role(pool, role).await.map_err(db_error)?;
+					let details = format!("{} -> {}", account.role.as_str(), role.as_str());
+					LogEntry::log_command(
+						pool,
+						LogAction::SetRole,
+						Some(account.id),
+						None::<i64>,
+						&details,
+					)
+					.await
+					.map_err(db_error)?;
+				}
+				Ok(format!(
+					"Created @{} ({}) with id {}",
+					account.handle,
+					role.as_str(),
+					i32::from(account.id)
+				))
+			}
+			Self::ResetPassword { handle, password } => {
+				let account = find(pool, &handle).await?;
+				validation::validate_password(&password, &account.handle)
+					.map_err(|e| e.message())?;
+				account
+					.set_password(pool, &password)
+					.await
+					.map_err(db_error)?;
+				Ok(format!(
+					"Changed the password of @{}, their sessions are signed out",
+					account.handle
+				))
+			}
+			Self::SetRole { handle, role } => {
+				let account = find(pool, &handle).await?;
+				account.set_role(pool, role).await.map_err(db_error)?;
+				let details = format!("{} -> {}", account.role.as_str(), role.as_str());
+				LogEntry::log_command(
+					pool,
+					LogAction::SetRole,
+					Some(account.id),
+					None::<i64>,
+					&details,
+				)
+				.await
+				.map_err(db_error)?;
+				Ok(format!("@{} is now {}", account.handle, role.as_str()))
+			}
+			Self::Suspend {
+				handle,
+				days,
+				reason,
+			} => {
+				let account = find(pool, &handle).await?;
+				account
+					.suspend(pool, &reason, days)
+					.await
+					.map_err(db_error)?;
+				let action = match days {
+					Some(1) => "Account suspended for 1 day".to_string(),
+					Some(days) => format!("Account suspended for {days} days"),
+					None => "Account suspended".to_string(),
+				};
+				LogEntry::log_command(
+					pool,
+					LogAction::Suspend,
+					Some(account.id),
+					None::<i64>,
+					&format!("{action}: {reason}"),
+				)
+				.await
+				.map_err(db_error)?;
+				Ok(format!("{action}: @{}", account.handle))
+			}
+			Self::Unsuspend { handle } => {
+				let account = find(pool, &handle).await?;
+				account.lift_restrictions(pool).await.map_err(db_error)?;
+				LogEntry::log_command(
+					pool,
+					LogAction::Lift,
+					Some(account.id),
+					None::<i64>,
+					"Suspension and limits lifted",
+				)
+				.await
+				.map_err(db_error)?;
+				Ok(format!("Lifted the restrictions on @{}", account.handle))
+			}
+			Self::DeletePost { id } => {
+				let post = Post::find_by_id(pool, id, None::<i32>)
+					.await
+					.map_err(db_error)?
+					.ok_or(format!("There is no post {id}"))?;
+				let (author_id, body) = (post.author_id, post.body.clone());
+				post.delete(pool).await.map_err(db_error)?;
+				LogEntry::log_command(
+					pool,
+					LogAction::DeletePost,
+					Some(author_id),
+					Some(id),
+					&body,
+				)
+				.await
+				.map_err(db_error)?;
+				Ok(format!("Deleted post {id}"))
+			}
+			Self::PruneAttempts => {
+				let forgotten = attempt::forget_expired(pool).await.map_err(db_error)?;
+				Ok(format!("Forgot {forgotten} expired failed login counters"))
+			}
+			Self::Migrate => {
+				migrate::run(pool).await.map_err(|e| e.to_string())?;
+				Ok("Database is up to date".to_string())
+			}
+			Self::RevertMigration => {
+				match migrate::revert_last(pool)
+					.await
+					.map_err(|e| e.to_string())?
+				{
+					Some(version) => Ok(format!("Reverted migration {version}")),
+					None => Ok("No migration to revert".to_string()),
+				}
+			}
+			Self::MigrationStatus => {
+				let lines: Vec<String> = migrate::status(pool)
+					.await
+					.map_err(|e| e.to_string())?
+					.into_iter()
+					.map(|m| {
+						let state = if m.applied { "applied" } else { "pending" };
+						format!("{} {} ({state})", m.version, m.description)
+					})
+					.collect();
+				Ok(lines.join("\n"))
+			}
+			Self::Seed(options) => seed::seed(pool, options).await,
+			Self::Stats => {
+				let s = Stats::get(pool).await.map_err(db_error)?;
+				Ok(format!(
+					"accounts      {}
+  pending     {}
+  suspended   {}
+  moderators  {}
+  admins      {}
+posts         {}
+replies       {}
+likes         {}
+follows       {}
+messages      {}
+open reports  {}",
+					s.accounts,
+					s.pending,
+					s.suspended,
+					s.moderators,
+					s.admins,
+					s.posts,
+					s.replies,
+					s.likes,
+					s.follows,
+					s.messages,
+					s.open_reports
+				))
+			}
+		}
+	}
+}
+
+/// Runs the command given on the command line against the configured database
+pub async fn run(args: &[&str]) -> Result<(), String> {
+	let command = Command::parse(args, read_password)?;
+
+	let config = DatabaseConfig::from_figment(&rocket::Config::figment())?;
+	let pool = database::connect(&config)
+		.await
+		.map_err(|e| format!("Could not connect to the database: {e}"))?;
+
+	let output = command.run(&pool).await?;
+	println!("{output}");
+	Ok(())
+}
+
+// from stdin rather than the arguments, so it doesn't end up in the shell history
+fn read_password() -> Result<String, String> {
+	let stdin = io::stdin();
+	if stdin.is_terminal() {
+		eprint!("Password: ");
+		io::stderr().flush().ok();
+	}
+	let mut password = String::new();
+	stdin
+		.lock()
+		.read_line(&mut password)
+		.map_err(|e| format!("Could not read the password: {e}"))?;
+	let password = password.trim_end_matches(['\r', '\n']);
+	match password.is_empty() {
+		true => Err("No password given".to_string()),
+		false => Ok(password.to_string()),
+	}
+}
+
+fn parse_role(role: &str) -> Result<Role, String> {
+	match role {
+		"user" => Ok(Role::User),
+		"moderator" => Ok(Role::Moderator),
+		"admin" => Ok(Role::Admin),
+		_ => Err(format!("{role} isn't a role, use user, moderator or admin")),
+	}
+}
+
+async fn find(pool: &PgPool, handle: &str) -> Result<Account, String> {
+	Account::find_by_handle(pool, handle, None::<i32>)
+		.await
+		.map_err(db_error)?
+		.ok_or(format!("There is no account @{handle}"))
+}
+
+fn account_error(e: AccountError<'_>) -> String {
+	match e {
+		AccountError::Invalid(e) => e.message(),
+		AccountError::Sqlx(e) => db_error(e),
+		_ => "Could not create the account".to_string(),
+	}
+}
+
+fn db_error(e: sqlx::Error) -> String {
+	format!("Database error: {e}")
+}
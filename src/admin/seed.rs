@@ -0,0 +1,272 @@
+use std::collections::HashSet;
+
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use sqlx::PgPool;
+
+use crate::database::{
+	account::{Account, AccountError},
+	types::PostId,
+};
+
+/// Every seeded account can be signed into with this
+pub const PASSWORD: &str = "Seed-pass1";
+
+const ADJECTIVES: &[&str] = &[
+	"quiet", "sunny", "brave", "lazy", "clever", "rusty", "mellow", "curious", "fuzzy", "grumpy",
+	"happy", "sleepy", "swift", "tiny", "wild", "cosmic",
+];
+const NOUNS: &[&str] = &[
+	"otter", "falcon", "badger", "lemon", "comet", "walrus", "maple", "pixel", "panda", "kettle",
+	"raven", "turnip", "cactus", "yak", "heron", "bagel",
+];
+const WORDS: &[&str] = &[
+	"the",
+	"a",
+	"feed",
+	"coffee",
+	"today",
+	"really",
+	"just",
+	"deploy",
+	"weekend",
+	"rain",
+	"cat",
+	"thread",
+	"why",
+	"is",
+	"was",
+	"never",
+	"always",
+	"think",
+	"new",
+	"old",
+	"code",
+	"bug",
+	"fixed",
+	"broke",
+	"again",
+	"morning",
+	"night",
+	"train",
+	"late",
+	"pizza",
+	"music",
+	"book",
+	"finally",
+	"nobody",
+	"everyone",
+	"likes",
+	"this",
+	"that",
+	"my",
+	"your",
+	"lol",
+	"honestly",
+	"tiny",
+	"huge",
+	"postgres",
+	"rust",
+	"walk",
+	"garden",
+	"tea",
+	"meeting",
+	"cancelled",
+];
+
+/// How much to generate, the same seed always gives the same accounts, posts and likes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SeedOptions {
+	pub accounts: usize,
+	pub follows: usize,
+	pub posts: usize,
+	pub replies: usize,
+	pub likes: usize,
+	pub seed: u64,
+}
+impl Default for SeedOptions {
+	fn default() -> Self {
+		Self {
+			accounts: 50,
+			follows: 500,
+			posts: 200,
+			replies: 400,
+			likes: 1500,
+			seed: 1,
+		}
+	}
+}
+impl SeedOptions {
+	pub fn parse(args: &[&str]) -> Result<Self, String> {
+		let mut options = Self::default();
+		for pair in args.chunks(2) {
+			let (flag, value) = match pair {
+				[flag, value] => (*flag, *value),
+				[flag] => return Err(format!("{flag} needs a value")),
+				_ => unreachable!("chunks of two"),
+			};
+			let number = || {
+				value
+					.parse::<usize>()
+					.map_err(|_| format!("{value} isn't a number for {flag}"))
+			};
+			match flag {
+				"--accounts" => options.accounts = number()?,
+				"--follows" => options.follows = number()?,
+				"--posts" => options.posts = number()?,
+				"--replies" => options.replies = number()?,
+				"--likes" => options.likes = number()?,
+				"--seed" => {
+					options.seed = value.parse().map_err(|_| format!("{value} isn't a seed"))?
+				}
+				_ => return Err(format!("{flag} isn't an option of seed")),
+			}
+		}
+		if options.accounts < 2 {
+			return Err("seed needs at least 2 accounts".to_string());
+		}
+		Ok(options)
+	}
+}
+
+/// Picks indices with a probability proportional to their weight, weights can be added as it goes
+#[derive(Default)]
+struct Weighted {
+	totals: Vec<f64>,
+}
+impl Weighted {
+	fn push(&mut self, weight: f64) {
+		let total = self.totals.last().copied().unwrap_or(0.0);
+		self.totals.push(total + weight);
+	}
+	fn sample(&self, rng: &mut StdRng) -> usize {
+		let total = self.totals.last().copied().unwrap_or(0.0);
+		let point = rng.gen_range(0.0..total);
+		self.totals.partition_point(|&t| t <= point)
+	}
+	fn weight(&self, i: usize) -> f64 {
+		match i {
+			0 => self.totals[0],
+			i => self.totals[i] - self.totals[i - 1],
+		}
+	}
+}
+
+// a few accounts get most of the attention, like on any real instance
+fn zipf(count: usize, rng: &mut StdRng) -> Weighted {
+	let mut ranks: Vec<usize> = (0..count).collect();
+	ranks.shuffle(rng);
+	let mut weights = Weighted::default();
+	for rank in ranks {
+		weights.push(1.0 / (rank as f64 + 1.0).powf(1.1));
+	}
+	weights
+}
+
+fn sentence(rng: &mut StdRng) -> String {
+	let length = rng.gen_range(3..=18);
+	let words: Vec<&str> = (0..length)
+		.map(|_| *WORDS.choose(rng).expect("words"))
+		.collect();
+	let text = words.join(" ");
+	let end = *[".", ".", ".", "!", "?"].choose(rng).expect("ends");
+	text[..1].to_uppercase() + &text[1..] + end
+}
+
+/// Fills the database with accounts following each other, posts, reply threads and likes
+pub async fn seed(pool: &PgPool, options: SeedOptions) -> Result<String, String> {
+	let mut rng = StdRng::seed_from_u64(options.seed);
+	let db_error = |e: sqlx::Error| format!("Database error: {e}");
+
+	let mut accounts = Vec::with_capacity(options.accounts);
+	for i in 0..options.accounts {
+		let handle = format!(
+			"{}_{}{i}",
+			ADJECTIVES.choose(&mut rng).expect("adjectives"),
+			NOUNS.choose(&mut rng).expect("nouns"),
+		);
+		match Account::register(pool, &handle, PASSWORD, None, false).await {
+			Ok(account) => accounts.push(account),
+			Err(AccountError::Invalid(e)) => {
+				return Err(format!(
+					"Could not create @{handle}: {}, seed an empty database or pick another --seed",
+					e.message()
+				))
+			}
+			Err(AccountError::Sqlx(e)) => return Err(db_error(e)),
+			Err(_) => return Err(format!("Could not create @{handle}")),
+		}
+	}
+
+	// who gets followed and liked, and who does the posting
+	let popularity = zipf(accounts.len(), &mut rng);
+	let activity = zipf(accounts.len(), &mut rng);
+
+	let follows = options.follows.min(accounts.len() * (accounts.len() - 1));
+	let mut followed = HashSet::new();
+	// the weights make some pairs much more likely, so duplicates are skipped up to a limit
+	for _ in 0..follows * 20 {
+		if followed.len() == follows {
+			break;
+		}
+		let (user, target) = (activity.sample(&mut rng), popularity.sample(&mut rng));
+		if user == target || !followed.insert((user, target)) {
+			continue;
+		}
+		accounts[user]
+			.follow(pool, accounts[target].id)
+			.await
+			.map_err(db_error)?;
+	}
+
+	// with the index of the author, so nobody likes their own posts
+	let mut posts: Vec<(PostId, usize)> = Vec::new();
+	let mut parents = Weighted::default();
+	for _ in 0..options.posts {
+		let author = activity.sample(&mut rng);
+		let post = accounts[author]
+			.post(pool, &sentence(&mut rng))
+			.await
+			.map_err(db_error)?;
+		posts.push((post.id, author));
+		parents.push(popularity.weight(author));
+	}
+
+	// replies can be replied to as well, which makes the threads nest, popular authors get more of them
+	let mut replies = 0;
+	while replies < options.replies && !posts.is_empty() {
+		let (parent, _) = posts[parents.sample(&mut rng)];
+		let author = activity.sample(&mut rng);
+		let reply = accounts[author]
+			.reply(pool, &sentence(&mut rng), parent)
+			.await
+			.map_err(db_error)?;
+		posts.push((reply.id, author));
+		parents.push(popularity.weight(author));
+		replies += 1;
+	}
+
+	let likes = options.likes.min((accounts.len() - 1) * posts.len());
+	let mut liked = HashSet::new();
+	for _ in 0..likes * 20 {
+		if liked.len() == likes {
+			break;
+		}
+		let (voter, post) = (activity.sample(&mut rng), parents.sample(&mut rng));
+		let (post_id, author) = posts[post];
+		if voter == author || !liked.insert((voter, post)) {
+			continue;
+		}
+		accounts[voter]
+			.add_vote(pool, post_id)
+			.await
+			.map_err(db_error)?;
+	}
+
+	Ok(format!(
+		"Seeded {} accounts, {} follows, {} posts, {replies} replies and {} likes, every account has the password {PASSWORD}",
+		accounts.len(),
+		followed.len(),
+		options.posts,
+		liked.len(),
+	))
+}
@@ -0,0 +1,123 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use rocket::figment::Figment;
+use serde::Deserialize;
+
+/// The `[database]` table of Rocket.toml
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct DatabaseConfig {
+	// falls back to DATABASE_URL, which sqlx also needs to check the queries at compile time
+	pub url: Option<String>,
+	pub max_connections: u32,
+	pub min_connections: u32,
+	/// Seconds to wait for a connection before a query fails
+	pub acquire_timeout: u64,
+	/// Seconds an unused connection is kept open, never closed if unset
+	pub idle_timeout: Option<u64>,
+	/// Queries taking longer than this many milliseconds are logged as warnings
+	pub slow_query_ms: u64,
+}
+impl Default for DatabaseConfig {
+	fn default() -> Self {
+		Self {
+			url: None,
+			max_connections: 10,
+			min_connections: 0,
+			acquire_timeout: 5,
+			idle_timeout: Some(10 * 60),
+			slow_query_ms: 250,
+		}
+	}
+}
+impl DatabaseConfig {
+	pub fn from_figment(figment: &Figment) -> Result<Self, String> {
+		figment
+			.focus("database")
+			.extract()
+			.map_err(|e| format!("Invalid database configuration: {e}"))
+	}
+}
+
+/// The `[templates]` table of Rocket.toml
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct TemplateConfig {
+	pub dir: String,
+}
+impl Default for TemplateConfig {
+	fn default() -> Self {
+		Self {
+			dir: "./templates".into(),
+		}
+	}
+}
+impl TemplateConfig {
+	pub fn from_figment(figment: &Figment) -> Result<Self, String> {
+		figment
+			.focus("templates")
+			.extract()
+			.map_err(|e| format!("Invalid template configuration: {e}"))
+	}
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+	#[default]
+	Pretty,
+	Json,
+}
+
+/// The `[tracing]` table of Rocket.toml
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct TracingConfig {
+	pub format: LogFormat,
+	// the same syntax as RUST_LOG, which takes precedence
+	pub filter: String,
+}
+impl Default for TracingConfig {
+	fn default() -> Self {
+		Self {
+			format: LogFormat::Pretty,
+			filter:
+				"info,rocket::server=error,sqlx::postgres::notice=warn,sqlx_postgres::options=error"
+					.into(),
+		}
+	}
+}
+impl TracingConfig {
+	pub fn from_figment(figment: &Figment) -> Result<Self, String> {
+		figment
+			.focus("tracing")
+			.extract()
+			.map_err(|e| format!("Invalid tracing configuration: {e}"))
+	}
+}
+
+/// The `[metrics]` table of Rocket.toml
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct MetricsConfig {
+	/// Client addresses that can read `/metrics` without the token
+	pub allow: Vec<IpAddr>,
+	/// Sent as `Authorization: Bearer <token>` from anywhere else
+	pub token: Option<String>,
+}
+impl Default for MetricsConfig {
+	fn default() -> Self {
+		Self {
+			allow: vec![Ipv4Addr::LOCALHOST.into(), Ipv6Addr::LOCALHOST.into()],
+			token: None,
+		}
+	}
+}
+impl MetricsConfig {
+	pub fn from_figment(figment: &Figment) -> Result<Self, String> {
+		figment
+			.focus("metrics")
+			.extract()
+			.map_err(|e| format!("Invalid metrics configuration: {e}"))
+	}
+}
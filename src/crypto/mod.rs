@@ -1,5 +1,52 @@
-use hmac_sha512::Hash;
+pub mod totp;
+
+use std::sync::LazyLock;
+
+use hmac_sha512::{Hash, HMAC};
 use rand::Rng;
+use tracing::warn;
+
+// links signed with a random key stop working when the server restarts, so production should set SIGNING_KEY
+static SIGNING_KEY: LazyLock<Vec<u8>> = LazyLock::new(|| match dotenvy::var("SIGNING_KEY") {
+	Ok(key) => key.into_bytes(),
+	Err(_) => {
+		warn!("SIGNING_KEY isn't set, signed links will only work until the server restarts");
+		rand::thread_rng().gen::<[u8; 32]>().to_vec()
+	}
+});
+
+/// A random hex token for links and forms
+pub fn generate_token() -> String {
+	let bytes: [u8; 32] = rand::thread_rng().gen();
+	bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// A hex signature proving the message was made by this server
+pub fn sign(message: &str) -> String {
+	HMAC::mac(message.as_bytes(), &*SIGNING_KEY)
+		.iter()
+		.map(|b| format!("{b:02x}"))
+		.collect()
+}
+pub fn verify_signature(message: &str, signature: &str) -> bool {
+	tokens_match(&sign(message), signature)
+}
+
+// comparing every byte so the time taken doesn't leak how much of the token matched
+pub fn tokens_match(a: &str, b: &str) -> bool {
+	a.len() == b.len()
+		&& a.bytes()
+			.zip(b.bytes())
+			.fold(0, |acc, (x, y)| acc | (x ^ y))
+			== 0
+}
+
+// tokens are stored hashed so a database leak doesn't hand out working links
+pub fn hash_token(token: &str) -> [u8; 64] {
+	let mut hasher = Hash::new();
+	hasher.update(token.as_bytes());
+	hasher.finalize()
+}
 
 pub fn encode_password(password: &str, max_iterations: u8) -> [u8; 64] {
 	let mut hasher = Hash::new();
@@ -0,0 +1,88 @@
+use rocket::{
+	fairing::{Fairing, Info, Kind},
+	http::{uri::Origin, Method, Status},
+	Data, Request,
+};
+
+use crate::{crypto, error::AppError};
+
+// the token lives in a private cookie and has to be echoed back in a form field or header
+pub const TOKEN_COOKIE: &str = "csrf_token";
+pub const TOKEN_FIELD: &str = "csrf_token";
+pub const TOKEN_HEADER: &str = "X-CSRF-Token";
+
+// the token field has to be within this many bytes of the start of the form body,
+// which is why every form in the templates has it as one of its first fields
+const PEEK_LIMIT: usize = 512;
+
+const REJECTED_PATH: &str = "/csrf_rejected";
+const TOO_LATE_PATH: &str = "/csrf_rejected/too_late";
+
+/// Rejects every state-changing request that doesn't carry the token from the session cookie
+pub struct CsrfFairing;
+
+#[rocket::async_trait]
+impl Fairing for CsrfFairing {
+	fn info(&self) -> Info {
+		Info {
+			name: "CSRF protection",
+			kind: Kind::Request,
+		}
+	}
+
+	async fn on_request(&self, req: &mut Request<'_>, data: &mut Data<'_>) {
+		if matches!(req.method(), Method::Get | Method::Head | Method::Options) {
+			return;
+		}
+
+		let expected = req
+			.cookies()
+			.get_private(TOKEN_COOKIE)
+			.map(|c| c.value().to_owned());
+
+		// whether the form went on past what was read, so the token could still be further in
+		let mut cut_off = false;
+		let submitted = match req.headers().get_one(TOKEN_HEADER) {
+			Some(token) => Some(token.to_owned()),
+			None if req.content_type().is_some_and(|ct| ct.is_form()) => {
+				let body = data.peek(PEEK_LIMIT).await;
+				cut_off = body.len() >= PEEK_LIMIT;
+				form_token(body)
+			}
+			None => None,
+		};
+
+		let valid = match (expected, &submitted) {
+			(Some(expected), Some(submitted)) => crypto::tokens_match(&expected, submitted),
+			_ => false,
+		};
+		if !valid {
+			// sending the request to a route that only answers with 403 instead of the real handler
+			let path = match cut_off && submitted.is_none() {
+				true => TOO_LATE_PATH,
+				false => REJECTED_PATH,
+			};
+			req.set_method(Method::Get);
+			req.set_uri(Origin::parse(path).expect("valid path"));
+		}
+	}
+}
+
+#[get("/csrf_rejected")]
+pub fn rejected() -> Status {
+	Status::Forbidden
+}
+
+#[get("/csrf_rejected/too_late")]
+pub fn rejected_too_late() -> AppError {
+	AppError::Refused(
+		"The csrf_token field has to come before the other fields of the form, or be sent in the X-CSRF-Token header",
+	)
+}
+
+fn form_token(body: &[u8]) -> Option<String> {
+	String::from_utf8_lossy(body)
+		.split('&')
+		.find_map(|pair| pair.strip_prefix(TOKEN_FIELD)?.strip_prefix('='))
+		.map(str::to_owned)
+}
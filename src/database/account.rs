@@ -1,14 +1,25 @@
 use super::{
-	types::{AccountId, PgU64},
-	MAX_ITERATIONS, POOL,
+	types::{AccountId, DmPermission, PgU64, Role},
+	MAX_ITERATIONS,
 };
-use crate::crypto;
-use chrono::NaiveDateTime;
+use crate::{
+	crypto,
+	validation::{self, ValidationError},
+};
+use chrono::{NaiveDateTime, Utc};
 use serde::Serialize;
+use sqlx::PgPool;
+use tracing::instrument;
 
 pub enum AccountError<'a> {
 	Handle(&'a str),
 	Password(&'a str),
+	Suspended {
+		reason: String,
+		end: Option<NaiveDateTime>,
+	},
+	Pending,
+	Invalid(ValidationError),
 	Sqlx(sqlx::Error),
 }
 impl<'a> From<sqlx::Error> for AccountError<'a> {
@@ -17,20 +28,37 @@ impl<'a> From<sqlx::Error> for AccountError<'a> {
 	}
 }
 
-#[derive(Serialize)]
+#[derive(Clone, Serialize)]
 pub struct Account {
 	pub id: AccountId,
 	pub handle: String,
 	pub username: Option<String>,
 	pub password_hash: Box<[u8]>,
 	pub create_time: NaiveDateTime,
+	pub dm_permission: DmPermission,
+	pub role: Role,
+	pub suspension_reason: Option<String>,
+	pub suspension_end: Option<NaiveDateTime>,
+	pub limited: bool,
+	#[serde(skip)]
+	pub totp_secret: Option<Vec<u8>>,
+	#[serde(skip)]
+	pub totp_last_step: Option<i64>,
+	pub email: Option<String>,
+	pub email_verified: bool,
+	pub pending: bool,
+	#[serde(skip)]
+	pub session_epoch: i32,
+	pub handle_change_time: Option<NaiveDateTime>,
 	pub following: PgU64,
 	pub followers: PgU64,
 	pub followed_by_user: bool,
 }
 // account actions
 impl Account {
+	#[instrument(level = "debug", skip_all)]
 	pub async fn find_by_id(
+		pool: &PgPool,
 		id: impl Into<i32>,
 		user_id: Option<impl Into<i32>>,
 	) -> sqlx::Result<Option<Self>> {
@@ -44,10 +72,12 @@ impl Account {
 			user_id.map(Into::into),
 			id.into(),
 		)
-		.fetch_optional(&*POOL)
+		.fetch_optional(pool)
 		.await
 	}
+	#[instrument(level = "debug", skip_all)]
 	pub async fn find_by_handle(
+		pool: &PgPool,
 		handle: &str,
 		user_id: Option<impl Into<i32>>,
 	) -> sqlx::Result<Option<Self>> {
@@ -61,11 +91,16 @@ impl Account {
 			user_id.map(Into::into),
 			handle,
 		)
-		.fetch_optional(&*POOL)
+		.fetch_optional(pool)
 		.await
 	}
 
-	pub async fn login<'a>(handle: &'a str, password: &'a str) -> Result<Self, AccountError<'a>> {
+	#[instrument(level = "debug", skip_all)]
+	pub async fn login<'a>(
+		pool: &PgPool,
+		handle: &'a str,
+		password: &'a str,
+	) -> Result<Self, AccountError<'a>> {
 		use AccountError::*;
 
 		match sqlx::query_as!(
@@ -74,59 +109,148 @@ impl Account {
 			(SELECT COUNT(*) FROM follow WHERE user_id = id) AS "following!",
 			(SELECT COUNT(*) FROM follow WHERE followed_id = id) AS "followers!",
 			FALSE AS "followed_by_user!"
-			FROM account WHERE handle = $1"#,
+			FROM account WHERE lower(handle) = lower($1)"#,
 			handle,
 		)
-		.fetch_optional(&*POOL)
+		.fetch_optional(pool)
 		.await?
 		{
-			Some(acc) => {
-				if crypto::validate_password(password, &acc.password_hash, MAX_ITERATIONS) {
-					Ok(acc)
-				} else {
-					Err(Password(password))
-				}
-			}
+			Some(acc) => acc.check_login(password),
 			None => Err(Handle(handle)),
 		}
 	}
+	/// Creates the account, using up one use of the invite if there is one
+	#[instrument(level = "debug", skip_all)]
 	pub async fn register<'a>(
+		pool: &PgPool,
 		handle: &'a str,
 		password: &'a str,
+		invite: Option<&'a str>,
+		pending: bool,
 	) -> Result<Self, AccountError<'a>> {
 		use sqlx::Error::Database;
 		use AccountError::*;
 
-		// TODO: password check
+		validation::validate_handle(handle).map_err(Invalid)?;
+		validation::validate_password(password, handle).map_err(Invalid)?;
+		if Self::is_handle_reserved(pool, handle, None::<i32>).await? {
+			return Err(Invalid(ValidationError::HandleReserved));
+		}
 
-		sqlx::query_as!(
+		let mut tx = pool.begin().await?;
+
+		if let Some(code) = invite {
+			let used = sqlx::query!(
+				r#"UPDATE invite SET uses = uses + 1
+				WHERE code = $1 AND uses < max_uses
+				AND (expire_time IS NULL OR expire_time > CURRENT_TIMESTAMP)"#,
+				code.trim().to_lowercase(),
+			)
+			.execute(&mut *tx)
+			.await?;
+			if used.rows_affected() == 0 {
+				return Err(Invalid(ValidationError::Invite));
+			}
+		}
+
+		let account = sqlx::query_as!(
 			Account,
-			r#"INSERT INTO account (handle, password_hash)
-			VALUES ($1, $2)
+			r#"INSERT INTO account (handle, password_hash, pending)
+			VALUES ($1, $2, $3)
 			RETURNING *,
 			0 AS "following!",
 			0 AS "followers!",
 			FALSE AS "followed_by_user!""#,
 			handle,
-			&crypto::encode_password(password, MAX_ITERATIONS)
+			&crypto::encode_password(password, MAX_ITERATIONS),
+			pending,
 		)
-		.fetch_one(&*POOL)
+		.fetch_one(&mut *tx)
 		.await
 		.map_err(|e| match e {
 			Database(err) => {
+				// the unique index is on the lowercase handle, so this covers handles differing only in case
 				if err.is_unique_violation() {
-					Handle(handle)
+					Invalid(ValidationError::HandleTaken)
 				} else {
 					Sqlx(Database(err))
 				}
 			}
 			e => Sqlx(e),
-		})
+		})?;
+
+		tx.commit().await?;
+		Ok(account)
+	}
+
+	#[instrument(level = "debug", skip_all)]
+	pub async fn set_dm_permission(
+		&self,
+		pool: &PgPool,
+		permission: DmPermission,
+	) -> sqlx::Result<()> {
+		sqlx::query!(
+			r#"UPDATE account SET dm_permission = $1 WHERE id = $2"#,
+			permission.as_str(),
+			i32::from(self.id),
+		)
+		.execute(pool)
+		.await
+		.map(|_| ())
 	}
 
-	pub async fn delete(self) -> sqlx::Result<()> {
+	/// Suspends the account for the given number of days, or indefinitely
+	#[instrument(level = "debug", skip_all)]
+	pub async fn suspend(
+		&self,
+		pool: &PgPool,
+		reason: &str,
+		days: Option<u32>,
+	) -> sqlx::Result<()> {
+		sqlx::query!(
+			r#"UPDATE account SET
+			suspension_reason = $1,
+			suspension_end = CURRENT_TIMESTAMP + MAKE_INTERVAL(days => $2)
+			WHERE id = $3"#,
+			reason,
+			days.map(|d| d as i32),
+			i32::from(self.id),
+		)
+		.execute(pool)
+		.await
+		.map(|_| ())
+	}
+	/// Hides the account's posts from everyone but its followers
+	#[instrument(level = "debug", skip_all)]
+	pub async fn limit(&self, pool: &PgPool) -> sqlx::Result<()> {
+		sqlx::query!(
+			r#"UPDATE account SET limited = TRUE WHERE id = $1"#,
+			i32::from(self.id),
+		)
+		.execute(pool)
+		.await
+		.map(|_| ())
+	}
+	/// Lifts both the suspension and the limited state
+	#[instrument(level = "debug", skip_all)]
+	pub async fn lift_restrictions(&self, pool: &PgPool) -> sqlx::Result<()> {
+		sqlx::query!(
+			r#"UPDATE account SET
+			suspension_reason = NULL,
+			suspension_end = NULL,
+			limited = FALSE
+			WHERE id = $1"#,
+			i32::from(self.id),
+		)
+		.execute(pool)
+		.await
+		.map(|_| ())
+	}
+
+	#[instrument(level = "debug", skip_all)]
+	pub async fn delete(self, pool: &PgPool) -> sqlx::Result<()> {
 		sqlx::query!(r#"DELETE FROM account WHERE id = $1"#, i32::from(self.id),)
-			.fetch_one(&*POOL)
+			.execute(pool)
 			.await
 			.map(|_| ())
 	}
@@ -137,4 +261,30 @@ impl Account {
 	pub fn display_name(&self) -> &str {
 		self.username.as_ref().unwrap_or(&self.handle)
 	}
+	pub fn check_password(&self, password: &str) -> bool {
+		crypto::validate_password(password, &self.password_hash, MAX_ITERATIONS)
+	}
+	/// Whether the account can sign in with this password right now
+	pub fn check_login(self, password: &str) -> Result<Self, AccountError<'_>> {
+		use AccountError::*;
+
+		if !self.check_password(password) {
+			Err(Password(password))
+		} else if self.pending {
+			Err(Pending)
+		} else if self.is_suspended() {
+			Err(Suspended {
+				reason: self.suspension_reason.unwrap_or_default(),
+				end: self.suspension_end,
+			})
+		} else {
+			Ok(self)
+		}
+	}
+	pub fn is_suspended(&self) -> bool {
+		self.suspension_reason.is_some()
+			&& self
+				.suspension_end
+				.is_none_or(|end| end > Utc::now().naive_utc())
+	}
 }
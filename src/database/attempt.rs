@@ -0,0 +1,106 @@
+use chrono::{NaiveDateTime, TimeDelta, Utc};
+use sqlx::PgPool;
+use tracing::instrument;
+
+use super::types::AttemptKind;
+
+// failures allowed before every further one is delayed
+const FREE_ATTEMPTS: i32 = 3;
+// the delay doubles with every failure after the free ones, up to this many seconds
+const MAX_BACKOFF_SECONDS: i64 = 5 * 60;
+// after this many failures the key is locked out completely
+const LOCKOUT_FAILURES: i32 = 10;
+const LOCKOUT_MINUTES: i64 = 30;
+// failures older than this are forgotten
+const RESET_HOURS: i32 = 1;
+
+/// Returns the time until which attempts for this key are blocked, if they are
+#[instrument(level = "debug", skip_all)]
+pub async fn blocked_until(
+	pool: &PgPool,
+	kind: AttemptKind,
+	key: &str,
+) -> sqlx::Result<Option<NaiveDateTime>> {
+	sqlx::query_scalar!(
+		r#"SELECT blocked_until AS "blocked_until!" FROM login_attempt
+		WHERE kind = $1 AND key = $2 AND blocked_until > CURRENT_TIMESTAMP"#,
+		kind.as_str(),
+		key,
+	)
+	.fetch_optional(pool)
+	.await
+}
+
+/// Counts a failed attempt and blocks the key for a while once it has failed too often
+#[instrument(level = "debug", skip_all)]
+pub async fn record_failure(pool: &PgPool, kind: AttemptKind, key: &str) -> sqlx::Result<()> {
+	let mut tx = pool.begin().await?;
+
+	let failures = sqlx::query_scalar!(
+		r#"INSERT INTO login_attempt (kind, key, failures)
+		VALUES ($1, $2, 1)
+		ON CONFLICT (kind, key) DO UPDATE SET
+		failures = CASE
+			WHEN login_attempt.last_failure < CURRENT_TIMESTAMP - MAKE_INTERVAL(hours => $3) THEN 1
+			ELSE login_attempt.failures + 1
+		END,
+		last_failure = CURRENT_TIMESTAMP
+		RETURNING failures"#,
+		kind.as_str(),
+		key,
+		RESET_HOURS,
+	)
+	.fetch_one(&mut *tx)
+	.await?;
+
+	if let Some(delay) = backoff(failures) {
+		sqlx::query!(
+			r#"UPDATE login_attempt SET blocked_until = $3 WHERE kind = $1 AND key = $2"#,
+			kind.as_str(),
+			key,
+			Utc::now().naive_utc() + delay,
+		)
+		.execute(&mut *tx)
+		.await?;
+	}
+
+	tx.commit().await
+}
+
+/// Forgets the failures of a key, e.g. after a successful login
+#[instrument(level = "debug", skip_all)]
+pub async fn clear(pool: &PgPool, kind: AttemptKind, key: &str) -> sqlx::Result<()> {
+	sqlx::query!(
+		r#"DELETE FROM login_attempt WHERE kind = $1 AND key = $2"#,
+		kind.as_str(),
+		key,
+	)
+	.execute(pool)
+	.await
+	.map(|_| ())
+}
+
+/// Drops the failures that are old enough to be forgotten and aren't blocking anything, returns how many
+#[instrument(level = "debug", skip_all)]
+pub async fn forget_expired(pool: &PgPool) -> sqlx::Result<u64> {
+	sqlx::query!(
+		r#"DELETE FROM login_attempt
+		WHERE last_failure < CURRENT_TIMESTAMP - MAKE_INTERVAL(hours => $1)
+		AND (blocked_until IS NULL OR blocked_until < CURRENT_TIMESTAMP)"#,
+		RESET_HOURS,
+	)
+	.execute(pool)
+	.await
+	.map(|r| r.rows_affected())
+}
+
+fn backoff(failures: i32) -> Option<TimeDelta> {
+	if failures >= LOCKOUT_FAILURES {
+		return Some(TimeDelta::minutes(LOCKOUT_MINUTES));
+	}
+	if failures < FREE_ATTEMPTS {
+		return None;
+	}
+	let seconds = 1_i64 << (failures - FREE_ATTEMPTS);
+	Some(TimeDelta::seconds(seconds.min(MAX_BACKOFF_SECONDS)))
+}
@@ -1,4 +1,7 @@
-use super::{account::Account, types::AccountId, POOL};
+use sqlx::PgPool;
+use tracing::instrument;
+
+use super::{account::Account, types::AccountId};
 
 pub struct Follow {
 	pub user_id: AccountId,
@@ -6,27 +9,35 @@ pub struct Follow {
 }
 
 impl Account {
-	pub async fn follow(&self, follow_id: impl Into<i32>) -> sqlx::Result<Follow> {
+	#[instrument(level = "debug", skip_all)]
+	pub async fn follow(&self, pool: &PgPool, follow_id: impl Into<i32>) -> sqlx::Result<Follow> {
 		sqlx::query_as!(
 			Follow,
-			r#"INSERT INTO follow (user_id, followed_id)
-			VALUES ($1, $2)
-			ON CONFLICT (user_id, followed_id) DO NOTHING
-			RETURNING *"#,
+			r#"WITH inserted AS (
+				INSERT INTO follow (user_id, followed_id)
+				VALUES ($1, $2)
+				ON CONFLICT (user_id, followed_id) DO NOTHING
+				RETURNING *
+			), notified AS (
+				INSERT INTO notification (account_id, actor_id, kind)
+				SELECT followed_id, user_id, 'follow' FROM inserted
+			)
+			SELECT user_id AS "user_id!", followed_id AS "followed_id!" FROM inserted"#,
 			i32::from(self.id),
 			follow_id.into(),
 		)
-		.fetch_one(&*POOL)
+		.fetch_one(pool)
 		.await
 	}
-	pub async fn unfollow(&self, followed_id: impl Into<i32>) -> sqlx::Result<()> {
+	#[instrument(level = "debug", skip_all)]
+	pub async fn unfollow(&self, pool: &PgPool, followed_id: impl Into<i32>) -> sqlx::Result<()> {
 		sqlx::query!(
 			r#"DELETE FROM follow
 			WHERE user_id = $1 AND followed_id = $2"#,
 			i32::from(self.id),
 			followed_id.into(),
 		)
-		.execute(&*POOL)
+		.execute(pool)
 		.await
 		.map(|_| ())
 	}
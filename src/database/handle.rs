@@ -0,0 +1,110 @@
+use chrono::{NaiveDateTime, TimeDelta, Utc};
+use sqlx::{Error::Database, PgPool};
+use tracing::instrument;
+
+use super::account::{Account, AccountError};
+use crate::validation::{self, ValidationError};
+
+// how long after a change the handle has to stay the same
+pub const HANDLE_CHANGE_COOLDOWN_DAYS: i64 = 30;
+// how long an old handle keeps redirecting and can't be taken by anyone else
+const OLD_HANDLE_RESERVATION_DAYS: i64 = 90;
+
+// handle change actions for account
+impl Account {
+	/// When the handle can be changed again, if it can't be right now
+	pub fn next_handle_change(&self) -> Option<NaiveDateTime> {
+		let next = self.handle_change_time? + TimeDelta::days(HANDLE_CHANGE_COOLDOWN_DAYS);
+		(next > Utc::now().naive_utc()).then_some(next)
+	}
+
+	/// The current handle of the account that used to have this one
+	#[instrument(level = "debug", skip_all)]
+	pub async fn find_by_old_handle(pool: &PgPool, handle: &str) -> sqlx::Result<Option<String>> {
+		sqlx::query_scalar!(
+			r#"SELECT a.handle FROM old_handle o
+			JOIN account a ON a.id = o.account_id
+			WHERE lower(o.handle) = lower($1) AND o.release_time > CURRENT_TIMESTAMP"#,
+			handle,
+		)
+		.fetch_optional(pool)
+		.await
+	}
+
+	/// Whether the handle was recently given up by an account other than this one
+	#[instrument(level = "debug", skip_all)]
+	pub async fn is_handle_reserved(
+		pool: &PgPool,
+		handle: &str,
+		account_id: Option<impl Into<i32>>,
+	) -> sqlx::Result<bool> {
+		sqlx::query_scalar!(
+			r#"SELECT EXISTS(
+				SELECT * FROM old_handle
+				WHERE lower(handle) = lower($1)
+				AND release_time > CURRENT_TIMESTAMP
+				AND account_id IS DISTINCT FROM $2
+			) AS "exists!""#,
+			handle,
+			account_id.map(Into::into),
+		)
+		.fetch_one(pool)
+		.await
+	}
+
+	/// Changes the handle and keeps the old one reserved for this account
+	#[instrument(level = "debug", skip_all)]
+	pub async fn change_handle<'a>(
+		&self,
+		pool: &PgPool,
+		handle: &'a str,
+	) -> Result<(), AccountError<'a>> {
+		use AccountError::*;
+
+		validation::validate_handle(handle).map_err(Invalid)?;
+		if Self::is_handle_reserved(pool, handle, Some(self.id)).await? {
+			return Err(Invalid(ValidationError::HandleReserved));
+		}
+
+		let mut tx = pool.begin().await?;
+
+		// taking back one of its own old handles or one whose reservation ran out
+		sqlx::query!(
+			r#"DELETE FROM old_handle WHERE lower(handle) = lower($1)"#,
+			handle,
+		)
+		.execute(&mut *tx)
+		.await?;
+		sqlx::query!(
+			r#"INSERT INTO old_handle (handle, account_id, release_time)
+			VALUES ($1, $2, $3)
+			ON CONFLICT ((lower(handle))) DO UPDATE SET
+			handle = EXCLUDED.handle,
+			account_id = EXCLUDED.account_id,
+			release_time = EXCLUDED.release_time"#,
+			self.handle,
+			i32::from(self.id),
+			Utc::now().naive_utc() + TimeDelta::days(OLD_HANDLE_RESERVATION_DAYS),
+		)
+		.execute(&mut *tx)
+		.await?;
+		match sqlx::query!(
+			r#"UPDATE account SET handle = $1, handle_change_time = CURRENT_TIMESTAMP
+			WHERE id = $2"#,
+			handle,
+			i32::from(self.id),
+		)
+		.execute(&mut *tx)
+		.await
+		{
+			Ok(_) => (),
+			Err(Database(err)) if err.is_unique_violation() => {
+				return Err(Invalid(ValidationError::HandleTaken))
+			}
+			Err(e) => return Err(e.into()),
+		}
+
+		tx.commit().await?;
+		Ok(())
+	}
+}
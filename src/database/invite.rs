@@ -0,0 +1,120 @@
+use chrono::NaiveDateTime;
+use rand::{distributions::Alphanumeric, Rng};
+use sqlx::PgPool;
+use tracing::instrument;
+
+use super::{account::Account, types::AccountId};
+
+const CODE_LENGTH: usize = 12;
+
+// what regular users can create, admins aren't limited beyond the maximums
+pub const USER_MAX_USES: i32 = 5;
+pub const USER_MAX_DAYS: i64 = 30;
+pub const USER_MAX_ACTIVE_INVITES: i64 = 5;
+pub const MAX_USES: i32 = 1000;
+pub const MAX_DAYS: i64 = 365;
+
+#[derive(Debug)]
+pub struct Invite {
+	pub code: String,
+	pub creator_id: AccountId,
+	pub max_uses: i32,
+	pub uses: i32,
+	pub expire_time: Option<NaiveDateTime>,
+	pub create_time: NaiveDateTime,
+}
+
+// invite actions for account
+impl Account {
+	#[instrument(level = "debug", skip_all)]
+	pub async fn create_invite(
+		&self,
+		pool: &PgPool,
+		max_uses: i32,
+		expire_time: Option<NaiveDateTime>,
+	) -> sqlx::Result<Invite> {
+		let code: String = rand::thread_rng()
+			.sample_iter(Alphanumeric)
+			.take(CODE_LENGTH)
+			.map(|c| (c as char).to_ascii_lowercase())
+			.collect();
+
+		sqlx::query_as!(
+			Invite,
+			r#"INSERT INTO invite (code, creator_id, max_uses, expire_time)
+			VALUES ($1, $2, $3, $4)
+			RETURNING *"#,
+			code,
+			i32::from(self.id),
+			max_uses,
+			expire_time,
+		)
+		.fetch_one(pool)
+		.await
+	}
+
+	#[instrument(level = "debug", skip_all)]
+	pub async fn get_invites(&self, pool: &PgPool) -> sqlx::Result<Vec<Invite>> {
+		sqlx::query_as!(
+			Invite,
+			r#"SELECT * FROM invite WHERE creator_id = $1 ORDER BY create_time DESC"#,
+			i32::from(self.id),
+		)
+		.fetch_all(pool)
+		.await
+	}
+
+	/// Invites that can still be used
+	#[instrument(level = "debug", skip_all)]
+	pub async fn count_active_invites(&self, pool: &PgPool) -> sqlx::Result<i64> {
+		sqlx::query_scalar!(
+			r#"SELECT COUNT(*) AS "count!" FROM invite
+			WHERE creator_id = $1 AND uses < max_uses
+			AND (expire_time IS NULL OR expire_time > CURRENT_TIMESTAMP)"#,
+			i32::from(self.id),
+		)
+		.fetch_one(pool)
+		.await
+	}
+
+	#[instrument(level = "debug", skip_all)]
+	pub async fn delete_invite(&self, pool: &PgPool, code: &str) -> sqlx::Result<bool> {
+		sqlx::query!(
+			r#"DELETE FROM invite WHERE code = $1 AND creator_id = $2"#,
+			code,
+			i32::from(self.id),
+		)
+		.execute(pool)
+		.await
+		.map(|r| r.rows_affected() > 0)
+	}
+
+	/// Accounts waiting for an admin to approve them, oldest first
+	#[instrument(level = "debug", skip_all)]
+	pub async fn get_pending(pool: &PgPool, limit: u64) -> sqlx::Result<Vec<Account>> {
+		sqlx::query_as!(
+			Account,
+			r#"SELECT *,
+			0 AS "following!",
+			0 AS "followers!",
+			FALSE AS "followed_by_user!"
+			FROM account WHERE pending
+			ORDER BY create_time
+			LIMIT $1"#,
+			limit as i64,
+		)
+		.fetch_all(pool)
+		.await
+	}
+
+	#[instrument(level = "debug", skip_all)]
+	pub async fn approve(&self, pool: &PgPool) -> sqlx::Result<()> {
+		sqlx::query!(
+			r#"UPDATE account SET pending = FALSE WHERE id = $1"#,
+			i32::from(self.id),
+		)
+		.execute(pool)
+		.await
+		.map(|_| ())
+	}
+}
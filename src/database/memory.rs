@@ -0,0 +1,580 @@
+use std::{
+	cmp::Reverse,
+	collections::{BTreeMap, BTreeSet},
+	sync::{Mutex, MutexGuard},
+};
+
+use chrono::Utc;
+
+use super::{
+	account::{Account, AccountError},
+	follow::Follow,
+	post::Post,
+	storage::Storage,
+	types::{AccountId, DmPermission, LogAction, OptPostId, PostId},
+	vote::Vote,
+	MAX_ITERATIONS,
+};
+use crate::{
+	crypto,
+	validation::{self, ValidationError},
+};
+
+#[derive(Default)]
+struct Tables {
+	accounts: BTreeMap<AccountId, Account>,
+	posts: BTreeMap<PostId, Post>,
+	// (voter, post)
+	votes: BTreeSet<(AccountId, PostId)>,
+	// (user, followed)
+	follows: BTreeSet<(AccountId, AccountId)>,
+	next_account_id: u32,
+	next_post_id: u64,
+}
+
+impl Tables {
+	// fills in the columns postgres computes in the select
+	fn account(&self, account: &Account, user_id: Option<AccountId>) -> Account {
+		let id = account.id;
+		Account {
+			following: (self.follows.iter().filter(|f| f.0 == id).count() as u64).into(),
+			followers: (self.follows.iter().filter(|f| f.1 == id).count() as u64).into(),
+			followed_by_user: user_id.is_some_and(|u| self.follows.contains(&(u, id))),
+			..account.clone()
+		}
+	}
+	fn post(&self, post: &Post, user_id: Option<AccountId>) -> Post {
+		let author = &self.accounts[&post.author_id];
+		Post {
+			author_handle: author.handle.as_str().into(),
+			author_username: author.username.clone(),
+			votes: (self.votes.iter().filter(|v| v.1 == post.id).count() as u64).into(),
+			voted_by_user: user_id.is_some_and(|u| self.votes.contains(&(u, post.id))),
+			..post.clone()
+		}
+	}
+	// newest first like the timeline queries
+	fn posts_where(&self, user_id: Option<AccountId>, filter: impl Fn(&Post) -> bool) -> Vec<Post> {
+		self.posts
+			.values()
+			.rev()
+			.filter(|p| filter(p))
+			.map(|p| self.post(p, user_id))
+			.collect()
+	}
+}
+
+/// Keeps everything in memory, for tests that shouldn't need postgres.
+/// Invites, old handles, mutes, notifications and the moderation log aren't kept,
+/// so invite codes are always invalid and nothing is muted
+#[derive(Default)]
+pub struct MemoryStorage {
+	tables: Mutex<Tables>,
+}
+impl MemoryStorage {
+	fn tables(&self) -> MutexGuard<'_, Tables> {
+		// a panicking test shouldn't take the other ones down with it
+		self.tables.lock().unwrap_or_else(|e| e.into_inner())
+	}
+}
+
+#[rocket::async_trait]
+impl Storage for MemoryStorage {
+	async fn find_account_by_id(
+		&self,
+		id: AccountId,
+		user_id: Option<AccountId>,
+	) -> sqlx::Result<Option<Account>> {
+		let tables = self.tables();
+		Ok(tables.accounts.get(&id).map(|a| tables.account(a, user_id)))
+	}
+	async fn find_account_by_handle(
+		&self,
+		handle: &str,
+		user_id: Option<AccountId>,
+	) -> sqlx::Result<Option<Account>> {
+		let tables = self.tables();
+		Ok(tables
+			.accounts
+			.values()
+			.find(|a| a.handle == handle)
+			.map(|a| tables.account(a, user_id)))
+	}
+	async fn login<'a>(
+		&self,
+		handle: &'a str,
+		password: &'a str,
+	) -> Result<Account, AccountError<'a>> {
+		let account = {
+			let tables = self.tables();
+			tables
+				.accounts
+				.values()
+				.find(|a| a.handle.eq_ignore_ascii_case(handle))
+				.map(|a| tables.account(a, None))
+		};
+		match account {
+			Some(acc) => acc.check_login(password),
+			None => Err(AccountError::Handle(handle)),
+		}
+	}
+	async fn register<'a>(
+		&self,
+		handle: &'a str,
+		password: &'a str,
+		invite: Option<&'a str>,
+		pending: bool,
+	) -> Result<Account, AccountError<'a>> {
+		use AccountError::*;
+
+		validation::validate_handle(handle).map_err(Invalid)?;
+		validation::validate_password(password, handle).map_err(Invalid)?;
+		if invite.is_some() {
+			return Err(Invalid(ValidationError::Invite));
+		}
+
+		let mut tables = self.tables();
+		if tables
+			.accounts
+			.values()
+			.any(|a| a.handle.eq_ignore_ascii_case(handle))
+		{
+			return Err(Invalid(ValidationError::HandleTaken));
+		}
+		tables.next_account_id += 1;
+		let account = Account {
+			id: tables.next_account_id.into(),
+			handle: handle.into(),
+			username: None,
+			password_hash: crypto::encode_password(password, MAX_ITERATIONS).into(),
+			create_time: Utc::now().naive_utc(),
+			dm_permission: DmPermission::default(),
+			role: Default::default(),
+			suspension_reason: None,
+			suspension_end: None,
+			limited: false,
+			totp_secret: None,
+			totp_last_step: None,
+			email: None,
+			email_verified: false,
+			pending,
+			session_epoch: 0,
+			handle_change_time: None,
+			following: 0u64.into(),
+			followers: 0u64.into(),
+			followed_by_user: false,
+		};
+		tables.accounts.insert(account.id, account.clone());
+		Ok(account)
+	}
+	async fn get_session_epoch(&self, id: AccountId) -> sqlx::Result<Option<i32>> {
+		Ok(self.tables().accounts.get(&id).map(|a| a.session_epoch))
+	}
+	async fn set_dm_permission(
+		&self,
+		account: &Account,
+		permission: DmPermission,
+	) -> sqlx::Result<()> {
+		if let Some(a) = self.tables().accounts.get_mut(&account.id) {
+			a.dm_permission = permission;
+		}
+		Ok(())
+	}
+	async fn suspend(
+		&self,
+		account: &Account,
+		reason: &str,
+		days: Option<u32>,
+	) -> sqlx::Result<()> {
+		if let Some(a) = self.tables().accounts.get_mut(&account.id) {
+			a.suspension_reason = Some(reason.into());
+			a.suspension_end =
+				days.map(|d| Utc::now().naive_utc() + chrono::TimeDelta::days(d.into()));
+		}
+		Ok(())
+	}
+	async fn limit(&self, account: &Account) -> sqlx::Result<()> {
+		if let Some(a) = self.tables().accounts.get_mut(&account.id) {
+			a.limited = true;
+		}
+		Ok(())
+	}
+	async fn lift_restrictions(&self, account: &Account) -> sqlx::Result<()> {
+		if let Some(a) = self.tables().accounts.get_mut(&account.id) {
+			a.suspension_reason = None;
+			a.suspension_end = None;
+			a.limited = false;
+		}
+		Ok(())
+	}
+	async fn delete_account(&self, account: Account) -> sqlx::Result<()> {
+		let mut tables = self.tables();
+		let id = account.id;
+		tables.accounts.remove(&id);
+		// the same as the cascading foreign keys
+		let removed: Vec<PostId> = tables
+			.posts
+			.values()
+			.filter(|p| p.author_id == id)
+			.map(|p| p.id)
+			.collect();
+		for post_id in removed {
+			remove_post(&mut tables, post_id);
+		}
+		tables.votes.retain(|v| v.0 != id);
+		tables.follows.retain(|f| f.0 != id && f.1 != id);
+		Ok(())
+	}
+	async fn find_handle_by_old_handle(&self, _handle: &str) -> sqlx::Result<Option<String>> {
+		Ok(None)
+	}
+
+	async fn find_post_by_id(
+		&self,
+		id: PostId,
+		user_id: Option<AccountId>,
+	) -> sqlx::Result<Option<Post>> {
+		let tables = self.tables();
+		Ok(tables.posts.get(&id).map(|p| tables.post(p, user_id)))
+	}
+	async fn get_recent_posts(
+		&self,
+		limit: u64,
+		user_id: Option<AccountId>,
+	) -> sqlx::Result<Vec<Post>> {
+		let tables = self.tables();
+		let mut posts = tables.posts_where(user_id, |p| {
+			let author = &tables.accounts[&p.author_id];
+			p.parent_id.0.is_none()
+				&& (!author.limited
+					|| Some(author.id) == user_id
+					|| user_id.is_some_and(|u| tables.follows.contains(&(u, author.id))))
+		});
+		posts.truncate(limit as usize);
+		Ok(posts)
+	}
+	async fn get_replies(
+		&self,
+		post: &Post,
+		limit: u64,
+		user_id: Option<AccountId>,
+	) -> sqlx::Result<Vec<Post>> {
+		let tables = self.tables();
+		let mut replies = tables.posts_where(user_id, |p| p.parent_id.0 == Some(post.id));
+		// most liked first, the sort is stable so ties stay newest first
+		replies.sort_by_key(|p| Reverse(p.votes));
+		replies.truncate(limit as usize);
+		Ok(replies)
+	}
+	async fn get_posts(
+		&self,
+		account: &Account,
+		limit: u64,
+		include_replies: bool,
+		user_id: Option<AccountId>,
+	) -> sqlx::Result<Vec<Post>> {
+		let tables = self.tables();
+		let mut posts = tables.posts_where(user_id, |p| {
+			p.author_id == account.id && (p.parent_id.0.is_none() || include_replies)
+		});
+		posts.truncate(limit as usize);
+		Ok(posts)
+	}
+	async fn create_post(
+		&self,
+		author: &Account,
+		body: &str,
+		parent_id: Option<PostId>,
+	) -> sqlx::Result<Post> {
+		let mut tables = self.tables();
+		if !tables.accounts.contains_key(&author.id)
+			|| parent_id.is_some_and(|id| !tables.posts.contains_key(&id))
+		{
+			return Err(sqlx::Error::RowNotFound);
+		}
+		tables.next_post_id += 1;
+		let post = Post {
+			id: tables.next_post_id.into(),
+			author_id: author.id,
+			author_handle: "".into(),
+			author_username: None,
+			body: body.into(),
+			create_time: Utc::now().naive_utc(),
+			parent_id: OptPostId(parent_id),
+			votes: 0u64.into(),
+			voted_by_user: false,
+		};
+		let post = tables.post(&post, None);
+		tables.posts.insert(post.id, post.clone());
+		Ok(post)
+	}
+	async fn delete_post(&self, post: Post) -> sqlx::Result<Option<PostId>> {
+		let mut tables = self.tables();
+		match remove_post(&mut tables, post.id) {
+			Some(p) => Ok(p.parent_id.0),
+			None => Err(sqlx::Error::RowNotFound),
+		}
+	}
+
+	async fn add_vote(&self, voter: &Account, post_id: PostId) -> sqlx::Result<Vote> {
+		// like the insert returning nothing on conflict
+		if !self.tables().votes.insert((voter.id, post_id)) {
+			return Err(sqlx::Error::RowNotFound);
+		}
+		Ok(Vote {
+			voter_id: voter.id,
+			post_id,
+		})
+	}
+	async fn remove_vote(&self, voter: &Account, post_id: PostId) -> sqlx::Result<()> {
+		self.tables().votes.remove(&(voter.id, post_id));
+		Ok(())
+	}
+	async fn get_voters(
+		&self,
+		post: &Post,
+		user_id: Option<AccountId>,
+	) -> sqlx::Result<Vec<Account>> {
+		let tables = self.tables();
+		Ok(tables
+			.votes
+			.iter()
+			.filter(|v| v.1 == post.id)
+			.filter_map(|v| tables.accounts.get(&v.0))
+			.map(|a| tables.account(a, user_id))
+			.collect())
+	}
+	async fn voted_by(&self, post: &Post, account_id: AccountId) -> sqlx::Result<bool> {
+		Ok(self.tables().votes.contains(&(account_id, post.id)))
+	}
+	async fn get_voted_posts(
+		&self,
+		account: &Account,
+		user_id: Option<AccountId>,
+	) -> sqlx::Result<Vec<Post>> {
+		let tables = self.tables();
+		Ok(tables.posts_where(user_id, |p| tables.votes.contains(&(account.id, p.id))))
+	}
+
+	async fn follow(&self, account: &Account, followed_id: AccountId) -> sqlx::Result<Follow> {
+		let mut tables = self.tables();
+		if !tables.accounts.contains_key(&followed_id)
+			|| !tables.follows.insert((account.id, followed_id))
+		{
+			return Err(sqlx::Error::RowNotFound);
+		}
+		Ok(Follow {
+			user_id: account.id,
+			followed_id,
+		})
+	}
+	async fn unfollow(&self, account: &Account, followed_id: AccountId) -> sqlx::Result<()> {
+		self.tables().follows.remove(&(account.id, followed_id));
+		Ok(())
+	}
+
+	async fn is_muting(&self, _account: &Account, _muted_id: AccountId) -> sqlx::Result<bool> {
+		Ok(false)
+	}
+	async fn is_muting_thread(&self, _account: &Account, _post: &Post) -> sqlx::Result<bool> {
+		Ok(false)
+	}
+
+	async fn log_action(
+		&self,
+		_moderator: &Account,
+		_action: LogAction,
+		_account_id: Option<AccountId>,
+		_post_id: Option<PostId>,
+		_details: &str,
+	) -> sqlx::Result<()> {
+		Ok(())
+	}
+}
+
+// replies are kept with their parent unset, the same as the foreign key
+fn remove_post(tables: &mut Tables, post_id: PostId) -> Option<Post> {
+	let post = tables.posts.remove(&post_id)?;
+	for reply in tables.posts.values_mut() {
+		if reply.parent_id.0 == Some(post_id) {
+			reply.parent_id = OptPostId(None);
+		}
+	}
+	tables.votes.retain(|v| v.1 != post_id);
+	Some(post)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const PASSWORD: &str = "Test-pass1";
+
+	async fn account(storage: &MemoryStorage, handle: &str) -> Account {
+		storage
+			.register(handle, PASSWORD, None, false)
+			.await
+			.unwrap_or_else(|_| panic!("couldn't register {handle}"))
+	}
+
+	#[rocket::async_test]
+	async fn handles_are_unique_in_any_case() {
+		let storage = MemoryStorage::default();
+		account(&storage, "alice").await;
+
+		let res = storage.register("ALICE", PASSWORD, None, false).await;
+		assert!(matches!(
+			res,
+			Err(AccountError::Invalid(ValidationError::HandleTaken))
+		));
+	}
+
+	#[rocket::async_test]
+	async fn login_checks_the_handle_and_password() {
+		let storage = MemoryStorage::default();
+		let alice = account(&storage, "alice").await;
+
+		assert!(matches!(
+			storage.login("nobody", PASSWORD).await,
+			Err(AccountError::Handle(_))
+		));
+		assert!(matches!(
+			storage.login("alice", "Wrong-pass1").await,
+			Err(AccountError::Password(_))
+		));
+		assert!(matches!(
+			storage.login("Alice", PASSWORD).await,
+			Ok(acc) if acc.id == alice.id
+		));
+
+		storage.suspend(&alice, "spam", None).await.unwrap();
+		assert!(matches!(
+			storage.login("alice", PASSWORD).await,
+			Err(AccountError::Suspended { .. })
+		));
+	}
+
+	#[rocket::async_test]
+	async fn counts_follows_and_votes() {
+		let storage = MemoryStorage::default();
+		let alice = account(&storage, "alice").await;
+		let bob = account(&storage, "bob").await;
+		let post = storage.create_post(&alice, "hi", None).await.unwrap();
+
+		storage.follow(&bob, alice.id).await.unwrap();
+		// following twice fails like the insert that returns nothing
+		assert!(storage.follow(&bob, alice.id).await.is_err());
+		storage.add_vote(&bob, post.id).await.unwrap();
+
+		let alice = storage
+			.find_account_by_id(alice.id, Some(bob.id))
+			.await
+			.unwrap()
+			.unwrap();
+		assert_eq!(alice.followers.0, 1);
+		assert!(alice.followed_by_user);
+
+		let post = storage
+			.find_post_by_id(post.id, Some(bob.id))
+			.await
+			.unwrap()
+			.unwrap();
+		assert_eq!(post.votes.0, 1);
+		assert!(post.voted_by_user);
+		assert_eq!(&*post.author_handle, "alice");
+	}
+
+	#[rocket::async_test]
+	async fn replies_are_ordered_by_votes() {
+		let storage = MemoryStorage::default();
+		let alice = account(&storage, "alice").await;
+		let bob = account(&storage, "bob").await;
+		let post = storage.create_post(&alice, "question", None).await.unwrap();
+		let first = storage
+			.create_post(&bob, "first", Some(post.id))
+			.await
+			.unwrap();
+		storage
+			.create_post(&bob, "second", Some(post.id))
+			.await
+			.unwrap();
+		storage.add_vote(&alice, first.id).await.unwrap();
+
+		let replies = storage.get_replies(&post, 10, None).await.unwrap();
+		let bodies: Vec<&str> = replies.iter().map(|p| &*p.body).collect();
+		assert_eq!(bodies, ["first", "second"]);
+
+		// replies stay out of the timeline
+		let recent = storage.get_recent_posts(10, None).await.unwrap();
+		assert_eq!(recent.len(), 1);
+	}
+
+	#[rocket::async_test]
+	async fn limited_accounts_only_show_up_for_followers() {
+		let storage = MemoryStorage::default();
+		let alice = account(&storage, "alice").await;
+		let bob = account(&storage, "bob").await;
+		let carol = account(&storage, "carol").await;
+		storage.create_post(&alice, "hi", None).await.unwrap();
+		storage.limit(&alice).await.unwrap();
+		storage.follow(&bob, alice.id).await.unwrap();
+
+		assert_eq!(
+			storage
+				.get_recent_posts(10, Some(bob.id))
+				.await
+				.unwrap()
+				.len(),
+			1
+		);
+		assert_eq!(
+			storage
+				.get_recent_posts(10, Some(alice.id))
+				.await
+				.unwrap()
+				.len(),
+			1
+		);
+		assert!(storage
+			.get_recent_posts(10, Some(carol.id))
+			.await
+			.unwrap()
+			.is_empty());
+	}
+
+	#[rocket::async_test]
+	async fn deleting_cascades_like_the_foreign_keys() {
+		let storage = MemoryStorage::default();
+		let alice = account(&storage, "alice").await;
+		let bob = account(&storage, "bob").await;
+		let post = storage.create_post(&alice, "hi", None).await.unwrap();
+		let reply = storage
+			.create_post(&bob, "hello", Some(post.id))
+			.await
+			.unwrap();
+		storage.follow(&alice, bob.id).await.unwrap();
+
+		// replies outlive their parent
+		let parent = storage.delete_post(post).await.unwrap();
+		assert_eq!(parent, None);
+		let reply = storage
+			.find_post_by_id(reply.id, None)
+			.await
+			.unwrap()
+			.unwrap();
+		assert_eq!(reply.parent_id.0, None);
+
+		storage.delete_account(bob.clone()).await.unwrap();
+		assert!(storage
+			.find_post_by_id(reply.id, None)
+			.await
+			.unwrap()
+			.is_none());
+		let alice = storage
+			.find_account_by_id(alice.id, None)
+			.await
+			.unwrap()
+			.unwrap();
+		assert_eq!(alice.following.0, 0);
+	}
+}
@@ -0,0 +1,263 @@
+use chrono::NaiveDateTime;
+use sqlx::PgPool;
+use tracing::instrument;
+
+use super::{
+	account::Account,
+	types::{AccountId, ConversationId, MessageId, PgU64},
+};
+
+pub const MAX_CONVERSATION_MEMBERS: usize = 8;
+
+#[derive(Debug)]
+pub struct Conversation {
+	pub id: ConversationId,
+	pub create_time: NaiveDateTime,
+	pub member_handles: Vec<String>,
+	pub last_message: Option<String>,
+	pub last_message_time: Option<NaiveDateTime>,
+	pub unread: PgU64,
+}
+
+#[derive(Debug)]
+pub struct Message {
+	pub id: MessageId,
+	pub conversation_id: ConversationId,
+	pub author_id: AccountId,
+	pub author_handle: Box<str>,
+	pub body: Box<str>,
+	pub create_time: NaiveDateTime,
+}
+
+// conversation actions for account
+impl Account {
+	#[instrument(level = "debug", skip_all)]
+	pub async fn get_conversations(&self, pool: &PgPool) -> sqlx::Result<Vec<Conversation>> {
+		sqlx::query_as!(
+			Conversation,
+			r#"SELECT c.*,
+			ARRAY(
+				SELECT a.handle FROM conversation_member m, account a
+				WHERE m.conversation_id = c.id AND a.id = m.account_id AND a.id <> $1
+				ORDER BY a.handle
+			) AS "member_handles!",
+			last.body AS "last_message?",
+			last.create_time AS "last_message_time?",
+			(
+				SELECT COUNT(*) FROM message msg
+				WHERE msg.conversation_id = c.id AND
+				msg.author_id <> $1 AND
+				msg.create_time > cm.last_read_time AND
+				NOT EXISTS(SELECT * FROM message_deletion WHERE message_id = msg.id AND account_id = $1)
+			) AS "unread!"
+			FROM conversation c
+			JOIN conversation_member cm ON cm.conversation_id = c.id AND cm.account_id = $1
+			LEFT JOIN LATERAL (
+				SELECT msg.body, msg.create_time FROM message msg
+				WHERE msg.conversation_id = c.id AND
+				NOT EXISTS(SELECT * FROM message_deletion WHERE message_id = msg.id AND account_id = $1)
+				ORDER BY msg.create_time DESC
+				LIMIT 1
+			) last ON TRUE
+			ORDER BY COALESCE(last.create_time, c.create_time) DESC"#,
+			i32::from(self.id),
+		)
+		.fetch_all(pool)
+		.await
+	}
+
+	#[instrument(level = "debug", skip_all)]
+	pub async fn find_conversation(
+		&self,
+		pool: &PgPool,
+		conversation_id: impl Into<i32>,
+	) -> sqlx::Result<Option<Conversation>> {
+		sqlx::query_as!(
+			Conversation,
+			r#"SELECT c.*,
+			ARRAY(
+				SELECT a.handle FROM conversation_member m, account a
+				WHERE m.conversation_id = c.id AND a.id = m.account_id AND a.id <> $1
+				ORDER BY a.handle
+			) AS "member_handles!",
+			NULL::text AS "last_message?",
+			NULL::timestamp AS "last_message_time?",
+			0 AS "unread!"
+			FROM conversation c, conversation_member cm
+			WHERE c.id = $2 AND cm.conversation_id = c.id AND cm.account_id = $1"#,
+			i32::from(self.id),
+			conversation_id.into(),
+		)
+		.fetch_optional(pool)
+		.await
+	}
+
+	/// Returns the conversation with exactly these members, creating it if it doesn't exist
+	#[instrument(level = "debug", skip_all)]
+	pub async fn start_conversation(
+		&self,
+		pool: &PgPool,
+		recipients: &[AccountId],
+	) -> sqlx::Result<ConversationId> {
+		let mut members: Vec<i32> = recipients.iter().map(|&id| id.into()).collect();
+		members.push(self.id.into());
+		members.sort_unstable();
+		members.dedup();
+
+		let mut tx = pool.begin().await?;
+
+		let existing = sqlx::query_scalar!(
+			r#"SELECT conversation_id FROM conversation_member
+			GROUP BY conversation_id
+			HAVING ARRAY_AGG(account_id ORDER BY account_id) = $1"#,
+			&members,
+		)
+		.fetch_optional(&mut *tx)
+		.await?;
+		if let Some(id) = existing {
+			return Ok(id.into());
+		}
+
+		let id = sqlx::query_scalar!(r#"INSERT INTO conversation DEFAULT VALUES RETURNING id"#)
+			.fetch_one(&mut *tx)
+			.await?;
+		sqlx::query!(
+			r#"INSERT INTO conversation_member (conversation_id, account_id)
+			SELECT $1, UNNEST($2::integer[])"#,
+			id,
+			&members,
+		)
+		.execute(&mut *tx)
+		.await?;
+
+		tx.commit().await?;
+		Ok(id.into())
+	}
+
+	/// Checks whether the account may send messages to all of the recipients,
+	/// taking blocks in either direction and the recipients' DM permissions into account
+	#[instrument(level = "debug", skip_all)]
+	pub async fn can_message(&self, pool: &PgPool, recipients: &[AccountId]) -> sqlx::Result<bool> {
+		let recipients: Vec<i32> = recipients.iter().map(|&id| id.into()).collect();
+		sqlx::query_scalar!(
+			r#"SELECT COALESCE(BOOL_AND(
+				NOT EXISTS(
+					SELECT * FROM block
+					WHERE (user_id = a.id AND blocked_id = $1) OR
+					(user_id = $1 AND blocked_id = a.id)
+				) AND (
+					a.dm_permission = 'everyone' OR
+					(a.dm_permission = 'following' AND
+					EXISTS(SELECT * FROM follow WHERE user_id = a.id AND followed_id = $1))
+				)
+			), TRUE) AS "allowed!"
+			FROM account a
+			WHERE a.id = ANY($2) AND a.id <> $1"#,
+			i32::from(self.id),
+			&recipients,
+		)
+		.fetch_one(pool)
+		.await
+	}
+}
+
+impl Conversation {
+	#[instrument(level = "debug", skip_all)]
+	pub async fn get_member_ids(&self, pool: &PgPool) -> sqlx::Result<Vec<AccountId>> {
+		sqlx::query_scalar!(
+			r#"SELECT account_id FROM conversation_member WHERE conversation_id = $1"#,
+			i32::from(self.id),
+		)
+		.fetch_all(pool)
+		.await
+		.map(|ids| ids.into_iter().map(Into::into).collect())
+	}
+
+	#[instrument(level = "debug", skip_all)]
+	pub async fn get_messages(
+		&self,
+		pool: &PgPool,
+		limit: u64,
+		user_id: impl Into<i32>,
+	) -> sqlx::Result<Vec<Message>> {
+		sqlx::query_as!(
+			Message,
+			r#"SELECT * FROM (
+				SELECT m.*,
+				a.handle AS author_handle
+				FROM message m, account a
+				WHERE m.conversation_id = $1 AND
+				a.id = m.author_id AND
+				NOT EXISTS(SELECT * FROM message_deletion WHERE message_id = m.id AND account_id = $3)
+				ORDER BY m.create_time DESC
+				LIMIT $2
+			) latest
+			ORDER BY create_time ASC"#,
+			i32::from(self.id),
+			limit as i64,
+			user_id.into(),
+		)
+		.fetch_all(pool)
+		.await
+	}
+
+	#[instrument(level = "debug", skip_all)]
+	pub async fn send_message(
+		&self,
+		pool: &PgPool,
+		author: &Account,
+		body: &str,
+	) -> sqlx::Result<Message> {
+		sqlx::query_as!(
+			Message,
+			r#"WITH inserted AS (
+				INSERT INTO message (conversation_id, author_id, body)
+				VALUES ($1, $2, $3)
+				RETURNING *
+			)
+			SELECT m.*,
+			a.handle AS author_handle
+			FROM inserted m, account a
+			WHERE a.id = m.author_id"#,
+			i32::from(self.id),
+			i32::from(author.id),
+			body,
+		)
+		.fetch_one(pool)
+		.await
+	}
+
+	#[instrument(level = "debug", skip_all)]
+	pub async fn mark_read(&self, pool: &PgPool, user_id: impl Into<i32>) -> sqlx::Result<()> {
+		sqlx::query!(
+			r#"UPDATE conversation_member SET last_read_time = CURRENT_TIMESTAMP
+			WHERE conversation_id = $1 AND account_id = $2"#,
+			i32::from(self.id),
+			user_id.into(),
+		)
+		.execute(pool)
+		.await
+		.map(|_| ())
+	}
+
+	/// Hides a message of this conversation for the given member only
+	#[instrument(level = "debug", skip_all)]
+	pub async fn delete_message(
+		&self,
+		pool: &PgPool,
+		message_id: impl Into<i64>,
+		user_id: impl Into<i32>,
+	) -> sqlx::Result<bool> {
+		sqlx::query!(
+			r#"INSERT INTO message_deletion (message_id, account_id)
+			SELECT id, $3 FROM message WHERE id = $1 AND conversation_id = $2
+			ON CONFLICT (message_id, account_id) DO NOTHING"#,
+			message_id.into(),
+			i32::from(self.id),
+			user_id.into(),
+		)
+		.execute(pool)
+		.await
+		.map(|res| res.rows_affected() > 0)
+	}
+}
@@ -0,0 +1,131 @@
+use sqlx::{
+	migrate::{Migrate, MigrateError, Migrator},
+	PgPool,
+};
+
+// the files in ./migrations, embedded into the binary
+static MIGRATOR: Migrator = sqlx::migrate!();
+
+pub struct MigrationStatus {
+	pub version: i64,
+	pub description: String,
+	pub applied: bool,
+}
+
+/// Applies every migration that hasn't been applied yet
+pub async fn run(pool: &PgPool) -> Result<(), MigrateError> {
+	adopt_existing_schema(pool).await?;
+	MIGRATOR.run(pool).await
+}
+
+/// Reverts the newest applied migration, returns its version
+pub async fn revert_last(pool: &PgPool) -> Result<Option<i64>, MigrateError> {
+	let applied = applied_versions(pool).await?;
+	let (last, target) = match applied.as_slice() {
+		[] => return Ok(None),
+		[.., before, last] => (*last, *before),
+		[last] => (*last, 0),
+	};
+	MIGRATOR.undo(pool, target).await?;
+	Ok(Some(last))
+}
+
+pub async fn status(pool: &PgPool) -> Result<Vec<MigrationStatus>, MigrateError> {
+	let applied = applied_versions(pool).await?;
+	Ok(MIGRATOR
+		.iter()
+		.filter(|m| !m.migration_type.is_down_migration())
+		.map(|m| MigrationStatus {
+			version: m.version,
+			description: m.description.to_string(),
+			applied: applied.contains(&m.version),
+		})
+		.collect())
+}
+
+/// The versions still to be applied, only reading, so it can run on every readiness check
+pub async fn pending(pool: &PgPool) -> Result<Vec<i64>, MigrateError> {
+	let mut conn = pool.acquire().await?;
+	let applied: Vec<i64> = conn
+		.list_applied_migrations()
+		.await?
+		.into_iter()
+		.map(|m| m.version)
+		.collect();
+	Ok(MIGRATOR
+		.iter()
+		.filter(|m| !m.migration_type.is_down_migration() && !applied.contains(&m.version))
+		.map(|m| m.version)
+		.collect())
+}
+
+async fn applied_versions(pool: &PgPool) -> Result<Vec<i64>, MigrateError> {
+	adopt_existing_schema(pool).await?;
+	let mut conn = pool.acquire().await?;
+	conn.ensure_migrations_table().await?;
+	let mut versions: Vec<i64> = conn
+		.list_applied_migrations()
+		.await?
+		.into_iter()
+		.map(|m| m.version)
+		.collect();
+	versions.sort_unstable();
+	Ok(versions)
+}
+
+// every table in the old init.sql, which the baseline migration creates
+const BASELINE_TABLES: &[&str] = &["account", "block", "follow", "post", "vote"];
+
+// databases created from the old init.sql already have the baseline schema, so it's only recorded as applied
+// and the migrations after it bring them up to date,
+// these queries aren't checked at compile time since the migrations table may not exist yet
+async fn adopt_existing_schema(pool: &PgPool) -> Result<(), MigrateError> {
+	let (has_account, has_migrations) = sqlx::query_as::<_, (bool, bool)>(
+		r#"SELECT
+		to_regclass('public.account') IS NOT NULL,
+		to_regclass('public._sqlx_migrations') IS NOT NULL"#,
+	)
+	.fetch_one(pool)
+	.await?;
+	if !has_account || has_migrations {
+		return Ok(());
+	}
+
+	// recording the baseline for a database that only has some of its tables would hide the missing ones
+	let missing: Vec<String> = sqlx::query_scalar(
+		r#"SELECT t FROM unnest($1::text[]) t WHERE to_regclass('public.' || t) IS NULL"#,
+	)
+	.bind(BASELINE_TABLES)
+	.fetch_all(pool)
+	.await?;
+	if !missing.is_empty() {
+		return Err(MigrateError::Source(
+			format!(
+				"the database has an account table but no {}, so it wasn't created from init.sql \
+				and can't be migrated, start from an empty database instead",
+				missing.join(", ")
+			)
+			.into(),
+		));
+	}
+
+	let baseline = match MIGRATOR
+		.iter()
+		.find(|m| !m.migration_type.is_down_migration())
+	{
+		Some(m) => m,
+		None => return Ok(()),
+	};
+	let mut conn = pool.acquire().await?;
+	conn.ensure_migrations_table().await?;
+	sqlx::query(
+		r#"INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time)
+		VALUES ($1, $2, TRUE, $3, 0)"#,
+	)
+	.bind(baseline.version)
+	.bind(&*baseline.description)
+	.bind(&*baseline.checksum)
+	.execute(&mut *conn)
+	.await?;
+	Ok(())
+}
@@ -1,26 +1,55 @@
 pub mod account;
+pub mod attempt;
 pub mod follow;
+pub mod handle;
+pub mod invite;
+#[cfg(test)]
+pub mod memory;
+pub mod message;
+pub mod migrate;
+pub mod moderation;
+pub mod mute;
+pub mod notification;
+pub mod password;
 pub mod post;
+pub mod report;
+pub mod stats;
+pub mod storage;
+pub mod two_factor;
 pub mod types;
 pub mod vote;
 
+use log::LevelFilter;
 use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
 use sqlx::{ConnectOptions, PgPool};
 use std::str::FromStr;
-use std::sync::LazyLock;
+use std::time::Duration;
+
+use crate::config::DatabaseConfig;
 
 const MAX_ITERATIONS: u8 = 100;
 
-static POOL: LazyLock<PgPool> = LazyLock::new(|| {
-	let conn_url = dotenvy::var("DATABASE_URL").unwrap();
-	let options = PgConnectOptions::from_str(&conn_url)
-		.unwrap()
-		.disable_statement_logging();
+/// Opens the pool and makes the first connection, so a wrong url or a database that is down shows up at startup
+pub async fn connect(config: &DatabaseConfig) -> sqlx::Result<PgPool> {
+	let conn_url = match &config.url {
+		Some(url) => url.clone(),
+		None => dotenvy::var("DATABASE_URL").map_err(|_| {
+			sqlx::Error::Configuration("set database.url in Rocket.toml or DATABASE_URL".into())
+		})?,
+	};
+	// every statement is a debug event with its duration, inside the span of the function that ran it
+	let options = PgConnectOptions::from_str(&conn_url)?
+		.log_statements(LevelFilter::Debug)
+		.log_slow_statements(
+			LevelFilter::Warn,
+			Duration::from_millis(config.slow_query_ms),
+		);
 
-	futures::executor::block_on(async move {
-		PgPoolOptions::new()
-			.connect_with(options)
-			.await
-			.expect("Could not connect to database")
-	})
-});
+	PgPoolOptions::new()
+		.max_connections(config.max_connections)
+		.min_connections(config.min_connections)
+		.acquire_timeout(Duration::from_secs(config.acquire_timeout))
+		.idle_timeout(config.idle_timeout.map(Duration::from_secs))
+		.connect_with(options)
+		.await
+}
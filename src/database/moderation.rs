@@ -0,0 +1,118 @@
+use chrono::NaiveDateTime;
+use sqlx::PgPool;
+use tracing::instrument;
+
+use super::{
+	account::Account,
+	types::{LogAction, Role},
+};
+
+#[derive(Debug)]
+pub struct LogEntry {
+	pub id: i32,
+	pub moderator_handle: Option<String>,
+	pub action: LogAction,
+	pub target_handle: Option<String>,
+	pub target_post_id: Option<i64>,
+	pub details: String,
+	pub create_time: NaiveDateTime,
+}
+impl LogEntry {
+	#[instrument(level = "debug", skip_all)]
+	pub async fn get_recent(pool: &PgPool, limit: u64) -> sqlx::Result<Vec<Self>> {
+		sqlx::query_as!(
+			LogEntry,
+			r#"SELECT l.id, l.action, l.target_post_id, l.details, l.create_time,
+			moderator.handle AS "moderator_handle?",
+			target.handle AS "target_handle?"
+			FROM moderation_log l
+			LEFT JOIN account moderator ON moderator.id = l.moderator_id
+			LEFT JOIN account target ON target.id = l.target_account_id
+			ORDER BY l.create_time DESC
+			LIMIT $1"#,
+			limit as i64,
+		)
+		.fetch_all(pool)
+		.await
+	}
+
+	/// Logs an action taken from the command line, where there is no moderator account
+	#[instrument(level = "debug", skip_all)]
+	pub async fn log_command(
+		pool: &PgPool,
+		action: LogAction,
+		target_account_id: Option<impl Into<i32>>,
+		target_post_id: Option<impl Into<i64>>,
+		details: &str,
+	) -> sqlx::Result<()> {
+		sqlx::query!(
+			r#"INSERT INTO moderation_log (action, target_account_id, target_post_id, details)
+			VALUES ($1, $2, $3, $4)"#,
+			action.as_str(),
+			target_account_id.map(Into::into),
+			target_post_id.map(Into::into),
+			details,
+		)
+		.execute(pool)
+		.await
+		.map(|_| ())
+	}
+}
+
+// moderation actions for account
+impl Account {
+	#[instrument(level = "debug", skip_all)]
+	pub async fn log_action(
+		&self,
+		pool: &PgPool,
+		action: LogAction,
+		target_account_id: Option<impl Into<i32>>,
+		target_post_id: Option<impl Into<i64>>,
+		details: &str,
+	) -> sqlx::Result<()> {
+		sqlx::query!(
+			r#"INSERT INTO moderation_log (moderator_id, action, target_account_id, target_post_id, details)
+			VALUES ($1, $2, $3, $4, $5)"#,
+			i32::from(self.id),
+			action.as_str(),
+			target_account_id.map(Into::into),
+			target_post_id.map(Into::into),
+			details,
+		)
+		.execute(pool)
+		.await
+		.map(|_| ())
+	}
+
+	#[instrument(level = "debug", skip_all)]
+	pub async fn set_role(&self, pool: &PgPool, role: Role) -> sqlx::Result<()> {
+		sqlx::query!(
+			r#"UPDATE account SET role = $1 WHERE id = $2"#,
+			role.as_str(),
+			i32::from(self.id),
+		)
+		.execute(pool)
+		.await
+		.map(|_| ())
+	}
+
+	/// Accounts whose handle starts with the query, for the admin panel
+	#[instrument(level = "debug", skip_all)]
+	pub async fn search(pool: &PgPool, query: &str, limit: u64) -> sqlx::Result<Vec<Account>> {
+		sqlx::query_as!(
+			Account,
+			r#"SELECT *,
+			(SELECT COUNT(*) FROM follow WHERE user_id = id) AS "following!",
+			(SELECT COUNT(*) FROM follow WHERE followed_id = id) AS "followers!",
+			FALSE AS "followed_by_user!"
+			FROM account
+			WHERE handle ILIKE REPLACE(REPLACE(REPLACE($1, '\', '\\'), '%', '\%'), '_', '\_') || '%'
+			ORDER BY CASE role WHEN 'admin' THEN 0 WHEN 'moderator' THEN 1 ELSE 2 END, handle
+			LIMIT $2"#,
+			query,
+			limit as i64,
+		)
+		.fetch_all(pool)
+		.await
+	}
+}
@@ -0,0 +1,236 @@
+use chrono::NaiveDateTime;
+use sqlx::PgPool;
+use tracing::instrument;
+
+use super::{
+	account::Account,
+	post::Post,
+	types::{AccountId, MutedWordId, PostId},
+};
+
+pub const MAX_PHRASE_LENGTH: usize = 100;
+
+#[derive(Debug)]
+pub struct MutedWord {
+	pub id: MutedWordId,
+	pub user_id: AccountId,
+	pub phrase: String,
+	pub create_time: NaiveDateTime,
+	pub expire_time: Option<NaiveDateTime>,
+}
+
+// muting accounts
+impl Account {
+	#[instrument(level = "debug", skip_all)]
+	pub async fn mute(&self, pool: &PgPool, muted_id: impl Into<i32>) -> sqlx::Result<()> {
+		sqlx::query!(
+			r#"INSERT INTO mute (user_id, muted_id)
+			VALUES ($1, $2)
+			ON CONFLICT (user_id, muted_id) DO NOTHING"#,
+			i32::from(self.id),
+			muted_id.into(),
+		)
+		.execute(pool)
+		.await
+		.map(|_| ())
+	}
+	#[instrument(level = "debug", skip_all)]
+	pub async fn unmute(&self, pool: &PgPool, muted_id: impl Into<i32>) -> sqlx::Result<()> {
+		sqlx::query!(
+			r#"DELETE FROM mute WHERE user_id = $1 AND muted_id = $2"#,
+			i32::from(self.id),
+			muted_id.into(),
+		)
+		.execute(pool)
+		.await
+		.map(|_| ())
+	}
+	#[instrument(level = "debug", skip_all)]
+	pub async fn is_muting(&self, pool: &PgPool, account_id: impl Into<i32>) -> sqlx::Result<bool> {
+		sqlx::query_scalar!(
+			r#"SELECT EXISTS(
+				SELECT * FROM mute WHERE user_id = $1 AND muted_id = $2
+			) AS "exists!""#,
+			i32::from(self.id),
+			account_id.into(),
+		)
+		.fetch_one(pool)
+		.await
+	}
+	#[instrument(level = "debug", skip_all)]
+	pub async fn get_muted_accounts(&self, pool: &PgPool) -> sqlx::Result<Vec<Account>> {
+		sqlx::query_as!(
+			Account,
+			r#"SELECT *,
+			(SELECT COUNT(*) FROM follow WHERE user_id = id) AS "following!",
+			(SELECT COUNT(*) FROM follow WHERE followed_id = id) AS "followers!",
+			EXISTS(SELECT * FROM follow WHERE user_id = $1 AND followed_id = id) AS "followed_by_user!"
+			FROM account
+			WHERE id IN (SELECT muted_id FROM mute WHERE user_id = $1)
+			ORDER BY handle"#,
+			i32::from(self.id),
+		)
+		.fetch_all(pool)
+		.await
+	}
+}
+
+// muting words
+impl Account {
+	#[instrument(level = "debug", skip_all)]
+	pub async fn mute_word(
+		&self,
+		pool: &PgPool,
+		phrase: &str,
+		days: Option<u32>,
+	) -> sqlx::Result<MutedWord> {
+		sqlx::query_as!(
+			MutedWord,
+			r#"INSERT INTO mute_word (user_id, phrase, expire_time)
+			VALUES (
+				$1,
+				$2,
+				CURRENT_TIMESTAMP + MAKE_INTERVAL(days => $3)
+			)
+			RETURNING *"#,
+			i32::from(self.id),
+			phrase,
+			days.map(|d| d as i32),
+		)
+		.fetch_one(pool)
+		.await
+	}
+	#[instrument(level = "debug", skip_all)]
+	pub async fn unmute_word(&self, pool: &PgPool, word_id: impl Into<i32>) -> sqlx::Result<bool> {
+		sqlx::query!(
+			r#"DELETE FROM mute_word WHERE id = $1 AND user_id = $2"#,
+			word_id.into(),
+			i32::from(self.id),
+		)
+		.execute(pool)
+		.await
+		.map(|res| res.rows_affected() > 0)
+	}
+	#[instrument(level = "debug", skip_all)]
+	pub async fn get_muted_words(&self, pool: &PgPool) -> sqlx::Result<Vec<MutedWord>> {
+		sqlx::query_as!(
+			MutedWord,
+			r#"SELECT * FROM mute_word
+			WHERE user_id = $1 AND
+			(expire_time IS NULL OR expire_time > CURRENT_TIMESTAMP)
+			ORDER BY create_time DESC"#,
+			i32::from(self.id),
+		)
+		.fetch_all(pool)
+		.await
+	}
+}
+
+// muting threads
+impl Account {
+	#[instrument(level = "debug", skip_all)]
+	pub async fn mute_thread(&self, pool: &PgPool, root_id: impl Into<i64>) -> sqlx::Result<()> {
+		sqlx::query!(
+			r#"INSERT INTO mute_thread (user_id, post_id)
+			VALUES ($1, $2)
+			ON CONFLICT (user_id, post_id) DO NOTHING"#,
+			i32::from(self.id),
+			root_id.into(),
+		)
+		.execute(pool)
+		.await
+		.map(|_| ())
+	}
+	#[instrument(level = "debug", skip_all)]
+	pub async fn unmute_thread(&self, pool: &PgPool, root_id: impl Into<i64>) -> sqlx::Result<()> {
+		sqlx::query!(
+			r#"DELETE FROM mute_thread WHERE user_id = $1 AND post_id = $2"#,
+			i32::from(self.id),
+			root_id.into(),
+		)
+		.execute(pool)
+		.await
+		.map(|_| ())
+	}
+	#[instrument(level = "debug", skip_all)]
+	pub async fn is_muting_thread(
+		&self,
+		pool: &PgPool,
+		root_id: impl Into<i64>,
+	) -> sqlx::Result<bool> {
+		sqlx::query_scalar!(
+			r#"SELECT EXISTS(
+				SELECT * FROM mute_thread WHERE user_id = $1 AND post_id = $2
+			) AS "exists!""#,
+			i32::from(self.id),
+			root_id.into(),
+		)
+		.fetch_one(pool)
+		.await
+	}
+	#[instrument(level = "debug", skip_all)]
+	pub async fn get_muted_threads(&self, pool: &PgPool) -> sqlx::Result<Vec<Post>> {
+		sqlx::query_as!(
+			Post,
+			r#"SELECT p.*,
+			a.handle AS author_handle,
+			a.username AS author_username,
+			(SELECT COUNT(*) FROM vote WHERE post_id = p.id) AS "votes!",
+			EXISTS(SELECT * FROM vote WHERE post_id = p.id AND voter_id = $1) as "voted_by_user!"
+			FROM post p, account a
+			WHERE a.id = p.author_id AND
+			p.id IN (SELECT post_id FROM mute_thread WHERE user_id = $1)
+			ORDER BY p.create_time DESC"#,
+			i32::from(self.id),
+		)
+		.fetch_all(pool)
+		.await
+	}
+}
+
+impl Post {
+	/// The top-level post this post is a reply to, or the post itself
+	#[instrument(level = "debug", skip_all)]
+	pub async fn get_root_id(&self, pool: &PgPool) -> sqlx::Result<PostId> {
+		sqlx::query_scalar!(
+			r#"WITH RECURSIVE ancestors AS (
+				SELECT id, parent_id FROM post WHERE id = $1
+				UNION ALL
+				SELECT p.id, p.parent_id FROM post p, ancestors an WHERE p.id = an.parent_id
+			)
+			SELECT id AS "id!" FROM ancestors WHERE parent_id IS NULL"#,
+			i64::from(self.id),
+		)
+		.fetch_optional(pool)
+		.await
+		.map(|id| id.map(Into::into).unwrap_or(self.id))
+	}
+
+	/// Whether the post is hidden from the user's timeline by a muted account or word,
+	/// or because its author is limited and not followed by the user
+	#[instrument(level = "debug", skip_all)]
+	pub async fn hidden_from(&self, pool: &PgPool, user_id: impl Into<i32>) -> sqlx::Result<bool> {
+		sqlx::query_scalar!(
+			r#"SELECT
+			(
+				a.limited AND
+				a.id <> $2 AND
+				NOT EXISTS(SELECT * FROM follow WHERE user_id = $2 AND followed_id = a.id)
+			) OR
+			EXISTS(SELECT * FROM mute WHERE user_id = $2 AND muted_id = p.author_id) OR
+			EXISTS(
+				SELECT * FROM mute_word w
+				WHERE w.user_id = $2 AND
+				(w.expire_time IS NULL OR w.expire_time > CURRENT_TIMESTAMP) AND
+				POSITION(LOWER(w.phrase) IN LOWER(p.body)) > 0
+			) AS "muted!"
+			FROM post p, account a
+			WHERE p.id = $1 AND a.id = p.author_id"#,
+			i64::from(self.id),
+			user_id.into(),
+		)
+		.fetch_optional(pool)
+		.await
+		.map(|muted| muted.unwrap_or(false))
+	}
+}
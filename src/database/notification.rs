@@ -0,0 +1,76 @@
+use chrono::NaiveDateTime;
+use sqlx::PgPool;
+use tracing::instrument;
+
+use super::{
+	account::Account,
+	types::{AccountId, NotificationKind, OptPostId, PgU64},
+};
+
+#[derive(Debug)]
+pub struct Notification {
+	pub id: i64,
+	pub account_id: AccountId,
+	pub actor_id: AccountId,
+	pub actor_handle: Box<str>,
+	pub kind: NotificationKind,
+	pub post_id: OptPostId,
+	pub create_time: NaiveDateTime,
+	pub seen: bool,
+	pub report_id: Option<i32>,
+	pub report_status: Option<String>,
+	pub report_action: Option<String>,
+}
+impl Notification {
+	#[instrument(level = "debug", skip_all)]
+	pub async fn count_unseen(pool: &PgPool, account_id: impl Into<i32>) -> sqlx::Result<PgU64> {
+		sqlx::query_scalar!(
+			r#"SELECT COUNT(*) AS "count!" FROM notification
+			WHERE account_id = $1 AND NOT seen"#,
+			account_id.into(),
+		)
+		.fetch_one(pool)
+		.await
+		.map(Into::into)
+	}
+}
+
+// notification actions for account
+impl Account {
+	#[instrument(level = "debug", skip_all)]
+	pub async fn get_notifications(
+		&self,
+		pool: &PgPool,
+		limit: u64,
+	) -> sqlx::Result<Vec<Notification>> {
+		sqlx::query_as!(
+			Notification,
+			r#"SELECT n.*,
+			a.handle AS actor_handle,
+			r.status AS "report_status?",
+			r.action AS "report_action?"
+			FROM notification n
+			JOIN account a ON a.id = n.actor_id
+			LEFT JOIN report r ON r.id = n.report_id
+			WHERE n.account_id = $1
+			ORDER BY n.create_time DESC
+			LIMIT $2"#,
+			i32::from(self.id),
+			limit as i64,
+		)
+		.fetch_all(pool)
+		.await
+	}
+
+	#[instrument(level = "debug", skip_all)]
+	pub async fn mark_notifications_seen(&self, pool: &PgPool) -> sqlx::Result<()> {
+		sqlx::query!(
+			r#"UPDATE notification SET seen = TRUE
+			WHERE account_id = $1 AND NOT seen"#,
+			i32::from(self.id),
+		)
+		.execute(pool)
+		.await
+		.map(|_| ())
+	}
+}
@@ -0,0 +1,205 @@
+use chrono::{TimeDelta, Utc};
+use sqlx::{Error::Database, PgPool};
+use tracing::instrument;
+
+use super::{account::Account, MAX_ITERATIONS};
+use crate::crypto;
+
+pub const MAX_EMAIL_LENGTH: usize = 254;
+
+// how long a reset link stays valid
+const RESET_TOKEN_HOURS: i64 = 1;
+// how long a verification link stays valid
+pub const VERIFICATION_LINK_HOURS: i64 = 24;
+
+// password and email actions for account
+impl Account {
+	/// Only verified addresses count, anyone could have typed in an unverified one
+	#[instrument(level = "debug", skip_all)]
+	pub async fn find_by_verified_email(pool: &PgPool, email: &str) -> sqlx::Result<Option<Self>> {
+		sqlx::query_as!(
+			Account,
+			r#"SELECT *,
+			(SELECT COUNT(*) FROM follow WHERE user_id = id) AS "following!",
+			(SELECT COUNT(*) FROM follow WHERE followed_id = id) AS "followers!",
+			FALSE AS "followed_by_user!"
+			FROM account WHERE LOWER(email) = LOWER($1) AND email_verified"#,
+			email,
+		)
+		.fetch_optional(pool)
+		.await
+	}
+
+	/// Returns false if another account has verified the email, a different address has to be verified again
+	#[instrument(level = "debug", skip_all)]
+	pub async fn set_email(&self, pool: &PgPool, email: Option<&str>) -> sqlx::Result<bool> {
+		match sqlx::query!(
+			r#"UPDATE account SET
+			email = $1,
+			email_verified = email_verified AND lower(email) IS NOT DISTINCT FROM lower($1::varchar)
+			WHERE id = $2 AND NOT EXISTS(
+				SELECT * FROM account other
+				WHERE lower(other.email) = lower($1::varchar) AND other.email_verified AND other.id <> $2
+			)"#,
+			email,
+			i32::from(self.id),
+		)
+		.execute(pool)
+		.await
+		{
+			Ok(res) => Ok(res.rows_affected() == 1),
+			// verified by someone else in the meantime
+			Err(Database(err)) if err.is_unique_violation() => Ok(false),
+			Err(e) => Err(e),
+		}
+	}
+
+	/// A signed token for verifying the current email, nothing has to be stored for it
+	pub fn email_verification_token(&self) -> Option<String> {
+		let email = self.email.as_ref()?.to_lowercase();
+		let expires = (Utc::now() + TimeDelta::hours(VERIFICATION_LINK_HOURS)).timestamp();
+		let signature = crypto::sign(&format!("{}.{expires}.{email}", self.id.0));
+		Some(format!("{}.{expires}.{signature}", self.id.0))
+	}
+
+	/// Marks the email as verified if the token was made for the account's current address and hasn't expired
+	#[instrument(level = "debug", skip_all)]
+	pub async fn verify_email(pool: &PgPool, token: &str) -> sqlx::Result<bool> {
+		let mut parts = token.splitn(3, '.');
+		let (id, expires, signature) = match (parts.next(), parts.next(), parts.next()) {
+			(Some(id), Some(expires), Some(signature)) => (id, expires, signature),
+			_ => return Ok(false),
+		};
+		let (id, expires) = match (id.parse::<i32>(), expires.parse::<i64>()) {
+			(Ok(id), Ok(expires)) => (id, expires),
+			_ => return Ok(false),
+		};
+		if expires < Utc::now().timestamp() {
+			return Ok(false);
+		}
+
+		let email = match Self::find_by_id(pool, id, None::<i32>).await? {
+			Some(Account {
+				email: Some(email), ..
+			}) => email.to_lowercase(),
+			_ => return Ok(false),
+		};
+		// the address is part of the signature, so changing it makes older links useless
+		if !crypto::verify_signature(&format!("{id}.{expires}.{email}"), signature) {
+			return Ok(false);
+		}
+
+		let mut tx = pool.begin().await?;
+		// whoever verifies an address first gets it, the other claims on it are dropped
+		sqlx::query!(
+			r#"UPDATE account SET email = NULL
+			WHERE lower(email) = $1 AND NOT email_verified AND id <> $2"#,
+			email,
+			id,
+		)
+		.execute(&mut *tx)
+		.await?;
+		let verified = sqlx::query!(
+			r#"UPDATE account SET email_verified = TRUE WHERE id = $1"#,
+			id,
+		)
+		.execute(&mut *tx)
+		.await;
+		match verified {
+			Ok(_) => (),
+			// someone else has verified it already
+			Err(Database(err)) if err.is_unique_violation() => return Ok(false),
+			Err(e) => return Err(e),
+		}
+		tx.commit().await?;
+		Ok(true)
+	}
+
+	/// Changes the password and signs out every session, returns the new session epoch
+	#[instrument(level = "debug", skip_all)]
+	pub async fn set_password(&self, pool: &PgPool, password: &str) -> sqlx::Result<i32> {
+		let mut tx = pool.begin().await?;
+
+		let epoch = sqlx::query_scalar!(
+			r#"UPDATE account SET
+			password_hash = $1,
+			session_epoch = session_epoch + 1
+			WHERE id = $2
+			RETURNING session_epoch"#,
+			&crypto::encode_password(password, MAX_ITERATIONS),
+			i32::from(self.id),
+		)
+		.fetch_one(&mut *tx)
+		.await?;
+		// any reset link that was still around is useless now
+		sqlx::query!(
+			r#"DELETE FROM password_reset WHERE account_id = $1"#,
+			i32::from(self.id),
+		)
+		.execute(&mut *tx)
+		.await?;
+
+		tx.commit().await?;
+		Ok(epoch)
+	}
+
+	#[instrument(level = "debug", skip_all)]
+	pub async fn get_session_epoch(pool: &PgPool, id: impl Into<i32>) -> sqlx::Result<Option<i32>> {
+		sqlx::query_scalar!(
+			r#"SELECT session_epoch FROM account WHERE id = $1"#,
+			id.into(),
+		)
+		.fetch_optional(pool)
+		.await
+	}
+
+	/// Creates a reset token for the account, only its hash is stored
+	#[instrument(level = "debug", skip_all)]
+	pub async fn create_password_reset(&self, pool: &PgPool) -> sqlx::Result<String> {
+		let token = crypto::generate_token();
+		sqlx::query!(
+			r#"INSERT INTO password_reset (token_hash, account_id, expire_time)
+			VALUES ($1, $2, $3)"#,
+			&crypto::hash_token(&token),
+			i32::from(self.id),
+			Utc::now().naive_utc() + TimeDelta::hours(RESET_TOKEN_HOURS),
+		)
+		.execute(pool)
+		.await?;
+		Ok(token)
+	}
+
+	/// The handle of the account a still valid token belongs to
+	#[instrument(level = "debug", skip_all)]
+	pub async fn password_reset_handle(pool: &PgPool, token: &str) -> sqlx::Result<Option<String>> {
+		sqlx::query_scalar!(
+			r#"SELECT a.handle FROM password_reset r
+			JOIN account a ON a.id = r.account_id
+			WHERE r.token_hash = $1 AND r.expire_time > CURRENT_TIMESTAMP"#,
+			&crypto::hash_token(token),
+		)
+		.fetch_optional(pool)
+		.await
+	}
+
+	/// Uses up the token and returns its account if it was still valid
+	#[instrument(level = "debug", skip_all)]
+	pub async fn consume_password_reset(pool: &PgPool, token: &str) -> sqlx::Result<Option<Self>> {
+		sqlx::query_as!(
+			Account,
+			r#"WITH used AS (
+				DELETE FROM password_reset WHERE token_hash = $1
+				RETURNING account_id, expire_time
+			)
+			SELECT a.*,
+			(SELECT COUNT(*) FROM follow WHERE user_id = a.id) AS "following!",
+			(SELECT COUNT(*) FROM follow WHERE followed_id = a.id) AS "followers!",
+			FALSE AS "followed_by_user!"
+			FROM account a, used
+			WHERE a.id = used.account_id AND used.expire_time > CURRENT_TIMESTAMP"#,
+			&crypto::hash_token(token),
+		)
+		.fetch_optional(pool)
+		.await
+	}
+}
@@ -1,13 +1,14 @@
 use chrono::NaiveDateTime;
+use sqlx::PgPool;
+use tracing::instrument;
 
 use super::{
 	account::Account,
 	types::{AccountId, OptPostId, PgU64, PostId},
 	vote::Vote,
-	POOL,
 };
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Post {
 	pub id: PostId,
 	pub author_id: AccountId,
@@ -20,7 +21,9 @@ pub struct Post {
 	pub voted_by_user: bool,
 }
 impl Post {
+	#[instrument(level = "debug", skip_all)]
 	pub async fn find_by_id(
+		pool: &PgPool,
 		post_id: impl Into<i64>,
 		user_id: Option<impl Into<i32>>,
 	) -> sqlx::Result<Option<Self>> {
@@ -36,10 +39,12 @@ impl Post {
 			post_id.into(),
 			user_id.map(Into::into),
 		)
-		.fetch_optional(&*POOL)
+		.fetch_optional(pool)
 		.await
 	}
+	#[instrument(level = "debug", skip_all)]
 	pub async fn get_recent(
+		pool: &PgPool,
 		limit: u64,
 		user_id: Option<impl Into<i32>>,
 	) -> sqlx::Result<Vec<Self>> {
@@ -52,28 +57,43 @@ impl Post {
 			EXISTS(SELECT * FROM vote WHERE post_id = p.id AND voter_id = $2) as "voted_by_user!"
 			FROM post p, account a
 			WHERE a.id = p.author_id AND
-			p.parent_id IS NULL
+			p.parent_id IS NULL AND
+			(
+				NOT a.limited OR
+				a.id = $2 OR
+				EXISTS(SELECT * FROM follow WHERE user_id = $2 AND followed_id = a.id)
+			) AND
+			NOT EXISTS(SELECT * FROM mute WHERE user_id = $2 AND muted_id = p.author_id) AND
+			NOT EXISTS(
+				SELECT * FROM mute_word w
+				WHERE w.user_id = $2 AND
+				(w.expire_time IS NULL OR w.expire_time > CURRENT_TIMESTAMP) AND
+				POSITION(LOWER(w.phrase) IN LOWER(p.body)) > 0
+			)
 			ORDER BY create_time DESC
 			LIMIT $1"#,
 			limit as i64,
 			user_id.map(Into::into),
 		)
-		.fetch_all(&*POOL)
+		.fetch_all(pool)
 		.await
 	}
 
-	pub async fn delete(self) -> sqlx::Result<Option<PostId>> {
+	#[instrument(level = "debug", skip_all)]
+	pub async fn delete(self, pool: &PgPool) -> sqlx::Result<Option<PostId>> {
 		sqlx::query_scalar!(
 			r#"DELETE FROM post WHERE id = $1 RETURNING parent_id"#,
 			i64::from(self.id),
 		)
-		.fetch_one(&*POOL)
+		.fetch_one(pool)
 		.await
 		.map(|o| o.map(Into::into))
 	}
 
+	#[instrument(level = "debug", skip_all)]
 	pub async fn get_replies(
 		&self,
+		pool: &PgPool,
 		limit: u64,
 		user_id: Option<impl Into<i32>>,
 	) -> sqlx::Result<Vec<Post>> {
@@ -92,15 +112,17 @@ impl Post {
 			limit as i64,
 			user_id.map(Into::into),
 		)
-		.fetch_all(&*POOL)
+		.fetch_all(pool)
 		.await
 	}
 }
 
 // post actions for account
 impl Account {
+	#[instrument(level = "debug", skip_all)]
 	pub async fn get_posts(
 		&self,
+		pool: &PgPool,
 		limit: u64,
 		include_replies: bool,
 		user_id: Option<impl Into<i32>>,
@@ -126,21 +148,37 @@ impl Account {
 			limit as i64,
 			user_id.map(Into::into),
 		)
-		.fetch_all(&*POOL)
+		.fetch_all(pool)
 		.await
 	}
 
+	#[instrument(level = "debug", skip_all)]
 	pub async fn create_post(
 		&self,
+		pool: &PgPool,
 		body: &str,
 		parent_id: Option<impl Into<i64>>,
 	) -> sqlx::Result<Post> {
 		sqlx::query_as!(
 			Post,
-			r#"WITH inserted AS (
+			r#"WITH RECURSIVE inserted AS (
 				INSERT INTO post (author_id, body, parent_id)
 				VALUES ($1, $2, $3)
 				RETURNING *
+			), ancestors AS (
+				SELECT id, parent_id FROM post WHERE id = $3
+				UNION ALL
+				SELECT p.id, p.parent_id FROM post p, ancestors an WHERE p.id = an.parent_id
+			), notified AS (
+				INSERT INTO notification (account_id, actor_id, kind, post_id)
+				SELECT parent.author_id, p.author_id, 'reply', p.id
+				FROM inserted p, post parent
+				WHERE parent.id = p.parent_id AND
+				parent.author_id <> p.author_id AND
+				NOT EXISTS(
+					SELECT * FROM mute_thread m, ancestors an
+					WHERE m.user_id = parent.author_id AND m.post_id = an.id
+				)
 			)
 			SELECT p.*,
 			a.handle AS author_handle,
@@ -153,37 +191,54 @@ impl Account {
 			body,
 			parent_id.map(Into::into),
 		)
-		.fetch_one(&*POOL)
+		.fetch_one(pool)
 		.await
 	}
-	pub async fn post(&self, body: &str) -> sqlx::Result<Post> {
-		self.create_post(body, None as Option<i64>).await
+	#[instrument(level = "debug", skip_all)]
+	pub async fn post(&self, pool: &PgPool, body: &str) -> sqlx::Result<Post> {
+		self.create_post(pool, body, None as Option<i64>).await
 	}
-	pub async fn reply(&self, body: &str, parent_id: impl Into<i64>) -> sqlx::Result<Post> {
-		self.create_post(body, Some(parent_id)).await
+	#[instrument(level = "debug", skip_all)]
+	pub async fn reply(
+		&self,
+		pool: &PgPool,
+		body: &str,
+		parent_id: impl Into<i64>,
+	) -> sqlx::Result<Post> {
+		self.create_post(pool, body, Some(parent_id)).await
 	}
 
-	pub async fn add_vote(&self, post_id: impl Into<i64>) -> sqlx::Result<Vote> {
+	#[instrument(level = "debug", skip_all)]
+	pub async fn add_vote(&self, pool: &PgPool, post_id: impl Into<i64>) -> sqlx::Result<Vote> {
 		sqlx::query_as!(
 			Vote,
-			r#"INSERT INTO vote (voter_id, post_id)
-			VALUES ($1, $2)
-			ON CONFLICT (voter_id, post_id) DO NOTHING
-			RETURNING *"#,
+			r#"WITH inserted AS (
+				INSERT INTO vote (voter_id, post_id)
+				VALUES ($1, $2)
+				ON CONFLICT (voter_id, post_id) DO NOTHING
+				RETURNING *
+			), notified AS (
+				INSERT INTO notification (account_id, actor_id, kind, post_id)
+				SELECT p.author_id, v.voter_id, 'like', v.post_id
+				FROM inserted v, post p
+				WHERE p.id = v.post_id AND p.author_id <> v.voter_id
+			)
+			SELECT voter_id AS "voter_id!", post_id AS "post_id!" FROM inserted"#,
 			i32::from(self.id),
 			post_id.into(),
 		)
-		.fetch_one(&*POOL)
+		.fetch_one(pool)
 		.await
 	}
-	pub async fn remove_vote(&self, post_id: impl Into<i64>) -> sqlx::Result<()> {
+	#[instrument(level = "debug", skip_all)]
+	pub async fn remove_vote(&self, pool: &PgPool, post_id: impl Into<i64>) -> sqlx::Result<()> {
 		sqlx::query_as!(
 			Vote,
 			r#"DELETE FROM vote WHERE voter_id = $1 AND post_id = $2"#,
 			i32::from(self.id),
 			post_id.into(),
 		)
-		.execute(&*POOL)
+		.execute(pool)
 		.await
 		.map(|_| ())
 	}
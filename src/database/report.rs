@@ -0,0 +1,150 @@
+use chrono::NaiveDateTime;
+use sqlx::PgPool;
+use tracing::instrument;
+
+use super::{
+	account::Account,
+	types::{AccountId, OptPostId, ReportId, ReportReason, ReportStatus},
+};
+
+#[derive(Debug)]
+pub struct Report {
+	pub id: ReportId,
+	pub reporter_id: AccountId,
+	pub reporter_handle: Box<str>,
+	pub account_id: AccountId,
+	pub account_handle: Box<str>,
+	pub post_id: OptPostId,
+	pub post_body: Option<String>,
+	pub reason: ReportReason,
+	pub details: Option<String>,
+	pub status: ReportStatus,
+	pub action: Option<String>,
+	pub moderator_handle: Option<String>,
+	pub create_time: NaiveDateTime,
+	pub resolve_time: Option<NaiveDateTime>,
+}
+impl Report {
+	#[instrument(level = "debug", skip_all)]
+	pub async fn find_by_id(
+		pool: &PgPool,
+		report_id: impl Into<i32>,
+	) -> sqlx::Result<Option<Self>> {
+		sqlx::query_as!(
+			Report,
+			r#"SELECT r.id, r.reporter_id, r.account_id, r.post_id, r.reason, r.details,
+			r.status, r.action, r.create_time, r.resolve_time,
+			reporter.handle AS reporter_handle,
+			reported.handle AS account_handle,
+			p.body AS "post_body?",
+			moderator.handle AS "moderator_handle?"
+			FROM report r
+			JOIN account reporter ON reporter.id = r.reporter_id
+			JOIN account reported ON reported.id = r.account_id
+			LEFT JOIN post p ON p.id = r.post_id
+			LEFT JOIN account moderator ON moderator.id = r.moderator_id
+			WHERE r.id = $1"#,
+			report_id.into(),
+		)
+		.fetch_optional(pool)
+		.await
+	}
+
+	/// Reports in the given state, oldest open reports first and newest closed reports first
+	#[instrument(level = "debug", skip_all)]
+	pub async fn get_by_status(
+		pool: &PgPool,
+		status: ReportStatus,
+		limit: u64,
+	) -> sqlx::Result<Vec<Self>> {
+		sqlx::query_as!(
+			Report,
+			r#"SELECT r.id, r.reporter_id, r.account_id, r.post_id, r.reason, r.details,
+			r.status, r.action, r.create_time, r.resolve_time,
+			reporter.handle AS reporter_handle,
+			reported.handle AS account_handle,
+			p.body AS "post_body?",
+			moderator.handle AS "moderator_handle?"
+			FROM report r
+			JOIN account reporter ON reporter.id = r.reporter_id
+			JOIN account reported ON reported.id = r.account_id
+			LEFT JOIN post p ON p.id = r.post_id
+			LEFT JOIN account moderator ON moderator.id = r.moderator_id
+			WHERE r.status = $1
+			ORDER BY
+			CASE WHEN r.status = 'open' THEN r.create_time END ASC,
+			r.resolve_time DESC
+			LIMIT $2"#,
+			status.as_str(),
+			limit as i64,
+		)
+		.fetch_all(pool)
+		.await
+	}
+
+	/// Closes this report along with every other open report about the same post or account,
+	/// notifying each reporter of the outcome
+	#[instrument(level = "debug", skip_all)]
+	pub async fn resolve(
+		&self,
+		pool: &PgPool,
+		moderator: &Account,
+		status: ReportStatus,
+		action: &str,
+	) -> sqlx::Result<()> {
+		sqlx::query!(
+			r#"WITH resolved AS (
+				UPDATE report SET
+				status = $2,
+				action = $3,
+				moderator_id = $4,
+				resolve_time = CURRENT_TIMESTAMP
+				WHERE status = 'open' AND (
+					id = $1 OR (
+						account_id = $5 AND
+						(post_id = $6 OR (post_id IS NULL AND $6::bigint IS NULL))
+					)
+				)
+				RETURNING *
+			)
+			INSERT INTO notification (account_id, actor_id, kind, report_id)
+			SELECT reporter_id, account_id, 'report', id FROM resolved"#,
+			i32::from(self.id),
+			status.as_str(),
+			action,
+			i32::from(moderator.id),
+			i32::from(self.account_id),
+			self.post_id.0.map(i64::from),
+		)
+		.execute(pool)
+		.await
+		.map(|_| ())
+	}
+}
+
+// report actions for account
+impl Account {
+	#[instrument(level = "debug", skip_all)]
+	pub async fn report(
+		&self,
+		pool: &PgPool,
+		account_id: impl Into<i32>,
+		post_id: Option<impl Into<i64>>,
+		reason: ReportReason,
+		details: Option<&str>,
+	) -> sqlx::Result<ReportId> {
+		sqlx::query_scalar!(
+			r#"INSERT INTO report (reporter_id, account_id, post_id, reason, details)
+			VALUES ($1, $2, $3, $4, $5)
+			RETURNING id"#,
+			i32::from(self.id),
+			account_id.into(),
+			post_id.map(Into::into),
+			reason.as_str(),
+			details,
+		)
+		.fetch_one(pool)
+		.await
+		.map(Into::into)
+	}
+}
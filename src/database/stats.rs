@@ -0,0 +1,41 @@
+use sqlx::PgPool;
+use tracing::instrument;
+
+/// Totals for the whole instance, printed by `my-twitter stats`
+#[derive(Debug)]
+pub struct Stats {
+	pub accounts: i64,
+	pub pending: i64,
+	pub suspended: i64,
+	pub moderators: i64,
+	pub admins: i64,
+	pub posts: i64,
+	pub replies: i64,
+	pub likes: i64,
+	pub follows: i64,
+	pub messages: i64,
+	pub open_reports: i64,
+}
+impl Stats {
+	#[instrument(level = "debug", skip_all)]
+	pub async fn get(pool: &PgPool) -> sqlx::Result<Self> {
+		sqlx::query_as!(
+			Stats,
+			r#"SELECT
+			(SELECT COUNT(*) FROM account) AS "accounts!",
+			(SELECT COUNT(*) FROM account WHERE pending) AS "pending!",
+			(SELECT COUNT(*) FROM account WHERE suspension_reason IS NOT NULL
+				AND (suspension_end IS NULL OR suspension_end > CURRENT_TIMESTAMP)) AS "suspended!",
+			(SELECT COUNT(*) FROM account WHERE role = 'moderator') AS "moderators!",
+			(SELECT COUNT(*) FROM account WHERE role = 'admin') AS "admins!",
+			(SELECT COUNT(*) FROM post WHERE parent_id IS NULL) AS "posts!",
+			(SELECT COUNT(*) FROM post WHERE parent_id IS NOT NULL) AS "replies!",
+			(SELECT COUNT(*) FROM vote) AS "likes!",
+			(SELECT COUNT(*) FROM follow) AS "follows!",
+			(SELECT COUNT(*) FROM message) AS "messages!",
+			(SELECT COUNT(*) FROM report WHERE status = 'open') AS "open_reports!""#,
+		)
+		.fetch_one(pool)
+		.await
+	}
+}
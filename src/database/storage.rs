@@ -0,0 +1,294 @@
+use sqlx::PgPool;
+
+use super::{
+	account::{Account, AccountError},
+	follow::Follow,
+	post::Post,
+	types::{AccountId, DmPermission, LogAction, PostId},
+	vote::Vote,
+};
+
+/// The storage routes are handed through managed state
+pub type Store = Box<dyn Storage>;
+
+/// Everything the core routes read and write: accounts, posts, votes and follows,
+/// and the mute state, old handles and moderation log entries that come up on their pages.
+/// The other routes, from logging in and settings to messages, reports and the admin pages,
+/// still query postgres directly, so they're only mounted by `app` next to a pool
+#[rocket::async_trait]
+pub trait Storage: Send + Sync {
+	// accounts
+	async fn find_account_by_id(
+		&self,
+		id: AccountId,
+		user_id: Option<AccountId>,
+	) -> sqlx::Result<Option<Account>>;
+	async fn find_account_by_handle(
+		&self,
+		handle: &str,
+		user_id: Option<AccountId>,
+	) -> sqlx::Result<Option<Account>>;
+	async fn login<'a>(
+		&self,
+		handle: &'a str,
+		password: &'a str,
+	) -> Result<Account, AccountError<'a>>;
+	async fn register<'a>(
+		&self,
+		handle: &'a str,
+		password: &'a str,
+		invite: Option<&'a str>,
+		pending: bool,
+	) -> Result<Account, AccountError<'a>>;
+	async fn get_session_epoch(&self, id: AccountId) -> sqlx::Result<Option<i32>>;
+	async fn set_dm_permission(
+		&self,
+		account: &Account,
+		permission: DmPermission,
+	) -> sqlx::Result<()>;
+	async fn suspend(&self, account: &Account, reason: &str, days: Option<u32>)
+		-> sqlx::Result<()>;
+	async fn limit(&self, account: &Account) -> sqlx::Result<()>;
+	async fn lift_restrictions(&self, account: &Account) -> sqlx::Result<()>;
+	async fn delete_account(&self, account: Account) -> sqlx::Result<()>;
+	/// The current handle of the account that used to have this one
+	async fn find_handle_by_old_handle(&self, handle: &str) -> sqlx::Result<Option<String>>;
+
+	// posts
+	async fn find_post_by_id(
+		&self,
+		id: PostId,
+		user_id: Option<AccountId>,
+	) -> sqlx::Result<Option<Post>>;
+	async fn get_recent_posts(
+		&self,
+		limit: u64,
+		user_id: Option<AccountId>,
+	) -> sqlx::Result<Vec<Post>>;
+	async fn get_replies(
+		&self,
+		post: &Post,
+		limit: u64,
+		user_id: Option<AccountId>,
+	) -> sqlx::Result<Vec<Post>>;
+	async fn get_posts(
+		&self,
+		account: &Account,
+		limit: u64,
+		include_replies: bool,
+		user_id: Option<AccountId>,
+	) -> sqlx::Result<Vec<Post>>;
+	async fn create_post(
+		&self,
+		author: &Account,
+		body: &str,
+		parent_id: Option<PostId>,
+	) -> sqlx::Result<Post>;
+	/// Returns the parent of the deleted post
+	async fn delete_post(&self, post: Post) -> sqlx::Result<Option<PostId>>;
+
+	// votes
+	async fn add_vote(&self, voter: &Account, post_id: PostId) -> sqlx::Result<Vote>;
+	async fn remove_vote(&self, voter: &Account, post_id: PostId) -> sqlx::Result<()>;
+	async fn get_voters(
+		&self,
+		post: &Post,
+		user_id: Option<AccountId>,
+	) -> sqlx::Result<Vec<Account>>;
+	async fn voted_by(&self, post: &Post, account_id: AccountId) -> sqlx::Result<bool>;
+	async fn get_voted_posts(
+		&self,
+		account: &Account,
+		user_id: Option<AccountId>,
+	) -> sqlx::Result<Vec<Post>>;
+
+	// follows
+	async fn follow(&self, account: &Account, followed_id: AccountId) -> sqlx::Result<Follow>;
+	async fn unfollow(&self, account: &Account, followed_id: AccountId) -> sqlx::Result<()>;
+
+	// mutes
+	async fn is_muting(&self, account: &Account, muted_id: AccountId) -> sqlx::Result<bool>;
+	/// Whether the account muted the thread the post is in
+	async fn is_muting_thread(&self, account: &Account, post: &Post) -> sqlx::Result<bool>;
+
+	// moderation
+	async fn log_action(
+		&self,
+		moderator: &Account,
+		action: LogAction,
+		account_id: Option<AccountId>,
+		post_id: Option<PostId>,
+		details: &str,
+	) -> sqlx::Result<()>;
+}
+
+/// The storage backed by postgres, using the queries on the database types
+pub struct PgStorage {
+	pool: PgPool,
+}
+impl PgStorage {
+	pub fn new(pool: PgPool) -> Self {
+		Self { pool }
+	}
+}
+
+#[rocket::async_trait]
+impl Storage for PgStorage {
+	async fn find_account_by_id(
+		&self,
+		id: AccountId,
+		user_id: Option<AccountId>,
+	) -> sqlx::Result<Option<Account>> {
+		Account::find_by_id(&self.pool, id, user_id).await
+	}
+	async fn find_account_by_handle(
+		&self,
+		handle: &str,
+		user_id: Option<AccountId>,
+	) -> sqlx::Result<Option<Account>> {
+		Account::find_by_handle(&self.pool, handle, user_id).await
+	}
+	async fn login<'a>(
+		&self,
+		handle: &'a str,
+		password: &'a str,
+	) -> Result<Account, AccountError<'a>> {
+		Account::login(&self.pool, handle, password).await
+	}
+	async fn register<'a>(
+		&self,
+		handle: &'a str,
+		password: &'a str,
+		invite: Option<&'a str>,
+		pending: bool,
+	) -> Result<Account, AccountError<'a>> {
+		Account::register(&self.pool, handle, password, invite, pending).await
+	}
+	async fn get_session_epoch(&self, id: AccountId) -> sqlx::Result<Option<i32>> {
+		Account::get_session_epoch(&self.pool, id).await
+	}
+	async fn set_dm_permission(
+		&self,
+		account: &Account,
+		permission: DmPermission,
+	) -> sqlx::Result<()> {
+		account.set_dm_permission(&self.pool, permission).await
+	}
+	async fn suspend(
+		&self,
+		account: &Account,
+		reason: &str,
+		days: Option<u32>,
+	) -> sqlx::Result<()> {
+		account.suspend(&self.pool, reason, days).await
+	}
+	async fn limit(&self, account: &Account) -> sqlx::Result<()> {
+		account.limit(&self.pool).await
+	}
+	async fn lift_restrictions(&self, account: &Account) -> sqlx::Result<()> {
+		account.lift_restrictions(&self.pool).await
+	}
+	async fn delete_account(&self, account: Account) -> sqlx::Result<()> {
+		account.delete(&self.pool).await
+	}
+	async fn find_handle_by_old_handle(&self, handle: &str) -> sqlx::Result<Option<String>> {
+		Account::find_by_old_handle(&self.pool, handle).await
+	}
+
+	async fn find_post_by_id(
+		&self,
+		id: PostId,
+		user_id: Option<AccountId>,
+	) -> sqlx::Result<Option<Post>> {
+		Post::find_by_id(&self.pool, id, user_id).await
+	}
+	async fn get_recent_posts(
+		&self,
+		limit: u64,
+		user_id: Option<AccountId>,
+	) -> sqlx::Result<Vec<Post>> {
+		Post::get_recent(&self.pool, limit, user_id).await
+	}
+	async fn get_replies(
+		&self,
+		post: &Post,
+		limit: u64,
+		user_id: Option<AccountId>,
+	) -> sqlx::Result<Vec<Post>> {
+		post.get_replies(&self.pool, limit, user_id).await
+	}
+	async fn get_posts(
+		&self,
+		account: &Account,
+		limit: u64,
+		include_replies: bool,
+		user_id: Option<AccountId>,
+	) -> sqlx::Result<Vec<Post>> {
+		account
+			.get_posts(&self.pool, limit, include_replies, user_id)
+			.await
+	}
+	async fn create_post(
+		&self,
+		author: &Account,
+		body: &str,
+		parent_id: Option<PostId>,
+	) -> sqlx::Result<Post> {
+		author.create_post(&self.pool, body, parent_id).await
+	}
+	async fn delete_post(&self, post: Post) -> sqlx::Result<Option<PostId>> {
+		post.delete(&self.pool).await
+	}
+
+	async fn add_vote(&self, voter: &Account, post_id: PostId) -> sqlx::Result<Vote> {
+		voter.add_vote(&self.pool, post_id).await
+	}
+	async fn remove_vote(&self, voter: &Account, post_id: PostId) -> sqlx::Result<()> {
+		voter.remove_vote(&self.pool, post_id).await
+	}
+	async fn get_voters(
+		&self,
+		post: &Post,
+		user_id: Option<AccountId>,
+	) -> sqlx::Result<Vec<Account>> {
+		post.get_voters(&self.pool, user_id).await
+	}
+	async fn voted_by(&self, post: &Post, account_id: AccountId) -> sqlx::Result<bool> {
+		post.voted_by(&self.pool, account_id).await
+	}
+	async fn get_voted_posts(
+		&self,
+		account: &Account,
+		user_id: Option<AccountId>,
+	) -> sqlx::Result<Vec<Post>> {
+		account.get_voted_posts(&self.pool, user_id).await
+	}
+
+	async fn follow(&self, account: &Account, followed_id: AccountId) -> sqlx::Result<Follow> {
+		account.follow(&self.pool, followed_id).await
+	}
+	async fn unfollow(&self, account: &Account, followed_id: AccountId) -> sqlx::Result<()> {
+		account.unfollow(&self.pool, followed_id).await
+	}
+
+	async fn is_muting(&self, account: &Account, muted_id: AccountId) -> sqlx::Result<bool> {
+		account.is_muting(&self.pool, muted_id).await
+	}
+	async fn is_muting_thread(&self, account: &Account, post: &Post) -> sqlx::Result<bool> {
+		let root_id = post.get_root_id(&self.pool).await?;
+		account.is_muting_thread(&self.pool, root_id).await
+	}
+
+	async fn log_action(
+		&self,
+		moderator: &Account,
+		action: LogAction,
+		account_id: Option<AccountId>,
+		post_id: Option<PostId>,
+		details: &str,
+	) -> sqlx::Result<()> {
+		moderator
+			.log_action(&self.pool, action, account_id, post_id, details)
+			.await
+	}
+}
@@ -0,0 +1,106 @@
+use sqlx::PgPool;
+use tracing::instrument;
+
+use super::{account::Account, types::PgU64};
+
+// two-factor actions for account
+impl Account {
+	pub fn has_two_factor(&self) -> bool {
+		self.totp_secret.is_some()
+	}
+
+	/// Turns two-factor authentication on, replacing any previous recovery codes
+	#[instrument(level = "debug", skip_all)]
+	pub async fn enable_two_factor(
+		&self,
+		pool: &PgPool,
+		secret: &[u8],
+		recovery_code_hashes: &[[u8; 64]],
+	) -> sqlx::Result<()> {
+		let hashes: Vec<Vec<u8>> = recovery_code_hashes.iter().map(|h| h.to_vec()).collect();
+
+		let mut tx = pool.begin().await?;
+
+		sqlx::query!(
+			r#"UPDATE account SET totp_secret = $1, totp_last_step = NULL WHERE id = $2"#,
+			secret,
+			i32::from(self.id),
+		)
+		.execute(&mut *tx)
+		.await?;
+		sqlx::query!(
+			r#"DELETE FROM recovery_code WHERE account_id = $1"#,
+			i32::from(self.id),
+		)
+		.execute(&mut *tx)
+		.await?;
+		sqlx::query!(
+			r#"INSERT INTO recovery_code (account_id, code_hash)
+			SELECT $1, UNNEST($2::bytea[])"#,
+			i32::from(self.id),
+			&hashes,
+		)
+		.execute(&mut *tx)
+		.await?;
+
+		tx.commit().await
+	}
+
+	#[instrument(level = "debug", skip_all)]
+	pub async fn disable_two_factor(&self, pool: &PgPool) -> sqlx::Result<()> {
+		let mut tx = pool.begin().await?;
+
+		sqlx::query!(
+			r#"UPDATE account SET totp_secret = NULL, totp_last_step = NULL WHERE id = $1"#,
+			i32::from(self.id),
+		)
+		.execute(&mut *tx)
+		.await?;
+		sqlx::query!(
+			r#"DELETE FROM recovery_code WHERE account_id = $1"#,
+			i32::from(self.id),
+		)
+		.execute(&mut *tx)
+		.await?;
+
+		tx.commit().await
+	}
+
+	/// Marks a time step as used, returns false if it or a later one was used already
+	#[instrument(level = "debug", skip_all)]
+	pub async fn use_totp_step(&self, pool: &PgPool, step: i64) -> sqlx::Result<bool> {
+		sqlx::query!(
+			r#"UPDATE account SET totp_last_step = $1
+			WHERE id = $2 AND (totp_last_step IS NULL OR totp_last_step < $1)"#,
+			step,
+			i32::from(self.id),
+		)
+		.execute(pool)
+		.await
+		.map(|res| res.rows_affected() > 0)
+	}
+
+	/// Consumes a recovery code, returns false if it doesn't exist
+	#[instrument(level = "debug", skip_all)]
+	pub async fn use_recovery_code(&self, pool: &PgPool, code_hash: &[u8]) -> sqlx::Result<bool> {
+		sqlx::query!(
+			r#"DELETE FROM recovery_code WHERE account_id = $1 AND code_hash = $2"#,
+			i32::from(self.id),
+			code_hash,
+		)
+		.execute(pool)
+		.await
+		.map(|res| res.rows_affected() > 0)
+	}
+
+	#[instrument(level = "debug", skip_all)]
+	pub async fn count_recovery_codes(&self, pool: &PgPool) -> sqlx::Result<PgU64> {
+		sqlx::query_scalar!(
+			r#"SELECT COUNT(*) AS "count!" FROM recovery_code WHERE account_id = $1"#,
+			i32::from(self.id),
+		)
+		.fetch_one(pool)
+		.await
+		.map(Into::into)
+	}
+}
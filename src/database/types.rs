@@ -113,3 +113,300 @@ impl From<Option<i64>> for OptPostId {
 		Self(value.map(From::from))
 	}
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct ConversationId(pub u32);
+impl From<u32> for ConversationId {
+	fn from(value: u32) -> Self {
+		Self(value)
+	}
+}
+impl From<i32> for ConversationId {
+	fn from(value: i32) -> Self {
+		Self(value as u32)
+	}
+}
+impl From<ConversationId> for i32 {
+	fn from(value: ConversationId) -> Self {
+		value.0 as i32
+	}
+}
+impl<'a> FromParam<'a> for ConversationId {
+	type Error = ParseIntError;
+	fn from_param(param: &'a str) -> Result<Self, Self::Error> {
+		Ok(Self(param.parse()?))
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct MessageId(pub u64);
+impl From<u64> for MessageId {
+	fn from(value: u64) -> Self {
+		Self(value)
+	}
+}
+impl From<i64> for MessageId {
+	fn from(value: i64) -> Self {
+		Self(value as u64)
+	}
+}
+impl From<MessageId> for i64 {
+	fn from(value: MessageId) -> Self {
+		value.0 as i64
+	}
+}
+impl<'a> FromParam<'a> for MessageId {
+	type Error = ParseIntError;
+	fn from_param(param: &'a str) -> Result<Self, Self::Error> {
+		Ok(Self(param.parse()?))
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct MutedWordId(pub u32);
+impl From<i32> for MutedWordId {
+	fn from(value: i32) -> Self {
+		Self(value as u32)
+	}
+}
+impl From<MutedWordId> for i32 {
+	fn from(value: MutedWordId) -> Self {
+		value.0 as i32
+	}
+}
+impl<'a> FromParam<'a> for MutedWordId {
+	type Error = ParseIntError;
+	fn from_param(param: &'a str) -> Result<Self, Self::Error> {
+		Ok(Self(param.parse()?))
+	}
+}
+
+/// Who is allowed to start conversations with an account
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, FromFormField)]
+#[serde(rename_all = "lowercase")]
+pub enum DmPermission {
+	#[default]
+	Everyone,
+	Following,
+	Nobody,
+}
+impl DmPermission {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			Self::Everyone => "everyone",
+			Self::Following => "following",
+			Self::Nobody => "nobody",
+		}
+	}
+}
+impl From<String> for DmPermission {
+	fn from(value: String) -> Self {
+		match value.as_str() {
+			"following" => Self::Following,
+			"nobody" => Self::Nobody,
+			_ => Self::Everyone,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NotificationKind {
+	Like,
+	Reply,
+	Follow,
+	Report,
+}
+impl NotificationKind {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			Self::Like => "like",
+			Self::Reply => "reply",
+			Self::Follow => "follow",
+			Self::Report => "report",
+		}
+	}
+}
+impl From<String> for NotificationKind {
+	fn from(value: String) -> Self {
+		match value.as_str() {
+			"like" => Self::Like,
+			"reply" => Self::Reply,
+			"report" => Self::Report,
+			_ => Self::Follow,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct ReportId(pub u32);
+impl From<i32> for ReportId {
+	fn from(value: i32) -> Self {
+		Self(value as u32)
+	}
+}
+impl From<ReportId> for i32 {
+	fn from(value: ReportId) -> Self {
+		value.0 as i32
+	}
+}
+impl<'a> FromParam<'a> for ReportId {
+	type Error = ParseIntError;
+	fn from_param(param: &'a str) -> Result<Self, Self::Error> {
+		Ok(Self(param.parse()?))
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, FromFormField)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportReason {
+	Spam,
+	Harassment,
+	Hate,
+	Violence,
+	Impersonation,
+	Other,
+}
+impl ReportReason {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			Self::Spam => "spam",
+			Self::Harassment => "harassment",
+			Self::Hate => "hate",
+			Self::Violence => "violence",
+			Self::Impersonation => "impersonation",
+			Self::Other => "other",
+		}
+	}
+}
+impl From<String> for ReportReason {
+	fn from(value: String) -> Self {
+		match value.as_str() {
+			"spam" => Self::Spam,
+			"harassment" => Self::Harassment,
+			"hate" => Self::Hate,
+			"violence" => Self::Violence,
+			"impersonation" => Self::Impersonation,
+			_ => Self::Other,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportStatus {
+	Open,
+	Resolved,
+	Dismissed,
+}
+impl ReportStatus {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			Self::Open => "open",
+			Self::Resolved => "resolved",
+			Self::Dismissed => "dismissed",
+		}
+	}
+}
+impl From<String> for ReportStatus {
+	fn from(value: String) -> Self {
+		match value.as_str() {
+			"resolved" => Self::Resolved,
+			"dismissed" => Self::Dismissed,
+			_ => Self::Open,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, FromFormField)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+	#[default]
+	User,
+	Moderator,
+	Admin,
+}
+impl Role {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			Self::User => "user",
+			Self::Moderator => "moderator",
+			Self::Admin => "admin",
+		}
+	}
+}
+impl From<String> for Role {
+	fn from(value: String) -> Self {
+		match value.as_str() {
+			"moderator" => Self::Moderator,
+			"admin" => Self::Admin,
+			_ => Self::User,
+		}
+	}
+}
+impl From<&str> for Role {
+	fn from(value: &str) -> Self {
+		value.to_owned().into()
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogAction {
+	DeletePost,
+	DismissReport,
+	Suspend,
+	Limit,
+	Lift,
+	SetRole,
+	Approve,
+	Reject,
+}
+impl LogAction {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			Self::DeletePost => "delete_post",
+			Self::DismissReport => "dismiss_report",
+			Self::Suspend => "suspend",
+			Self::Limit => "limit",
+			Self::Lift => "lift",
+			Self::SetRole => "set_role",
+			Self::Approve => "approve",
+			Self::Reject => "reject",
+		}
+	}
+}
+impl From<String> for LogAction {
+	fn from(value: String) -> Self {
+		match value.as_str() {
+			"delete_post" => Self::DeletePost,
+			"dismiss_report" => Self::DismissReport,
+			"suspend" => Self::Suspend,
+			"limit" => Self::Limit,
+			"lift" => Self::Lift,
+			"approve" => Self::Approve,
+			"reject" => Self::Reject,
+			_ => Self::SetRole,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttemptKind {
+	Ip,
+	Handle,
+	Register,
+	Reset,
+	Verify,
+}
+impl AttemptKind {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			Self::Ip => "ip",
+			Self::Handle => "handle",
+			Self::Register => "register",
+			Self::Reset => "reset",
+			Self::Verify => "verify",
+		}
+	}
+}
@@ -1,8 +1,10 @@
+use sqlx::PgPool;
+use tracing::instrument;
+
 use super::{
 	account::Account,
 	post::Post,
 	types::{AccountId, PostId},
-	POOL,
 };
 
 pub struct Vote {
@@ -11,7 +13,12 @@ pub struct Vote {
 }
 
 impl Post {
-	pub async fn get_voters(&self, user_id: Option<impl Into<i32>>) -> sqlx::Result<Vec<Account>> {
+	#[instrument(level = "debug", skip_all)]
+	pub async fn get_voters(
+		&self,
+		pool: &PgPool,
+		user_id: Option<impl Into<i32>>,
+	) -> sqlx::Result<Vec<Account>> {
 		sqlx::query_as!(
 			Account,
 			r#"SELECT *,
@@ -23,11 +30,12 @@ impl Post {
 			user_id.map(Into::into),
 			i64::from(self.id),
 		)
-		.fetch_all(&*POOL)
+		.fetch_all(pool)
 		.await
 	}
 
-	pub async fn voted_by(&self, account_id: impl Into<i32>) -> sqlx::Result<bool> {
+	#[instrument(level = "debug", skip_all)]
+	pub async fn voted_by(&self, pool: &PgPool, account_id: impl Into<i32>) -> sqlx::Result<bool> {
 		sqlx::query_scalar!(
 			r#"SELECT EXISTS(
 				SELECT * FROM vote WHERE post_id = $1 AND voter_id = $2
@@ -35,14 +43,16 @@ impl Post {
 			i64::from(self.id),
 			account_id.into(),
 		)
-		.fetch_one(&*POOL)
+		.fetch_one(pool)
 		.await
 	}
 }
 
 impl Account {
+	#[instrument(level = "debug", skip_all)]
 	pub async fn get_voted_posts(
 		&self,
+		pool: &PgPool,
 		user_id: Option<impl Into<i32>>,
 	) -> sqlx::Result<Vec<Post>> {
 		sqlx::query_as!(
@@ -59,7 +69,7 @@ impl Account {
 			i32::from(self.id),
 			user_id.map(Into::into),
 		)
-		.fetch_all(&*POOL)
+		.fetch_all(pool)
 		.await
 	}
 }
@@ -0,0 +1,169 @@
+use std::{error::Error, fmt::Display};
+
+use rocket::{
+	http::Status,
+	request::Request,
+	response::{self, content::RawHtml, Responder},
+	serde::json::Json,
+	Catcher,
+};
+use rocket_dyn_templates::tera::{self, Context, Tera};
+use serde::Serialize;
+use tracing::error;
+
+use crate::SessionData;
+
+// routes the pages call from javascript, they get their errors as json
+const API_ROUTES: &[&str] = &["like_post", "get_events"];
+
+/// Everything a route can fail with, answered with an error page or with json on api routes
+#[derive(Debug)]
+pub enum AppError {
+	NotFound,
+	Unauthorized,
+	Forbidden,
+	/// Forbidden, with a message telling the user what the request was missing
+	Refused(&'static str),
+	/// The request can't be handled as it is, the message is shown to the user
+	Validation(&'static str),
+	Database(sqlx::Error),
+	/// Anything else that failed on our side, the message is only logged
+	Internal(String),
+}
+impl AppError {
+	pub fn status(&self) -> Status {
+		match self {
+			Self::NotFound => Status::NotFound,
+			Self::Unauthorized => Status::Unauthorized,
+			Self::Forbidden | Self::Refused(_) => Status::Forbidden,
+			Self::Validation(_) => Status::BadRequest,
+			Self::Database(_) | Self::Internal(_) => Status::InternalServerError,
+		}
+	}
+	fn message(&self) -> &'static str {
+		match self {
+			Self::Refused(message) | Self::Validation(message) => message,
+			_ => message(self.status()),
+		}
+	}
+}
+impl Display for AppError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Database(e) => write!(f, "{e}"),
+			Self::Internal(e) => write!(f, "{e}"),
+			_ => write!(f, "{}", self.message()),
+		}
+	}
+}
+impl From<sqlx::Error> for AppError {
+	fn from(value: sqlx::Error) -> Self {
+		Self::Database(value)
+	}
+}
+impl From<tera::Error> for AppError {
+	fn from(value: tera::Error) -> Self {
+		// the useful part of a render error is usually in its sources
+		let mut message = value.to_string();
+		let mut source = value.source();
+		while let Some(e) = source {
+			message += &format!("\n{e}");
+			source = e.source();
+		}
+		Self::Internal(message)
+	}
+}
+
+impl<'r> Responder<'r, 'static> for AppError {
+	fn respond_to(self, req: &'r Request<'_>) -> response::Result<'static> {
+		if let Self::Database(_) | Self::Internal(_) = self {
+			error!("{self}");
+		}
+		respond(req, self.status(), self.message())
+	}
+}
+
+fn message(status: Status) -> &'static str {
+	match status.code {
+		400 | 422 => "That request didn't look right",
+		401 => "You need to log in to see this",
+		403 => "You're not allowed to do that",
+		404 => "There's nothing here",
+		500.. => "Something went wrong on our side",
+		_ => status.reason().unwrap_or("Something went wrong"),
+	}
+}
+
+#[derive(Serialize)]
+struct ErrorBody {
+	error: u16,
+	message: &'static str,
+}
+
+fn respond(req: &Request<'_>, status: Status, message: &'static str) -> response::Result<'static> {
+	let api_route = req
+		.route()
+		.and_then(|r| r.name.as_deref())
+		.is_some_and(|name| API_ROUTES.contains(&name));
+	let wants_json = req
+		.accept()
+		.is_some_and(|accept| accept.preferred().media_type().is_json());
+	if api_route || wants_json {
+		let body = ErrorBody {
+			error: status.code,
+			message,
+		};
+		return (status, Json(body)).respond_to(req);
+	}
+
+	match render_page(req, status, message) {
+		Ok(html) => (status, RawHtml(html)).respond_to(req),
+		Err(e) => {
+			error!("Could not render the error page: {e}");
+			(status, status.to_string()).respond_to(req)
+		}
+	}
+}
+
+fn render_page(req: &Request<'_>, status: Status, message: &str) -> Result<String, String> {
+	let tera = req
+		.rocket()
+		.state::<Tera>()
+		.ok_or("the templates aren't loaded")?;
+
+	// creating template context
+	let mut context = Context::new();
+
+	// inserting user data
+	let SessionData { user } = req.cookies().into();
+	context.insert("user", &user);
+
+	context.insert("status", &status.code);
+	context.insert("reason", status.reason().unwrap_or("Error"));
+	context.insert("message", message);
+
+	// the common statuses have their own page, the rest share one
+	let page = format!("error/{}.html", status.code);
+	let page = match tera.get_template_names().any(|name| name == page) {
+		true => page,
+		false => "error/index.html".to_string(),
+	};
+	tera.render(&page, &context).map_err(|e| e.to_string())
+}
+
+/// Answers the errors rocket raises itself, like unknown routes and failed guards
+#[catch(default)]
+fn default_catcher(status: Status, _req: &Request<'_>) -> Caught {
+	Caught(status)
+}
+
+struct Caught(Status);
+impl<'r> Responder<'r, 'static> for Caught {
+	fn respond_to(self, req: &'r Request<'_>) -> response::Result<'static> {
+		respond(req, self.0, message(self.0))
+	}
+}
+
+pub fn catchers() -> Vec<Catcher> {
+	catchers![default_catcher]
+}
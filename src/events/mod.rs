@@ -0,0 +1,107 @@
+use std::{sync::Arc, sync::LazyLock, time::Duration};
+
+use rocket::{
+	fairing::AdHoc,
+	tokio::{self, sync::broadcast},
+};
+use serde::Deserialize;
+use sqlx::{postgres::PgListener, PgPool};
+use tracing::error;
+
+use crate::database::{
+	post::Post,
+	types::{AccountId, PgU64, PostId},
+};
+
+// postgres channels filled by the triggers from the notifications migration
+const NEW_POST_CHANNEL: &str = "new_post";
+const VOTE_CHANNEL: &str = "vote_change";
+const NOTIFICATION_CHANNEL: &str = "new_notification";
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Updates shared with every open event stream on this instance
+#[derive(Debug, Clone)]
+pub enum Update {
+	Post(Arc<Post>),
+	Votes { post_id: PostId, votes: PgU64 },
+	Notification { account_id: AccountId },
+}
+
+#[derive(Deserialize)]
+struct VotePayload {
+	post_id: i64,
+	votes: i64,
+}
+
+#[derive(Deserialize)]
+struct NotificationPayload {
+	account_id: i32,
+}
+
+static UPDATES: LazyLock<broadcast::Sender<Update>> = LazyLock::new(|| broadcast::channel(1024).0);
+
+pub fn subscribe() -> broadcast::Receiver<Update> {
+	UPDATES.subscribe()
+}
+
+/// Starts forwarding postgres notifications to the event streams once rocket has launched
+pub fn fairing() -> AdHoc {
+	AdHoc::on_liftoff("Event listener", |rocket| {
+		let pool = rocket
+			.state::<PgPool>()
+			.cloned()
+			.expect("the pool is managed before launch");
+		Box::pin(async move {
+			tokio::spawn(async move {
+				loop {
+					if let Err(e) = listen(&pool).await {
+						error!("Event listener failed: {e}");
+					}
+					tokio::time::sleep(RECONNECT_DELAY).await;
+				}
+			});
+		})
+	})
+}
+
+async fn listen(pool: &PgPool) -> sqlx::Result<()> {
+	let mut listener = PgListener::connect_with(pool).await?;
+	listener
+		.listen_all([NEW_POST_CHANNEL, VOTE_CHANNEL, NOTIFICATION_CHANNEL])
+		.await?;
+
+	loop {
+		let notification = listener.recv().await?;
+		let payload = notification.payload();
+
+		let update = match notification.channel() {
+			NEW_POST_CHANNEL => {
+				let Ok(post_id) = payload.parse::<i64>() else {
+					continue;
+				};
+				match Post::find_by_id(pool, post_id, None::<i32>).await? {
+					Some(post) => Update::Post(Arc::new(post)),
+					None => continue,
+				}
+			}
+			VOTE_CHANNEL => match serde_json::from_str::<VotePayload>(payload) {
+				Ok(p) => Update::Votes {
+					post_id: p.post_id.into(),
+					votes: p.votes.into(),
+				},
+				Err(_) => continue,
+			},
+			NOTIFICATION_CHANNEL => match serde_json::from_str::<NotificationPayload>(payload) {
+				Ok(p) => Update::Notification {
+					account_id: p.account_id.into(),
+				},
+				Err(_) => continue,
+			},
+			_ => continue,
+		};
+
+		// sending only fails when nobody is subscribed
+		let _ = UPDATES.send(update);
+	}
+}
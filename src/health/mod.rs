@@ -0,0 +1,94 @@
+use rocket::{http::Status, serde::json::Json, State};
+use rocket_dyn_templates::tera::Tera;
+use serde::Serialize;
+use sqlx::PgPool;
+use tracing::error;
+
+use crate::database::migrate;
+
+#[derive(Serialize)]
+pub struct Health {
+	status: &'static str,
+}
+
+/// Answers as long as the process can handle requests at all
+#[get("/healthz")]
+pub fn healthz() -> Json<Health> {
+	Json(Health { status: "ok" })
+}
+
+#[derive(Serialize)]
+pub struct Check {
+	ok: bool,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	error: Option<&'static str>,
+}
+impl Check {
+	fn passed() -> Self {
+		Self {
+			ok: true,
+			error: None,
+		}
+	}
+	// anyone can ask, so the details only go to the log
+	fn failed(error: &'static str) -> Self {
+		Self {
+			ok: false,
+			error: Some(error),
+		}
+	}
+}
+
+#[derive(Serialize)]
+pub struct Readiness {
+	status: &'static str,
+	database: Check,
+	templates: Check,
+	migrations: Check,
+}
+
+/// Whether requests can be sent here, 503 while the database or the templates aren't there or migrations are missing
+#[get("/readyz")]
+pub async fn readyz(pool: &State<PgPool>, tera: Option<&State<Tera>>) -> (Status, Json<Readiness>) {
+	let database = match sqlx::query("SELECT 1").execute(pool.inner()).await {
+		Ok(_) => Check::passed(),
+		Err(e) => {
+			error!("Readiness check could not reach the database: {e}");
+			Check::failed("unavailable")
+		}
+	};
+
+	let templates = match tera {
+		Some(tera) if tera.get_template_names().next().is_some() => Check::passed(),
+		Some(_) => Check::failed("no templates were loaded"),
+		None => Check::failed("the templates aren't loaded"),
+	};
+
+	// only worth asking once the database answers
+	let migrations = match database.ok {
+		true => match migrate::pending(pool).await {
+			Ok(pending) if pending.is_empty() => Check::passed(),
+			Ok(pending) => {
+				error!("Readiness check found pending migrations: {pending:?}");
+				Check::failed("pending migrations")
+			}
+			Err(e) => {
+				error!("Readiness check could not list the migrations: {e}");
+				Check::failed("unavailable")
+			}
+		},
+		false => Check::failed("the database isn't reachable"),
+	};
+
+	let ready = database.ok && templates.ok && migrations.ok;
+	let readiness = Readiness {
+		status: if ready { "ready" } else { "unavailable" },
+		database,
+		templates,
+		migrations,
+	};
+	match ready {
+		true => (Status::Ok, Json(readiness)),
+		false => (Status::ServiceUnavailable, Json(readiness)),
+	}
+}
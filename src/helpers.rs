@@ -1,21 +1,21 @@
 use std::fmt::Display;
 
+use chrono::Utc;
 use rocket::http::{Cookie, CookieJar, Status};
+use tracing::error;
 
-use crate::database::types::AccountId;
+use crate::{
+	crypto, csrf,
+	database::{account::Account, types::AccountId},
+	session,
+};
 
 pub trait ErrorHelper {
-	fn print_and_err<T>(self) -> Result<T, Status>
-	where
-		Self: Sized + Display,
-	{
-		Err(self.print_and_status())
-	}
 	fn print_and_status(self) -> Status
 	where
 		Self: Sized + Display,
 	{
-		eprintln!("{self}");
+		error!("{self}");
 		Status::InternalServerError
 	}
 }
@@ -23,16 +23,60 @@ impl ErrorHelper for sqlx::Error {}
 impl ErrorHelper for rocket_dyn_templates::tera::Error {}
 
 pub trait CookieJarHelper {
-	fn set_user(&self, id: AccountId, handle: &str);
+	fn set_user(&self, account: &Account);
 	fn remove_user(&self);
+	fn csrf_token(&self) -> String;
+	fn set_pending_login(&self, id: AccountId);
+	fn pending_login(&self) -> Option<AccountId>;
+	fn remove_pending_login(&self);
 }
+
+// how long the second login step can take after the password was accepted
+const PENDING_LOGIN_SECONDS: i64 = 5 * 60;
+
 impl<'a> CookieJarHelper for CookieJar<'a> {
-	fn set_user(&self, id: AccountId, handle: &str) {
-		self.add_private(Cookie::new("id", id.0.to_string()));
-		self.add_private(Cookie::new("handle", handle.to_owned()));
+	fn set_user(&self, account: &Account) {
+		self.add_private(Cookie::new("id", account.id.0.to_string()));
+		self.add_private(Cookie::new("role", account.role.as_str()));
+		self.add_private(Cookie::new(
+			session::EPOCH_COOKIE,
+			account.session_epoch.to_string(),
+		));
+		// a new session always gets a new token
+		self.add_private(Cookie::new(csrf::TOKEN_COOKIE, crypto::generate_token()));
 	}
 	fn remove_user(&self) {
 		self.remove_private(Cookie::from("id"));
+		// sessions from before handles could change still carry it
 		self.remove_private(Cookie::from("handle"));
+		self.remove_private(Cookie::from("role"));
+		self.remove_private(Cookie::from(session::EPOCH_COOKIE));
+		self.remove_private(Cookie::from(csrf::TOKEN_COOKIE));
+	}
+	fn set_pending_login(&self, id: AccountId) {
+		let value = format!("{}:{}", id.0, Utc::now().timestamp());
+		self.add_private(Cookie::new("pending_login", value));
+	}
+	fn pending_login(&self) -> Option<AccountId> {
+		let cookie = self.get_private("pending_login")?;
+		let (id, time) = cookie.value().split_once(':')?;
+		let time: i64 = time.parse().ok()?;
+		if Utc::now().timestamp() - time > PENDING_LOGIN_SECONDS {
+			return None;
+		}
+		Some(id.parse::<u32>().ok()?.into())
+	}
+	fn remove_pending_login(&self) {
+		self.remove_private(Cookie::from("pending_login"));
+	}
+	fn csrf_token(&self) -> String {
+		match self.get_private(csrf::TOKEN_COOKIE) {
+			Some(cookie) => cookie.value().to_owned(),
+			None => {
+				let token = crypto::generate_token();
+				self.add_private(Cookie::new(csrf::TOKEN_COOKIE, token.clone()));
+				token
+			}
+		}
 	}
 }
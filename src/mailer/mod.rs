@@ -0,0 +1,187 @@
+use std::{
+	fs::OpenOptions,
+	io::{self, BufRead, BufReader, Write},
+	net::TcpStream,
+	sync::LazyLock,
+};
+
+use chrono::Utc;
+use rocket::tokio::task;
+
+#[derive(Clone)]
+pub struct Mail {
+	pub to: String,
+	pub subject: String,
+	pub body: String,
+}
+
+pub trait Mailer: Send + Sync {
+	fn send(&self, mail: &Mail) -> io::Result<()>;
+}
+
+/// Appends every mail to a file, for development and tests
+pub struct FileMailer {
+	pub path: String,
+}
+impl Mailer for FileMailer {
+	fn send(&self, mail: &Mail) -> io::Result<()> {
+		let mut file = OpenOptions::new()
+			.create(true)
+			.append(true)
+			.open(&self.path)?;
+		writeln!(
+			file,
+			"Date: {}\nTo: {}\nSubject: {}\n\n{}\n",
+			Utc::now().to_rfc2822(),
+			mail.to,
+			mail.subject,
+			mail.body
+		)
+	}
+}
+
+/// Prints every mail to stdout
+pub struct LogMailer;
+impl Mailer for LogMailer {
+	fn send(&self, mail: &Mail) -> io::Result<()> {
+		println!("Mail to {}: {}\n{}", mail.to, mail.subject, mail.body);
+		Ok(())
+	}
+}
+
+/// Keeps every mail, so tests can follow the links in them
+#[cfg(test)]
+pub struct MemoryMailer;
+#[cfg(test)]
+static SENT: std::sync::Mutex<Vec<Mail>> = std::sync::Mutex::new(Vec::new());
+#[cfg(test)]
+impl Mailer for MemoryMailer {
+	fn send(&self, mail: &Mail) -> io::Result<()> {
+		SENT.lock()
+			.map_err(|_| io::Error::other("poisoned"))?
+			.push(mail.clone());
+		Ok(())
+	}
+}
+#[cfg(test)]
+pub fn sent_to(to: &str) -> Vec<Mail> {
+	SENT.lock()
+		.map(|sent| sent.iter().filter(|m| m.to == to).cloned().collect())
+		.unwrap_or_default()
+}
+
+/// Hands mails to an SMTP server without TLS or authentication,
+/// meant for a local relay or a catch-all test server
+pub struct SmtpMailer {
+	pub address: String,
+	pub from: String,
+}
+impl Mailer for SmtpMailer {
+	fn send(&self, mail: &Mail) -> io::Result<()> {
+		let stream = TcpStream::connect(&self.address)?;
+		let mut reader = BufReader::new(stream.try_clone()?);
+		let mut writer = stream;
+
+		expect_reply(&mut reader, 220)?;
+		command(&mut writer, &mut reader, "HELO localhost", 250)?;
+		command(
+			&mut writer,
+			&mut reader,
+			&format!("MAIL FROM:<{}>", self.from),
+			250,
+		)?;
+		command(
+			&mut writer,
+			&mut reader,
+			&format!("RCPT TO:<{}>", mail.to),
+			250,
+		)?;
+		command(&mut writer, &mut reader, "DATA", 354)?;
+
+		// lines starting with a dot have to be escaped by doubling it
+		let body: Vec<String> = mail
+			.body
+			.lines()
+			.map(|line| match line.starts_with('.') {
+				true => format!(".{line}"),
+				false => line.to_owned(),
+			})
+			.collect();
+		let data = format!(
+			"From: <{}>\r\nTo: <{}>\r\nSubject: {}\r\nDate: {}\r\n\r\n{}\r\n.",
+			self.from,
+			mail.to,
+			mail.subject,
+			Utc::now().to_rfc2822(),
+			body.join("\r\n")
+		);
+		command(&mut writer, &mut reader, &data, 250)?;
+		command(&mut writer, &mut reader, "QUIT", 221)
+	}
+}
+
+fn command(
+	writer: &mut TcpStream,
+	reader: &mut BufReader<TcpStream>,
+	line: &str,
+	code: u16,
+) -> io::Result<()> {
+	write!(writer, "{line}\r\n")?;
+	writer.flush()?;
+	expect_reply(reader, code)
+}
+
+fn expect_reply(reader: &mut BufReader<TcpStream>, code: u16) -> io::Result<()> {
+	// multiline replies have a dash after the code on every line but the last
+	loop {
+		let mut line = String::new();
+		if reader.read_line(&mut line)? == 0 {
+			return Err(io::Error::new(
+				io::ErrorKind::UnexpectedEof,
+				"SMTP server closed the connection",
+			));
+		}
+		if line.as_bytes().get(3) == Some(&b'-') {
+			continue;
+		}
+		return match line.get(..3).and_then(|c| c.parse::<u16>().ok()) {
+			Some(c) if c == code => Ok(()),
+			_ => Err(io::Error::other(format!(
+				"unexpected SMTP reply: {}",
+				line.trim_end()
+			))),
+		};
+	}
+}
+
+// picked with MAILER=file|smtp|log, defaults to writing to a file and to memory in tests
+static MAILER: LazyLock<Box<dyn Mailer>> = LazyLock::new(|| {
+	let file = || {
+		Box::new(FileMailer {
+			path: dotenvy::var("MAIL_FILE").unwrap_or_else(|_| "mail.log".into()),
+		})
+	};
+	match dotenvy::var("MAILER").as_deref() {
+		Ok("smtp") => Box::new(SmtpMailer {
+			address: dotenvy::var("SMTP_ADDRESS").unwrap_or_else(|_| "127.0.0.1:25".into()),
+			from: dotenvy::var("MAIL_FROM").unwrap_or_else(|_| "noreply@localhost".into()),
+		}),
+		Ok("log") => Box::new(LogMailer),
+		Ok("file") => file(),
+		#[cfg(test)]
+		_ => Box::new(MemoryMailer),
+		#[cfg(not(test))]
+		_ => file(),
+	}
+});
+
+/// The address links in mails point to
+pub fn base_url() -> String {
+	dotenvy::var("BASE_URL").unwrap_or_else(|_| "http://127.0.0.1:8080".into())
+}
+
+pub async fn send(mail: Mail) -> io::Result<()> {
+	task::spawn_blocking(move || MAILER.send(&mail))
+		.await
+		.map_err(io::Error::other)?
+}
@@ -1,54 +1,113 @@
 #[macro_use]
 extern crate rocket;
 
+mod admin;
+mod config;
 mod crypto;
+mod csrf;
 mod database;
+mod error;
+mod events;
+mod health;
 mod helpers;
+mod mailer;
+mod metrics;
+mod session;
+mod telemetry;
 mod timestamps;
+mod validation;
 
+#[cfg(test)]
+mod tests;
+
+use config::{DatabaseConfig, MetricsConfig, TemplateConfig, TracingConfig};
+use crypto::totp;
 use database::{
 	account::{Account, AccountError},
+	attempt,
+	handle::HANDLE_CHANGE_COOLDOWN_DAYS,
+	invite::{Invite, MAX_DAYS, MAX_USES, USER_MAX_ACTIVE_INVITES, USER_MAX_DAYS, USER_MAX_USES},
+	message::{Conversation, Message, MAX_CONVERSATION_MEMBERS},
+	migrate,
+	moderation::LogEntry,
+	mute::{MutedWord, MAX_PHRASE_LENGTH},
+	notification::Notification,
+	password::{MAX_EMAIL_LENGTH, VERIFICATION_LINK_HOURS},
 	post::Post,
-	types::{AccountId, PostId},
+	report::Report,
+	storage::{PgStorage, Store},
+	types::{
+		AccountId, AttemptKind, ConversationId, DmPermission, LogAction, MessageId, MutedWordId,
+		NotificationKind, PostId, ReportId, ReportReason, ReportStatus, Role,
+	},
 };
+use error::AppError;
+use events::Update;
 use futures::future;
 use helpers::{CookieJarHelper, ErrorHelper};
+use metrics::METRICS;
+use qrcode::{render::svg, QrCode};
 use rocket::{
+	fairing::AdHoc,
 	form::{Form, FromForm},
 	fs::FileServer,
-	http::{uri::Origin, Cookie, CookieJar, Status},
-	response::{content::RawHtml, Redirect},
+	http::{uri::Origin, Cookie, CookieJar, RawStr, Status},
+	request::{self, FlashMessage, FromRequest, Request},
+	response::{
+		content::RawHtml,
+		stream::{Event, EventStream},
+		Flash, Redirect,
+	},
+	tokio::{select, sync::broadcast::error::RecvError},
+	Build, Either, Rocket, Shutdown, State,
 };
 use rocket_dyn_templates::tera::{Context, ErrorKind, Tera};
 use serde::Serialize;
-use std::sync::LazyLock;
+use sqlx::PgPool;
+use std::{cmp::Reverse, net::IpAddr, path::Path, sync::LazyLock};
+use tracing::error;
+use validation::ValidationError;
 
 // global constants
-static TERA: LazyLock<Tera> = LazyLock::new(|| match Tera::new("./templates/**/*.html") {
-	Ok(t) => t,
-	Err(e) => {
-		panic!(
-			"Could not load templates from ./templates\n{}",
-			if let ErrorKind::Msg(m) = e.kind {
-				m.to_string()
-			} else {
-				e.to_string()
-			}
-		);
-	}
-});
+// set REQUIRE_VERIFIED_EMAIL=true to only let accounts with a verified email post
+static REQUIRE_VERIFIED_EMAIL: LazyLock<bool> =
+	LazyLock::new(|| dotenvy::var("REQUIRE_VERIFIED_EMAIL").is_ok_and(|v| v == "true" || v == "1"));
+
+/// Who can register, managed next to the pool so every rocket can have its own
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum RegistrationMode {
+	Open,
+	Invite,
+	Closed,
+	Approval,
+}
+// picked with REGISTRATION_MODE=open|invite|closed|approval, defaults to open
+static REGISTRATION_MODE: LazyLock<RegistrationMode> =
+	LazyLock::new(|| match dotenvy::var("REGISTRATION_MODE").as_deref() {
+		Ok("invite") => RegistrationMode::Invite,
+		Ok("closed") => RegistrationMode::Closed,
+		Ok("approval") => RegistrationMode::Approval,
+		_ => RegistrationMode::Open,
+	});
 
 // session structs
 #[derive(Serialize)]
 struct SessionUser {
 	id: AccountId,
-	handle: Box<str>,
+	// only used to decide what to show, privileged routes check the database through the role guards
+	role: Role,
+	csrf_token: Box<str>,
 }
 impl SessionUser {
 	fn try_from_jar(jar: &CookieJar<'_>) -> Option<Self> {
 		Some(Self {
 			id: jar.get_private("id")?.value().parse::<u32>().ok()?.into(),
-			handle: jar.get_private("handle")?.value().into(),
+			role: jar
+				.get_private("role")
+				.map(|c| c.value().into())
+				.unwrap_or_default(),
+			csrf_token: jar.get_private(csrf::TOKEN_COOKIE)?.value().into(),
 		})
 	}
 }
@@ -69,10 +128,96 @@ impl<'a> From<&CookieJar<'a>> for SessionData {
 	}
 }
 
+// role guards
+async fn account_with_role(req: &Request<'_>, role: Role) -> request::Outcome<Account, ()> {
+	use rocket::outcome::Outcome::*;
+
+	let user = match SessionUser::try_from_jar(req.cookies()) {
+		Some(user) => user,
+		None => return Error((Status::Unauthorized, ())),
+	};
+	let store = match req.rocket().state::<Store>() {
+		Some(store) => store,
+		None => return Error((Status::InternalServerError, ())),
+	};
+	match store.find_account_by_id(user.id, None).await {
+		Ok(Some(acc)) if acc.role >= role => Success(acc),
+		Ok(Some(_)) => Error((Status::Forbidden, ())),
+		Ok(None) => Error((Status::Unauthorized, ())),
+		Err(e) => Error((e.print_and_status(), ())),
+	}
+}
+
+/// An account that is at least a moderator
+struct Moderator(Account);
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Moderator {
+	type Error = ();
+	async fn from_request(req: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+		account_with_role(req, Role::Moderator).await.map(Self)
+	}
+}
+
+/// An account that is an admin
+struct Admin(Account);
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Admin {
+	type Error = ();
+	async fn from_request(req: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+		account_with_role(req, Role::Admin).await.map(Self)
+	}
+}
+
+// nobody restricts an account of their own role or above, themselves included
+fn outranks(moderator: &Account, account: &Account) -> bool {
+	moderator.role > account.role
+}
+
 #[derive(FromForm)]
 struct AuthInput<'a> {
 	username: Option<&'a str>,
 	password: Option<&'a str>,
+	invite: Option<&'a str>,
+}
+
+#[derive(FromForm)]
+struct InviteInput {
+	max_uses: i32,
+	days: Option<i64>,
+}
+
+#[derive(FromForm)]
+struct EmailInput<'a> {
+	email: &'a str,
+}
+
+#[derive(FromForm)]
+struct HandleInput<'a> {
+	handle: &'a str,
+}
+
+#[derive(FromForm)]
+struct PasswordChangeInput<'a> {
+	current: &'a str,
+	password: &'a str,
+	confirm: &'a str,
+}
+
+#[derive(FromForm)]
+struct PasswordResetInput<'a> {
+	password: &'a str,
+	confirm: &'a str,
+}
+
+#[derive(FromForm)]
+struct TwoFactorInput<'a> {
+	code: &'a str,
+}
+
+#[derive(FromForm)]
+struct DisableTwoFactorInput<'a> {
+	password: &'a str,
+	code: &'a str,
 }
 
 #[derive(FromForm)]
@@ -81,6 +226,54 @@ struct PostInput {
 	parent_id: Option<PostId>,
 }
 
+#[derive(FromForm)]
+struct NewConversationInput {
+	handles: Option<String>,
+}
+
+#[derive(FromForm)]
+struct MessageInput {
+	body: Option<String>,
+}
+
+#[derive(FromForm)]
+struct DmPermissionInput {
+	dm_permission: DmPermission,
+}
+
+#[derive(FromForm)]
+struct MutedWordInput {
+	phrase: Option<String>,
+	days: Option<u32>,
+}
+
+#[derive(FromForm)]
+struct ReportInput {
+	reason: ReportReason,
+	details: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, FromFormField)]
+enum ModerationAction {
+	Dismiss,
+	#[field(value = "remove_post")]
+	RemovePost,
+	Suspend,
+	Limit,
+}
+
+#[derive(FromForm)]
+struct ModerationInput {
+	action: ModerationAction,
+	days: Option<u32>,
+	note: Option<String>,
+}
+
+#[derive(FromForm)]
+struct RoleInput {
+	role: Role,
+}
+
 #[derive(Serialize)]
 pub struct BaseTemplatePost {
 	pub id: u64,
@@ -112,15 +305,41 @@ impl From<Post> for BaseTemplatePost {
 	}
 }
 
+impl From<&Post> for BaseTemplatePost {
+	fn from(value: &Post) -> Self {
+		Self {
+			id: value.id.0,
+			author_id: value.author_id.0,
+			author_name: value
+				.author_username
+				.as_deref()
+				.unwrap_or(&value.author_handle)
+				.into(),
+			author_handle: value.author_handle.clone(),
+			body: value.body.clone(),
+			create_time: timestamps::format_timestamp(value.create_time).into_boxed_str(),
+			likes: value.votes.0,
+			liked_by_user: value.voted_by_user,
+			parent_id: value.parent_id.0.map(Into::into),
+		}
+	}
+}
+
 #[derive(Serialize)]
 pub struct ReplyTemplatePost {
 	base: BaseTemplatePost,
 	reply: Option<BaseTemplatePost>,
 }
 impl ReplyTemplatePost {
-	async fn from_posts(posts: Vec<Post>, user: &Option<SessionUser>) -> sqlx::Result<Vec<Self>> {
+	async fn from_posts(
+		store: &Store,
+		posts: Vec<Post>,
+		user: &Option<SessionUser>,
+	) -> sqlx::Result<Vec<Self>> {
 		future::join_all(posts.into_iter().map(|post| async {
-			let mut replies = post.get_replies(1, user.as_ref()).await?;
+			let mut replies = store
+				.get_replies(&post, 1, user.as_ref().map(|u| u.id))
+				.await?;
 			let reply = replies.pop().map(Into::into);
 			Ok(Self {
 				base: post.into(),
@@ -134,14 +353,347 @@ impl ReplyTemplatePost {
 	}
 }
 
-#[launch]
-fn rocket() -> _ {
-	let _ = &*TERA;
+#[derive(Serialize)]
+pub struct InboxTemplateConversation {
+	pub id: u32,
+	pub members: Vec<String>,
+	pub last_message: Option<String>,
+	pub last_message_time: Option<Box<str>>,
+	pub unread: u64,
+}
+impl From<Conversation> for InboxTemplateConversation {
+	fn from(value: Conversation) -> Self {
+		Self {
+			id: value.id.0,
+			members: value.member_handles,
+			last_message: value.last_message,
+			last_message_time: value
+				.last_message_time
+				.map(|t| timestamps::format_timestamp(t).into_boxed_str()),
+			unread: value.unread.0,
+		}
+	}
+}
+
+#[derive(Serialize)]
+pub struct TemplateMessage {
+	pub id: u64,
+	pub author_id: u32,
+	pub author_handle: Box<str>,
+	pub body: Box<str>,
+	pub create_time: Box<str>,
+}
+impl From<Message> for TemplateMessage {
+	fn from(value: Message) -> Self {
+		Self {
+			id: value.id.0,
+			author_id: value.author_id.0,
+			author_handle: value.author_handle,
+			body: value.body,
+			create_time: timestamps::format_timestamp(value.create_time).into_boxed_str(),
+		}
+	}
+}
+
+#[derive(Serialize)]
+pub struct TemplateNotification {
+	pub actor_handle: Box<str>,
+	pub kind: NotificationKind,
+	pub post_id: Option<u64>,
+	pub create_time: Box<str>,
+	pub seen: bool,
+	pub report_status: Option<ReportStatus>,
+	pub report_action: Option<String>,
+}
+impl From<Notification> for TemplateNotification {
+	fn from(value: Notification) -> Self {
+		Self {
+			actor_handle: value.actor_handle,
+			kind: value.kind,
+			post_id: value.post_id.0.map(Into::into),
+			create_time: timestamps::format_timestamp(value.create_time).into_boxed_str(),
+			seen: value.seen,
+			report_status: value.report_status.map(Into::into),
+			report_action: value.report_action,
+		}
+	}
+}
+
+#[derive(Serialize)]
+pub struct TemplateReport {
+	pub id: u32,
+	pub reporter_handle: Box<str>,
+	pub account_handle: Box<str>,
+	pub post_id: Option<u64>,
+	pub post_body: Option<String>,
+	pub reason: ReportReason,
+	pub details: Option<String>,
+	pub status: ReportStatus,
+	pub action: Option<String>,
+	pub moderator_handle: Option<String>,
+	pub create_time: Box<str>,
+	pub resolve_time: Option<Box<str>>,
+}
+impl From<Report> for TemplateReport {
+	fn from(value: Report) -> Self {
+		Self {
+			id: value.id.0,
+			reporter_handle: value.reporter_handle,
+			account_handle: value.account_handle,
+			post_id: value.post_id.0.map(Into::into),
+			post_body: value.post_body,
+			reason: value.reason,
+			details: value.details,
+			status: value.status,
+			action: value.action,
+			moderator_handle: value.moderator_handle,
+			create_time: timestamps::format_timestamp(value.create_time).into_boxed_str(),
+			resolve_time: value
+				.resolve_time
+				.map(|t| timestamps::format_timestamp(t).into_boxed_str()),
+		}
+	}
+}
+
+#[derive(Serialize)]
+pub struct TemplateMutedWord {
+	pub id: u32,
+	pub phrase: String,
+	pub expire_time: Option<Box<str>>,
+}
+impl From<MutedWord> for TemplateMutedWord {
+	fn from(value: MutedWord) -> Self {
+		Self {
+			id: value.id.0,
+			phrase: value.phrase,
+			expire_time: value
+				.expire_time
+				.map(|t| timestamps::format_timestamp(t).into_boxed_str()),
+		}
+	}
+}
+
+#[derive(Serialize)]
+pub struct TemplateInvite {
+	pub code: String,
+	pub link: String,
+	pub uses: i32,
+	pub max_uses: i32,
+	pub expire_time: Option<Box<str>>,
+	pub active: bool,
+}
+impl From<Invite> for TemplateInvite {
+	fn from(value: Invite) -> Self {
+		let expired = value
+			.expire_time
+			.is_some_and(|t| t <= chrono::Utc::now().naive_utc());
+		Self {
+			link: format!("{}/register?invite={}", mailer::base_url(), value.code),
+			code: value.code,
+			uses: value.uses,
+			max_uses: value.max_uses,
+			expire_time: value
+				.expire_time
+				.map(|t| timestamps::format_timestamp(t).into_boxed_str()),
+			active: !expired && value.uses < value.max_uses,
+		}
+	}
+}
+
+#[derive(Serialize)]
+pub struct TemplateLogEntry {
+	pub moderator_handle: Option<String>,
+	pub action: LogAction,
+	pub target_handle: Option<String>,
+	pub target_post_id: Option<i64>,
+	pub details: String,
+	pub create_time: Box<str>,
+}
+impl From<LogEntry> for TemplateLogEntry {
+	fn from(value: LogEntry) -> Self {
+		Self {
+			moderator_handle: value.moderator_handle,
+			action: value.action,
+			target_handle: value.target_handle,
+			target_post_id: value.target_post_id,
+			details: value.details,
+			create_time: timestamps::format_timestamp(value.create_time).into_boxed_str(),
+		}
+	}
+}
+
+#[derive(Serialize)]
+struct LivePost {
+	id: u64,
+	parent_id: Option<u64>,
+	html: String,
+}
+
+#[derive(Serialize)]
+struct LiveVotes {
+	id: u64,
+	likes: u64,
+}
+
+#[rocket::main]
+async fn main() {
+	let args: Vec<String> = std::env::args().skip(1).collect();
+	let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+	let tracing = TracingConfig::from_figment(&rocket::Config::figment())
+		.and_then(|config| telemetry::init(&config));
+	if let Err(e) = tracing {
+		eprintln!("{e}");
+		std::process::exit(1);
+	}
+
+	let result = match args.as_slice() {
+		[] => rocket()
+			.launch()
+			.await
+			.map(|_| ())
+			.map_err(|e| e.to_string()),
+		args => admin::run(args).await,
+	};
+	if let Err(e) = result {
+		eprintln!("{e}");
+		std::process::exit(1);
+	}
+}
+
+// set MIGRATE_ON_STARTUP=false to only migrate through `my-twitter migrate`
+static MIGRATE_ON_STARTUP: LazyLock<bool> =
+	LazyLock::new(|| dotenvy::var("MIGRATE_ON_STARTUP").map_or(true, |v| v != "false" && v != "0"));
+
+/// Parses the templates in the configured directory, with the parse errors readable in the console
+fn load_templates(config: &TemplateConfig) -> Result<Tera, String> {
+	// tera is happy with a glob that matches nothing, which would only fail on the first render
+	if !Path::new(&config.dir).is_dir() {
+		return Err(format!("Template directory {} doesn't exist", config.dir));
+	}
+	Tera::new(&format!("{}/**/*.html", config.dir.trim_end_matches('/'))).map_err(|e| {
+		format!(
+			"Could not load templates from {}\n{}",
+			config.dir,
+			if let ErrorKind::Msg(m) = e.kind {
+				m.to_string()
+			} else {
+				e.to_string()
+			}
+		)
+	})
+}
+
+fn rocket() -> Rocket<Build> {
+	app(rocket::build())
+		.manage(*REGISTRATION_MODE)
+		.attach(AdHoc::try_on_ignite("Database pool", |rocket| async {
+			let config = match DatabaseConfig::from_figment(rocket.figment()) {
+				Ok(config) => config,
+				Err(e) => {
+					error!("{e}");
+					return Err(rocket);
+				}
+			};
+			match database::connect(&config).await {
+				Ok(pool) => {
+					let store: Store = Box::new(PgStorage::new(pool.clone()));
+					Ok(rocket.manage(pool).manage(store))
+				}
+				Err(e) => {
+					error!("Could not connect to the database: {e}");
+					Err(rocket)
+				}
+			}
+		}))
+		.attach(AdHoc::try_on_ignite(
+			"Database migrations",
+			|rocket| async {
+				if !*MIGRATE_ON_STARTUP {
+					return Ok(rocket);
+				}
+				let pool = match rocket.state::<PgPool>() {
+					Some(pool) => pool,
+					None => return Err(rocket),
+				};
+				match migrate::run(pool).await {
+					Ok(()) => Ok(rocket),
+					Err(e) => {
+						error!("Could not migrate the database: {e}");
+						Err(rocket)
+					}
+				}
+			},
+		))
+		.attach(events::fairing())
+}
+
+/// Every route, the pool, the storage and the registration mode are managed by whoever builds it
+fn app(rocket: Rocket<Build>) -> Rocket<Build> {
+	core(rocket).mount(
+		"/",
+		telemetry::traced(routes![
+			get_login,
+			get_register,
+			login,
+			get_login_two_factor,
+			login_two_factor,
+			get_reset,
+			request_reset,
+			get_reset_password,
+			reset_password,
+			register,
+			get_settings,
+			set_email,
+			resend_verification,
+			verify_email,
+			set_handle,
+			change_password,
+			get_two_factor,
+			enable_two_factor,
+			disable_two_factor,
+			set_dm_permission,
+			get_inbox,
+			start_conversation,
+			get_conversation,
+			send_message,
+			delete_message,
+			get_notifications,
+			get_events,
+			mute_user,
+			unmute_user,
+			mute_thread,
+			unmute_thread,
+			get_mutes,
+			add_muted_word,
+			delete_muted_word,
+			get_invites,
+			create_invite,
+			delete_invite,
+			get_report_post,
+			report_post,
+			get_report_user,
+			report_user,
+			get_moderation,
+			moderate_report,
+			lift_restrictions,
+			get_admin,
+			set_role,
+			get_pending,
+			approve_account,
+			reject_account,
+			health::readyz,
+		]),
+	)
+}
 
-	rocket::build()
+/// The routes that only go through the storage, with the templates and the fairings every request goes through.
+/// Whoever builds it manages the storage, without a pool rocket won't launch if one of these asks for it
+fn core(rocket: Rocket<Build>) -> Rocket<Build> {
+	rocket
 		.mount(
 			"/",
-			routes![
+			telemetry::traced(routes![
 				favicon,
 				get_feed,
 				get_post,
@@ -149,19 +701,51 @@ fn rocket() -> _ {
 				create_post,
 				delete_post,
 				like_post,
+				get_profile,
 				get_user,
 				get_user_likes,
 				follow_user,
 				unfollow_user,
-				get_login,
-				get_register,
-				login,
-				register,
 				signout,
 				delete_account,
-			],
+			]),
 		)
 		.mount("/static", FileServer::from("./static"))
+		.mount(
+			"/",
+			telemetry::traced(routes![
+				csrf::rejected,
+				csrf::rejected_too_late,
+				session::expired,
+				metrics::get_metrics,
+				health::healthz,
+			]),
+		)
+		.register("/", error::catchers())
+		.attach(telemetry::RequestTracing)
+		.attach(metrics::RequestMetrics)
+		.attach(csrf::CsrfFairing)
+		.attach(session::SessionFairing)
+		.attach(AdHoc::try_on_ignite("Metrics", |rocket| async {
+			match MetricsConfig::from_figment(rocket.figment()) {
+				Ok(config) => Ok(rocket.manage(config)),
+				Err(e) => {
+					error!("{e}");
+					Err(rocket)
+				}
+			}
+		}))
+		.attach(AdHoc::try_on_ignite("Templates", |rocket| async {
+			let tera =
+				TemplateConfig::from_figment(rocket.figment()).and_then(|c| load_templates(&c));
+			match tera {
+				Ok(tera) => Ok(rocket.manage(tera)),
+				Err(e) => {
+					error!("{e}");
+					Err(rocket)
+				}
+			}
+		}))
 }
 
 // favicon
@@ -173,7 +757,11 @@ fn favicon() -> Redirect {
 // posts
 
 #[get("/")]
-async fn get_feed(jar: &CookieJar<'_>) -> Result<RawHtml<String>, Status> {
+async fn get_feed(
+	jar: &CookieJar<'_>,
+	store: &State<Store>,
+	tera: &State<Tera>,
+) -> Result<RawHtml<String>, AppError> {
 	let SessionData { user } = jar.into();
 
 	// creating template context
@@ -183,27 +771,35 @@ async fn get_feed(jar: &CookieJar<'_>) -> Result<RawHtml<String>, Status> {
 	context.insert("user", &user);
 
 	// inserting posts
-	let posts = match Post::get_recent(100, user.as_ref()).await {
+	let posts = match store
+		.get_recent_posts(100, user.as_ref().map(|u| u.id))
+		.await
+	{
 		Ok(p) => p,
-		Err(e) => return e.print_and_err(),
+		Err(e) => return Err(e.into()),
 	};
 
-	let posts = match ReplyTemplatePost::from_posts(posts, &user).await {
+	let posts = match ReplyTemplatePost::from_posts(store, posts, &user).await {
 		Ok(p) => p,
-		Err(e) => return e.print_and_err(),
+		Err(e) => return Err(e.into()),
 	};
 
 	context.insert("posts", &posts);
 
 	// rendering the template
-	match TERA.render("post/feed.html", &context) {
+	match metrics::render(tera, "post/feed.html", &context) {
 		Ok(s) => Ok(RawHtml(s)),
-		Err(e) => e.print_and_err(),
+		Err(e) => Err(e.into()),
 	}
 }
 
 #[get("/post/<post_id>")]
-async fn get_post(jar: &CookieJar<'_>, post_id: PostId) -> Result<RawHtml<String>, Status> {
+async fn get_post(
+	jar: &CookieJar<'_>,
+	post_id: PostId,
+	tera: &State<Tera>,
+	store: &State<Store>,
+) -> Result<RawHtml<String>, AppError> {
 	let SessionData { user }: SessionData = jar.into();
 
 	// creating template context
@@ -213,51 +809,78 @@ async fn get_post(jar: &CookieJar<'_>, post_id: PostId) -> Result<RawHtml<String
 	context.insert("user", &user);
 
 	// inserting post
-	let post = match Post::find_by_id(post_id, user.as_ref()).await {
+	let post = match store
+		.find_post_by_id(post_id, user.as_ref().map(|u| u.id))
+		.await
+	{
 		Ok(Some(p)) => p,
-		Ok(None) => return Err(Status::NotFound),
-		Err(e) => return e.print_and_err(),
+		Ok(None) => return Err(AppError::NotFound),
+		Err(e) => return Err(e.into()),
 	};
 	// inserting replies
-	let replies = match post.get_replies(100, user.as_ref()).await {
+	let replies = match store
+		.get_replies(&post, 100, user.as_ref().map(|u| u.id))
+		.await
+	{
 		Ok(p) => p,
-		Err(e) => return e.print_and_err(),
+		Err(e) => return Err(e.into()),
 	};
-	let replies = match ReplyTemplatePost::from_posts(replies, &user).await {
+	let replies = match ReplyTemplatePost::from_posts(store, replies, &user).await {
 		Ok(r) => r,
-		Err(e) => return e.print_and_err(),
+		Err(e) => return Err(e.into()),
 	};
 	context.insert("replies", &replies);
 
+	// inserting thread mute state
+	if let Some(user) = &user {
+		let account = match store.find_account_by_id(user.id, None).await {
+			Ok(Some(acc)) => acc,
+			Ok(None) => return Err(AppError::Unauthorized),
+			Err(e) => return Err(e.into()),
+		};
+		match store.is_muting_thread(&account, &post).await {
+			Ok(muted) => context.insert("thread_muted", &muted),
+			Err(e) => return Err(e.into()),
+		}
+	}
+
 	let post: BaseTemplatePost = post.into();
 	context.insert("base_post", &post);
 
 	// rendering the template
 	match post.parent_id {
-		None => match TERA.render("post/index.html", &context) {
+		None => match metrics::render(tera, "post/index.html", &context) {
 			Ok(s) => Ok(RawHtml(s)),
-			Err(e) => e.print_and_err(),
+			Err(e) => Err(e.into()),
 		},
 		Some(parent_id) => {
 			// inserting parent
-			let parent_post = match Post::find_by_id(PostId(parent_id), user.as_ref()).await {
+			let parent_post = match store
+				.find_post_by_id(PostId(parent_id), user.as_ref().map(|u| u.id))
+				.await
+			{
 				Ok(Some(post)) => BaseTemplatePost::from(post),
-				Ok(None) => return Err(Status::NotFound),
-				Err(e) => return e.print_and_err(),
+				Ok(None) => return Err(AppError::NotFound),
+				Err(e) => return Err(e.into()),
 			};
 
 			context.insert("parent_post", &parent_post);
 
-			match TERA.render("post/reply.html", &context) {
+			match metrics::render(tera, "post/reply.html", &context) {
 				Ok(s) => Ok(RawHtml(s)),
-				Err(e) => e.print_and_err(),
+				Err(e) => Err(e.into()),
 			}
 		}
 	}
 }
 
 #[get("/post/<post_id>/likes")]
-async fn get_post_likes(jar: &CookieJar<'_>, post_id: PostId) -> Result<RawHtml<String>, Status> {
+async fn get_post_likes(
+	jar: &CookieJar<'_>,
+	post_id: PostId,
+	store: &State<Store>,
+	tera: &State<Tera>,
+) -> Result<RawHtml<String>, AppError> {
 	let SessionData { user } = jar.into();
 
 	// creating template context
@@ -267,15 +890,18 @@ async fn get_post_likes(jar: &CookieJar<'_>, post_id: PostId) -> Result<RawHtml<
 	context.insert("user", &user);
 
 	// inserting post
-	let post = match Post::find_by_id(post_id, user.as_ref()).await {
+	let post = match store
+		.find_post_by_id(post_id, user.as_ref().map(|u| u.id))
+		.await
+	{
 		Ok(Some(p)) => p,
-		Ok(None) => return Err(Status::NotFound),
-		Err(e) => return e.print_and_err(),
+		Ok(None) => return Err(AppError::NotFound),
+		Err(e) => return Err(e.into()),
 	};
 	// inserting voters
-	let likes = match post.get_voters(user.as_ref()).await {
+	let likes = match store.get_voters(&post, user.as_ref().map(|u| u.id)).await {
 		Ok(v) => v,
-		Err(e) => return e.print_and_err(),
+		Err(e) => return Err(e.into()),
 	};
 	context.insert("likes", &likes);
 
@@ -283,107 +909,192 @@ async fn get_post_likes(jar: &CookieJar<'_>, post_id: PostId) -> Result<RawHtml<
 	let base_post: BaseTemplatePost = post.into();
 	context.insert("base_post", &base_post);
 
-	match TERA.render("post/likes.html", &context) {
+	match metrics::render(tera, "post/likes.html", &context) {
 		Ok(s) => Ok(RawHtml(s)),
-		Err(e) => e.print_and_err(),
+		Err(e) => Err(e.into()),
 	}
 }
 
 #[post("/create_post", data = "<post_input>")]
-async fn create_post(jar: &CookieJar<'_>, post_input: Form<PostInput>) -> Result<Redirect, Status> {
+async fn create_post(
+	jar: &CookieJar<'_>,
+	post_input: Form<PostInput>,
+	store: &State<Store>,
+) -> Result<Redirect, AppError> {
 	let SessionData { user } = jar.into();
 
 	let body = match &post_input.body {
 		Some(b) => b.as_ref(),
-		None => return Err(Status::BadRequest),
+		None => return Err(AppError::Validation("A post needs a body")),
 	};
 
 	let user = match user {
 		Some(user) => user,
-		None => return Err(Status::Unauthorized),
+		None => return Err(AppError::Unauthorized),
 	};
-	let account = match Account::find_by_id(user.id, None::<i32>).await {
+	let account = match store.find_account_by_id(user.id, None).await {
 		Ok(Some(acc)) => acc,
-		Ok(None) => return Err(Status::Unauthorized),
-		Err(e) => return e.print_and_err(),
+		Ok(None) => return Err(AppError::Unauthorized),
+		Err(e) => return Err(e.into()),
 	};
+	if account.is_suspended() {
+		return Err(AppError::Forbidden);
+	}
+	if *REQUIRE_VERIFIED_EMAIL && !account.email_verified {
+		return Ok(Redirect::to("/settings?err=unverified"));
+	}
 
-	match account.create_post(body, post_input.parent_id).await {
-		Ok(_) => Ok(Redirect::to(match post_input.parent_id {
-			Some(id) => format!("/post/{}", id.0),
-			None => "/".to_string(),
-		})),
-		Err(e) => e.print_and_err(),
+	match store
+		.create_post(&account, body, post_input.parent_id)
+		.await
+	{
+		Ok(_) => {
+			METRICS.posts_created.inc();
+			Ok(Redirect::to(match post_input.parent_id {
+				Some(id) => format!("/post/{}", id.0),
+				None => "/".to_string(),
+			}))
+		}
+		Err(e) => Err(e.into()),
 	}
 }
 
-#[get("/delete_post/<post_id>")]
-async fn delete_post(jar: &CookieJar<'_>, post_id: PostId) -> Result<Redirect, Status> {
+#[delete("/post/<post_id>")]
+async fn delete_post(
+	jar: &CookieJar<'_>,
+	post_id: PostId,
+	store: &State<Store>,
+) -> Result<Redirect, AppError> {
 	let SessionData { user } = jar.into();
 
 	let user = match user {
 		Some(user) => user,
-		None => return Err(Status::Unauthorized),
+		None => return Err(AppError::Unauthorized),
 	};
-	let post = match Post::find_by_id(post_id, Some(&user)).await {
+	let post = match store.find_post_by_id(post_id, Some(user.id)).await {
 		Ok(Some(post)) => post,
-		Ok(None) => return Err(Status::NotFound),
-		Err(e) => return e.print_and_err(),
+		Ok(None) => return Err(AppError::NotFound),
+		Err(e) => return Err(e.into()),
 	};
 	if user.id != post.author_id {
-		return Err(Status::Unauthorized);
+		// moderators can delete anyone's post, but it gets logged
+		let moderator = match store.find_account_by_id(user.id, None).await {
+			Ok(Some(acc)) if acc.role >= Role::Moderator => acc,
+			Ok(Some(_)) => return Err(AppError::Forbidden),
+			Ok(None) => return Err(AppError::Unauthorized),
+			Err(e) => return Err(e.into()),
+		};
+		if let Err(e) = store
+			.log_action(
+				&moderator,
+				LogAction::DeletePost,
+				Some(post.author_id),
+				Some(post.id),
+				&post.body,
+			)
+			.await
+		{
+			return Err(e.into());
+		}
 	}
-	match post.delete().await {
+	match store.delete_post(post).await {
 		Ok(Some(parent_id)) => Ok(Redirect::to(format!("/post/{}", parent_id.0))),
 		Ok(None) => Ok(Redirect::to("/")),
-		Err(e) => e.print_and_err(),
+		Err(e) => Err(e.into()),
 	}
 }
 
-#[get("/like_post/<post_id>")]
-async fn like_post(jar: &CookieJar<'_>, post_id: PostId) -> Status {
+#[post("/like_post/<post_id>")]
+async fn like_post(
+	jar: &CookieJar<'_>,
+	post_id: PostId,
+	store: &State<Store>,
+) -> Result<Status, AppError> {
 	let SessionData { user } = jar.into();
 
 	let user = match user {
 		Some(user) => user,
-		None => return Status::Unauthorized,
+		None => return Err(AppError::Unauthorized),
 	};
-	let account = match Account::find_by_id(user.id, None::<i32>).await {
+	let account = match store.find_account_by_id(user.id, None).await {
 		Ok(Some(acc)) => acc,
-		Ok(None) => return Status::Unauthorized,
-		Err(e) => return e.print_and_status(),
+		Ok(None) => return Err(AppError::Unauthorized),
+		Err(e) => return Err(e.into()),
 	};
+	if account.is_suspended() {
+		return Err(AppError::Forbidden);
+	}
 
-	let post = match Post::find_by_id(post_id, Some(&user)).await {
+	let post = match store.find_post_by_id(post_id, Some(user.id)).await {
 		Ok(Some(post)) => post,
-		Ok(None) => return Status::NotFound,
-		Err(e) => return e.print_and_status(),
+		Ok(None) => return Err(AppError::NotFound),
+		Err(e) => return Err(e.into()),
 	};
 
 	if post.author_id == account.id {
-		return Status::Forbidden;
+		return Err(AppError::Forbidden);
 	}
 
-	let liked_by_user = match post.voted_by(account.id).await {
+	let liked_by_user = match store.voted_by(&post, account.id).await {
 		Ok(v) => v,
-		Err(e) => return e.print_and_status(),
+		Err(e) => return Err(e.into()),
 	};
 
 	let res = if liked_by_user {
-		account.remove_vote(post.id).await
+		store.remove_vote(&account, post.id).await
 	} else {
-		account.add_vote(post.id).await.map(|_| ())
+		store
+			.add_vote(&account, post.id)
+			.await
+			.map(|_| METRICS.likes.inc())
 	};
 	match res {
-		Ok(_) => Status::Ok,
-		Err(e) => e.print_and_status(),
+		Ok(_) => Ok(Status::Ok),
+		Err(e) => Err(e.into()),
 	}
 }
 
 // users
 
+// the header links here so the session doesn't have to know the current handle
+#[get("/profile")]
+async fn get_profile(jar: &CookieJar<'_>, store: &State<Store>) -> Result<Redirect, AppError> {
+	let SessionData { user } = jar.into();
+
+	let user = match user {
+		Some(user) => user,
+		None => return Err(AppError::Unauthorized),
+	};
+	match store.find_account_by_id(user.id, None).await {
+		Ok(Some(acc)) => Ok(Redirect::to(format!("/user/{}", acc.handle))),
+		Ok(None) => Err(AppError::Unauthorized),
+		Err(e) => Err(e.into()),
+	}
+}
+
+// pages of an old handle point to the account's current one while it's reserved
+async fn old_handle_redirect<T>(
+	store: &Store,
+	handle: &str,
+	page: &str,
+) -> Result<Either<T, Redirect>, AppError> {
+	match store.find_handle_by_old_handle(handle).await {
+		// not permanent, the old handle can be taken by someone else once it's released
+		Ok(Some(current)) => Ok(Either::Right(Redirect::to(format!(
+			"/user/{current}{page}"
+		)))),
+		Ok(None) => Err(AppError::NotFound),
+		Err(e) => Err(e.into()),
+	}
+}
+
 #[get("/user/<handle>")]
-async fn get_user(jar: &CookieJar<'_>, handle: &str) -> Result<RawHtml<String>, Status> {
+async fn get_user(
+	jar: &CookieJar<'_>,
+	handle: &str,
+	tera: &State<Tera>,
+	store: &State<Store>,
+) -> Result<Either<RawHtml<String>, Redirect>, AppError> {
 	let SessionData { user } = jar.into();
 
 	// creating template context
@@ -392,36 +1103,70 @@ async fn get_user(jar: &CookieJar<'_>, handle: &str) -> Result<RawHtml<String>,
 	// inserting user data
 	context.insert("user", &user);
 
-	let account = match Account::find_by_handle(handle, user.as_ref()).await {
+	let account = match store
+		.find_account_by_handle(handle, user.as_ref().map(|u| u.id))
+		.await
+	{
 		Ok(Some(acc)) => acc,
-		Ok(None) => return Err(Status::NotFound),
-		Err(e) => return e.print_and_err(),
+		Ok(None) => return old_handle_redirect(store, handle, "").await,
+		Err(e) => return Err(e.into()),
 	};
 
 	// inserting handle
 	context.insert("account", &account);
 
+	// inserting suspension notice
+	if account.is_suspended() {
+		let until = account
+			.suspension_end
+			.map(|end| timestamps::format_timestamp(end).into_boxed_str());
+		context.insert("suspended_until", &until);
+		context.insert("suspended", &true);
+	}
+
+	// inserting mute state
+	if let Some(user) = &user {
+		let viewer = match store.find_account_by_id(user.id, None).await {
+			Ok(Some(acc)) => acc,
+			Ok(None) => return Err(AppError::Unauthorized),
+			Err(e) => return Err(e.into()),
+		};
+		match store.is_muting(&viewer, account.id).await {
+			Ok(muted) => context.insert("muted", &muted),
+			Err(e) => return Err(e.into()),
+		}
+		context.insert("can_moderate", &(viewer.role >= Role::Moderator));
+	}
+
 	// inserting posts
-	let posts = match account.get_posts(100, false, user.as_ref()).await {
+	let posts = match store
+		.get_posts(&account, 100, false, user.as_ref().map(|u| u.id))
+		.await
+	{
 		Ok(p) => p,
-		Err(e) => return e.print_and_err(),
+		Err(e) => return Err(e.into()),
 	};
-	let posts = match ReplyTemplatePost::from_posts(posts, &user).await {
+	let posts = match ReplyTemplatePost::from_posts(store, posts, &user).await {
 		Ok(p) => p,
-		Err(e) => return e.print_and_err(),
+		Err(e) => return Err(e.into()),
 	};
 
 	context.insert("posts", &posts);
 
 	// rendering the template
-	match TERA.render("account/index.html", &context) {
-		Ok(s) => Ok(RawHtml(s)),
-		Err(e) => e.print_and_err(),
+	match metrics::render(tera, "account/index.html", &context) {
+		Ok(s) => Ok(Either::Left(RawHtml(s))),
+		Err(e) => Err(e.into()),
 	}
 }
 
 #[get("/user/<handle>/likes")]
-async fn get_user_likes(jar: &CookieJar<'_>, handle: &str) -> Result<RawHtml<String>, Status> {
+async fn get_user_likes(
+	jar: &CookieJar<'_>,
+	handle: &str,
+	tera: &State<Tera>,
+	store: &State<Store>,
+) -> Result<Either<RawHtml<String>, Redirect>, AppError> {
 	let SessionData { user } = jar.into();
 
 	// creating template context
@@ -430,156 +1175,285 @@ async fn get_user_likes(jar: &CookieJar<'_>, handle: &str) -> Result<RawHtml<Str
 	// inserting user data
 	context.insert("user", &user);
 
-	let account = match Account::find_by_handle(handle, user.as_ref()).await {
+	let account = match store
+		.find_account_by_handle(handle, user.as_ref().map(|u| u.id))
+		.await
+	{
 		Ok(Some(acc)) => acc,
-		Ok(None) => return Err(Status::NotFound),
-		Err(e) => return e.print_and_err(),
+		Ok(None) => return old_handle_redirect(store, handle, "/likes").await,
+		Err(e) => return Err(e.into()),
 	};
 
 	// inserting handle
 	context.insert("account", &account);
 
 	// inserting posts
-	let posts = match account.get_voted_posts(user.as_ref()).await {
+	let posts = match store
+		.get_voted_posts(&account, user.as_ref().map(|u| u.id))
+		.await
+	{
 		Ok(p) => p,
-		Err(e) => return e.print_and_err(),
+		Err(e) => return Err(e.into()),
 	};
 	let posts: Vec<BaseTemplatePost> = posts.into_iter().map(Into::into).collect();
 
 	context.insert("posts", &posts);
 
 	// rendering the template
-	match TERA.render("account/likes.html", &context) {
-		Ok(s) => Ok(RawHtml(s)),
-		Err(e) => e.print_and_err(),
+	match metrics::render(tera, "account/likes.html", &context) {
+		Ok(s) => Ok(Either::Left(RawHtml(s))),
+		Err(e) => Err(e.into()),
 	}
 }
 
 async fn follow_or_unfollow(
+	store: &Store,
 	jar: &CookieJar<'_>,
 	handle: &str,
 	follow: bool,
-) -> Result<Redirect, Status> {
+) -> Result<Redirect, AppError> {
 	let SessionData { user } = jar.into();
 
 	let user = match user {
 		Some(user) => user,
-		None => return Err(Status::Unauthorized),
+		None => return Err(AppError::Unauthorized),
 	};
 
-	let account = match Account::find_by_id(user.id, Some(&user)).await {
+	let account = match store.find_account_by_id(user.id, Some(user.id)).await {
 		Ok(Some(acc)) => acc,
-		Ok(None) => return Err(Status::NotFound),
-		Err(e) => return e.print_and_err(),
+		Ok(None) => return Err(AppError::NotFound),
+		Err(e) => return Err(e.into()),
 	};
+	if account.is_suspended() {
+		return Err(AppError::Forbidden);
+	}
 
-	let to_follow = match Account::find_by_handle(handle, Some(&user)).await {
+	let to_follow = match store.find_account_by_handle(handle, Some(user.id)).await {
 		Ok(Some(acc)) => acc,
-		Ok(None) => return Err(Status::NotFound),
-		Err(e) => return e.print_and_err(),
+		Ok(None) => return Err(AppError::NotFound),
+		Err(e) => return Err(e.into()),
 	};
 
 	if account.id == to_follow.id {
-		return Err(Status::Forbidden);
+		return Err(AppError::Forbidden);
 	}
 
 	let result = if follow {
-		account.follow(to_follow.id).await.map(|_| ())
+		store
+			.follow(&account, to_follow.id)
+			.await
+			.map(|_| METRICS.follows.inc())
 	} else {
-		account.unfollow(to_follow.id).await
+		store.unfollow(&account, to_follow.id).await
 	};
 
 	match result {
 		Ok(()) => Ok(Redirect::to(format!("/user/{handle}"))),
-		Err(e) => e.print_and_err(),
+		Err(e) => Err(e.into()),
 	}
 }
 
-#[get("/user/<handle>/follow")]
-async fn follow_user(jar: &CookieJar<'_>, handle: &str) -> Result<Redirect, Status> {
-	follow_or_unfollow(jar, handle, true).await
+#[post("/user/<handle>/follow")]
+async fn follow_user(
+	jar: &CookieJar<'_>,
+	handle: &str,
+	store: &State<Store>,
+) -> Result<Redirect, AppError> {
+	follow_or_unfollow(store, jar, handle, true).await
 }
 
-#[get("/user/<handle>/unfollow")]
-async fn unfollow_user(jar: &CookieJar<'_>, handle: &str) -> Result<Redirect, Status> {
-	follow_or_unfollow(jar, handle, false).await
+#[post("/user/<handle>/unfollow")]
+async fn unfollow_user(
+	jar: &CookieJar<'_>,
+	handle: &str,
+	store: &State<Store>,
+) -> Result<Redirect, AppError> {
+	follow_or_unfollow(store, jar, handle, false).await
 }
 
 // accounts
 
 #[get("/login")]
-fn get_login(jar: &CookieJar<'_>, origin: &Origin) -> Result<RawHtml<String>, Status> {
+fn get_login(
+	jar: &CookieJar<'_>,
+	origin: &Origin,
+	flash: Option<FlashMessage<'_>>,
+	tera: &State<Tera>,
+) -> Result<RawHtml<String>, AppError> {
 	let SessionData { user } = jar.into();
 
 	// creating template context
 	let mut context = Context::new();
 
+	// suspension details don't fit in the query, so they come as a flash message
+	if let Some(flash) = &flash {
+		context.insert("error", flash.message());
+	}
+
 	if let Some(q) = origin.query() {
 		for pair in q.segments() {
 			match pair {
-				("err", "password") => {
-					context.insert("error", "Incorrect password!");
+				// not telling which one was wrong, so handles can't be probed
+				("err", "credentials") => {
+					context.insert("error", "Invalid credentials");
 					break;
 				}
-				("err", "handle") => {
-					context.insert("error", "A user with this handle doesn't exist");
+				("err", "throttled") => {
+					context.insert("error", "Too many attempts, please try again later");
 					break;
 				}
-				(_, _) => (),
-			};
-		}
-	}
-
-	if user.is_some() {
-		return Err(Status::BadRequest);
-	}
-
-	// inserting user data
+				("err", "expired") => {
+					context.insert("error", "Your login expired, please try again");
+					break;
+				}
+				("err", "session") => {
+					context.insert("error", "You were signed out, please log in again");
+					break;
+				}
+				("msg", "reset") => {
+					context.insert("message", "Your password was changed, you can log in now");
+					break;
+				}
+				("err", "pending") => {
+					context.insert("error", "Your account is still waiting for approval");
+					break;
+				}
+				("msg", "pending") => {
+					context.insert(
+						"message",
+						"Your account was created, you can log in once an admin approves it",
+					);
+					break;
+				}
+				("err", "verification") => {
+					context.insert("error", "This verification link is invalid or has expired");
+					break;
+				}
+				("msg", "verified") => {
+					context.insert("message", "Your email was verified");
+					break;
+				}
+				(_, _) => (),
+			};
+		}
+	}
+
+	if user.is_some() {
+		return Err(AppError::Validation("You're already logged in"));
+	}
+
+	// inserting user data
 	context.insert("user", &user);
+	context.insert("csrf_token", &jar.csrf_token());
 
 	// render the template
-	match TERA.render("user/login.html", &context) {
+	match metrics::render(tera, "user/login.html", &context) {
 		Ok(s) => Ok(RawHtml(s)),
-		Err(e) => e.print_and_err(),
+		Err(e) => Err(e.into()),
 	}
 }
 
 #[post("/login", data = "<login_input>")]
-async fn login(jar: &CookieJar<'_>, login_input: Form<AuthInput<'_>>) -> Result<Redirect, Status> {
+async fn login(
+	jar: &CookieJar<'_>,
+	ip: Option<IpAddr>,
+	login_input: Form<AuthInput<'_>>,
+	pool: &State<PgPool>,
+	store: &State<Store>,
+) -> Result<Either<Redirect, Flash<Redirect>>, AppError> {
 	let (handle, password) = match (login_input.username, login_input.password) {
 		(Some(u), Some(p)) => (u, p),
-		_ => return Err(Status::BadRequest),
+		_ => {
+			return Err(AppError::Validation(
+				"Both the handle and the password are needed",
+			))
+		}
 	};
 
+	// throttling both the client and the account, so neither spreading attempts
+	// over many handles nor over many addresses gets around it
+	let ip = ip.map_or_else(|| "unknown".to_string(), |ip| ip.to_string());
+	let handle_key = handle.to_lowercase();
+	let keys = [
+		(AttemptKind::Ip, ip.as_str()),
+		(AttemptKind::Handle, handle_key.as_str()),
+	];
+	match is_throttled(pool, &keys).await {
+		Ok(false) => (),
+		Ok(true) => return Ok(Either::Left(Redirect::to("/login?err=throttled"))),
+		Err(e) => return Err(e.into()),
+	}
+
 	use AccountError::*;
-	match Account::login(handle, password).await {
+	match store.login(handle, password).await {
 		Ok(acc) => {
-			jar.add_private(Cookie::new("id", acc.id.0.to_string()));
-			jar.add_private(Cookie::new("handle", acc.handle));
-			Ok(Redirect::to("/"))
+			// the session is only set once the second factor was checked too
+			if acc.has_two_factor() {
+				jar.set_pending_login(acc.id);
+				return Ok(Either::Left(Redirect::to("/login/2fa")));
+			}
+			if let Err(e) = attempt::clear(pool, AttemptKind::Handle, &handle_key).await {
+				return Err(e.into());
+			}
+			jar.set_user(&acc);
+			Ok(Either::Left(Redirect::to("/")))
+		}
+		Err(Handle(_) | Password(_)) => {
+			METRICS.failed_logins.inc();
+			for (kind, key) in keys {
+				if let Err(e) = attempt::record_failure(pool, kind, key).await {
+					return Err(e.into());
+				}
+			}
+			Ok(Either::Left(Redirect::to("/login?err=credentials")))
+		}
+		Err(Suspended { reason, end }) => {
+			let until = match end {
+				Some(end) => format!("until {}", timestamps::format_timestamp(end)),
+				None => "indefinitely".to_string(),
+			};
+			Ok(Either::Right(Flash::error(
+				Redirect::to("/login"),
+				format!("This account is suspended {until}: {reason}"),
+			)))
 		}
-		Err(Handle(_)) => Ok(Redirect::to("/login?err=handle")),
-		Err(Password(_)) => Ok(Redirect::to("/login?err=password")),
-		Err(Sqlx(e)) => e.print_and_err(),
+		Err(Pending) => Ok(Either::Left(Redirect::to("/login?err=pending"))),
+		// only returned when registering
+		Err(Invalid(_)) => Err(AppError::Validation(
+			"Both the handle and the password are needed",
+		)),
+		Err(Sqlx(e)) => Err(e.into()),
 	}
 }
 
 #[get("/register")]
-fn get_register(jar: &CookieJar<'_>, origin: &Origin) -> Result<RawHtml<String>, Status> {
+fn get_register(
+	jar: &CookieJar<'_>,
+	origin: &Origin,
+	mode: &State<RegistrationMode>,
+	tera: &State<Tera>,
+) -> Result<RawHtml<String>, AppError> {
 	// creating template context
 	let mut context = Context::new();
 
 	if let Some(q) = origin.query() {
 		for pair in q.segments() {
 			match pair {
-				("err", "password") => {
-					context.insert("error", "Please enter a valid password");
+				("invite", code) => context.insert("invite", code),
+				("err", "throttled") => {
+					context.insert("error", "Too many attempts, please try again later");
 					break;
 				}
-				("err", "handle") => {
-					context.insert("error", "A with this handle already exists");
+				("err", "invite_required") => {
+					context.insert("error", "You need an invite code to register");
 					break;
 				}
+				("err", code) => {
+					if let Some(e) = ValidationError::from_code(code) {
+						context.insert("error", &e.message());
+						break;
+					}
+				}
 				(_, _) => (),
 			};
 		}
@@ -588,49 +1462,392 @@ fn get_register(jar: &CookieJar<'_>, origin: &Origin) -> Result<RawHtml<String>,
 	let SessionData { user } = jar.into();
 
 	if user.is_some() {
-		return Err(Status::BadRequest);
+		return Err(AppError::Validation("You're already logged in"));
 	}
 
 	// inserting user data
 	context.insert("user", &user);
+	context.insert("csrf_token", &jar.csrf_token());
+	context.insert("mode", mode.inner());
 
 	// render the template
-	match TERA.render("user/register.html", &context) {
+	match metrics::render(tera, "user/register.html", &context) {
 		Ok(s) => Ok(RawHtml(s)),
-		Err(e) => e.print_and_err(),
+		Err(e) => Err(e.into()),
 	}
 }
 
 #[post("/register", data = "<register_input>")]
 async fn register(
 	jar: &CookieJar<'_>,
+	ip: Option<IpAddr>,
 	register_input: Form<AuthInput<'_>>,
-) -> Result<Redirect, Status> {
+	mode: &State<RegistrationMode>,
+	pool: &State<PgPool>,
+	store: &State<Store>,
+) -> Result<Redirect, AppError> {
 	let (handle, password) = match (register_input.username, register_input.password) {
 		(Some(u), Some(p)) => (u, p),
-		_ => return Err(Status::BadRequest),
+		_ => {
+			return Err(AppError::Validation(
+				"Both the handle and the password are needed",
+			))
+		}
 	};
+	let invite = register_input
+		.invite
+		.map(str::trim)
+		.filter(|i| !i.is_empty());
+
+	let mode = *mode.inner();
+	match (mode, invite) {
+		(RegistrationMode::Closed, _) => return Err(AppError::Forbidden),
+		(RegistrationMode::Invite, None) => {
+			return Ok(Redirect::to("/register?err=invite_required"))
+		}
+		_ => (),
+	}
+	// an invite is enough to skip the approval queue
+	let pending = mode == RegistrationMode::Approval && invite.is_none();
+
+	// every registration counts, successful or not, so one client can't create accounts in bulk
+	let ip = ip.map_or_else(|| "unknown".to_string(), |ip| ip.to_string());
+	let keys = [(AttemptKind::Register, ip.as_str())];
+	match is_throttled(pool, &keys).await {
+		Ok(false) => (),
+		Ok(true) => return Ok(Redirect::to("/register?err=throttled")),
+		Err(e) => return Err(e.into()),
+	}
+	if let Err(e) = attempt::record_failure(pool, AttemptKind::Register, &ip).await {
+		return Err(e.into());
+	}
 
 	use AccountError::*;
-	match Account::register(handle, password).await {
+	let registered = store.register(handle, password, invite, pending).await;
+	match registered.inspect(|_| METRICS.registrations.inc()) {
+		Ok(acc) if acc.pending => Ok(Redirect::to("/login?msg=pending")),
 		Ok(acc) => {
-			jar.set_user(acc.id, &acc.handle);
+			jar.set_user(&acc);
 			Ok(Redirect::to("/"))
 		}
-		Err(Handle(_)) => Ok(Redirect::to("/register?err=handle")),
-		Err(Password(_)) => Ok(Redirect::to("/register?err=password")),
-		Err(Sqlx(e)) => e.print_and_err(),
+		Err(Invalid(e)) => {
+			// keeping the invite in the form so it doesn't have to be entered again
+			let invite = invite
+				.map(|i| format!("invite={}&", RawStr::new(i).percent_encode()))
+				.unwrap_or_default();
+			Ok(Redirect::to(format!("/register?{invite}err={}", e.code())))
+		}
+		// these only come from logging in
+		Err(Handle(_) | Password(_) | Suspended { .. } | Pending) => Err(AppError::Forbidden),
+		Err(Sqlx(e)) => Err(e.into()),
+	}
+}
+
+#[get("/login/2fa")]
+fn get_login_two_factor(
+	jar: &CookieJar<'_>,
+	origin: &Origin,
+	tera: &State<Tera>,
+) -> Result<Either<RawHtml<String>, Redirect>, AppError> {
+	if jar.pending_login().is_none() {
+		return Ok(Either::Right(Redirect::to("/login?err=expired")));
+	}
+
+	// creating template context
+	let mut context = Context::new();
+
+	if let Some(q) = origin.query() {
+		for pair in q.segments() {
+			match pair {
+				("err", "code") => {
+					context.insert("error", "Invalid code");
+					break;
+				}
+				("err", "throttled") => {
+					context.insert("error", "Too many attempts, please try again later");
+					break;
+				}
+				(_, _) => (),
+			};
+		}
+	}
+
+	// inserting user data
+	context.insert("user", &None::<SessionUser>);
+	context.insert("csrf_token", &jar.csrf_token());
+
+	// render the template
+	match metrics::render(tera, "user/two_factor.html", &context) {
+		Ok(s) => Ok(Either::Left(RawHtml(s))),
+		Err(e) => Err(e.into()),
+	}
+}
+
+#[post("/login/2fa", data = "<two_factor_input>")]
+async fn login_two_factor(
+	jar: &CookieJar<'_>,
+	ip: Option<IpAddr>,
+	two_factor_input: Form<TwoFactorInput<'_>>,
+	pool: &State<PgPool>,
+	store: &State<Store>,
+) -> Result<Redirect, AppError> {
+	let id = match jar.pending_login() {
+		Some(id) => id,
+		None => return Ok(Redirect::to("/login?err=expired")),
+	};
+	let account = match store.find_account_by_id(id, None).await {
+		Ok(Some(acc)) => acc,
+		Ok(None) => return Ok(Redirect::to("/login?err=expired")),
+		Err(e) => return Err(e.into()),
+	};
+
+	// codes are short, so they're throttled the same way passwords are
+	let ip = ip.map_or_else(|| "unknown".to_string(), |ip| ip.to_string());
+	let handle_key = account.handle.to_lowercase();
+	let keys = [
+		(AttemptKind::Ip, ip.as_str()),
+		(AttemptKind::Handle, handle_key.as_str()),
+	];
+	match is_throttled(pool, &keys).await {
+		Ok(false) => (),
+		Ok(true) => return Ok(Redirect::to("/login/2fa?err=throttled")),
+		Err(e) => return Err(e.into()),
+	}
+
+	match check_second_factor(pool, &account, two_factor_input.code).await {
+		Ok(true) => (),
+		Ok(false) => {
+			METRICS.failed_logins.inc();
+			for (kind, key) in keys {
+				if let Err(e) = attempt::record_failure(pool, kind, key).await {
+					return Err(e.into());
+				}
+			}
+			return Ok(Redirect::to("/login/2fa?err=code"));
+		}
+		Err(e) => return Err(e.into()),
+	}
+
+	if let Err(e) = attempt::clear(pool, AttemptKind::Handle, &handle_key).await {
+		return Err(e.into());
+	}
+	jar.remove_pending_login();
+	jar.set_user(&account);
+	Ok(Redirect::to("/"))
+}
+
+/// Accepts either a current TOTP code or one of the account's unused recovery codes
+async fn check_second_factor(pool: &PgPool, account: &Account, code: &str) -> sqlx::Result<bool> {
+	let secret = match &account.totp_secret {
+		Some(secret) => secret,
+		None => return Ok(false),
+	};
+	match totp::verify(secret, code, totp::current_step()) {
+		Some(step) => account.use_totp_step(pool, step).await,
+		None => {
+			account
+				.use_recovery_code(pool, &totp::hash_recovery_code(code))
+				.await
+		}
+	}
+}
+
+#[get("/reset")]
+fn get_reset(
+	jar: &CookieJar<'_>,
+	origin: &Origin<'_>,
+	tera: &State<Tera>,
+) -> Result<RawHtml<String>, AppError> {
+	let SessionData { user } = jar.into();
+
+	if user.is_some() {
+		return Err(AppError::Validation("You're already logged in"));
+	}
+
+	// creating template context
+	let mut context = Context::new();
+
+	if let Some(q) = origin.query() {
+		for pair in q.segments() {
+			match pair {
+				// the same message whether the account exists or not
+				("msg", "sent") => {
+					context.insert(
+						"message",
+						"If an account uses this email, a reset link is on its way",
+					);
+					break;
+				}
+				("err", "throttled") => {
+					context.insert("error", "Too many attempts, please try again later");
+					break;
+				}
+				(_, _) => (),
+			};
+		}
+	}
+
+	// inserting user data
+	context.insert("user", &user);
+	context.insert("csrf_token", &jar.csrf_token());
+
+	// render the template
+	match metrics::render(tera, "user/reset.html", &context) {
+		Ok(s) => Ok(RawHtml(s)),
+		Err(e) => Err(e.into()),
+	}
+}
+
+#[post("/reset", data = "<email_input>")]
+async fn request_reset(
+	ip: Option<IpAddr>,
+	email_input: Form<EmailInput<'_>>,
+	pool: &State<PgPool>,
+) -> Result<Redirect, AppError> {
+	// every request counts, so the form can't be used to flood someone's inbox
+	let ip = ip.map_or_else(|| "unknown".to_string(), |ip| ip.to_string());
+	match is_throttled(pool, &[(AttemptKind::Reset, &ip)]).await {
+		Ok(false) => (),
+		Ok(true) => return Ok(Redirect::to("/reset?err=throttled")),
+		Err(e) => return Err(e.into()),
+	}
+	if let Err(e) = attempt::record_failure(pool, AttemptKind::Reset, &ip).await {
+		return Err(e.into());
+	}
+
+	let account = match Account::find_by_verified_email(pool, email_input.email.trim()).await {
+		Ok(Some(acc)) => acc,
+		Ok(None) => return Ok(Redirect::to("/reset?msg=sent")),
+		Err(e) => return Err(e.into()),
+	};
+	let email = match &account.email {
+		Some(email) => email.clone(),
+		None => return Ok(Redirect::to("/reset?msg=sent")),
+	};
+
+	let token = match account.create_password_reset(pool).await {
+		Ok(t) => t,
+		Err(e) => return Err(e.into()),
+	};
+	let mail = mailer::Mail {
+		to: email,
+		subject: "Reset your password".to_string(),
+		body: format!(
+			"Someone asked to reset the password of @{}.\n\
+			Open this link within an hour to choose a new one:\n\n\
+			{}/reset/{token}\n\n\
+			If it wasn't you, you can ignore this mail.",
+			account.handle,
+			mailer::base_url(),
+		),
+	};
+	if let Err(e) = mailer::send(mail).await {
+		return Err(AppError::Internal(format!("Could not send mail: {e}")));
+	}
+
+	Ok(Redirect::to("/reset?msg=sent"))
+}
+
+#[get("/reset/<token>")]
+async fn get_reset_password(
+	jar: &CookieJar<'_>,
+	origin: &Origin<'_>,
+	token: &str,
+	pool: &State<PgPool>,
+	tera: &State<Tera>,
+) -> Result<RawHtml<String>, AppError> {
+	match Account::password_reset_handle(pool, token).await {
+		Ok(Some(_)) => (),
+		Ok(None) => return Err(AppError::NotFound),
+		Err(e) => return Err(e.into()),
+	}
+
+	// creating template context
+	let mut context = Context::new();
+
+	if let Some(q) = origin.query() {
+		for pair in q.segments() {
+			match pair {
+				("err", "mismatch") => {
+					context.insert("error", "The passwords don't match");
+					break;
+				}
+				("err", code) => {
+					if let Some(e) = ValidationError::from_code(code) {
+						context.insert("error", &e.message());
+						break;
+					}
+				}
+				(_, _) => (),
+			};
+		}
+	}
+
+	// inserting user data
+	context.insert("user", &None::<SessionUser>);
+	context.insert("csrf_token", &jar.csrf_token());
+	context.insert("token", token);
+
+	// render the template
+	match metrics::render(tera, "user/reset_password.html", &context) {
+		Ok(s) => Ok(RawHtml(s)),
+		Err(e) => Err(e.into()),
+	}
+}
+
+#[post("/reset/<token>", data = "<reset_input>")]
+async fn reset_password(
+	token: &str,
+	reset_input: Form<PasswordResetInput<'_>>,
+	pool: &State<PgPool>,
+) -> Result<Redirect, AppError> {
+	// checking the input before the token is used up
+	let handle = match Account::password_reset_handle(pool, token).await {
+		Ok(Some(handle)) => handle,
+		Ok(None) => return Err(AppError::NotFound),
+		Err(e) => return Err(e.into()),
+	};
+	if let Err(e) = validation::validate_password(reset_input.password, &handle) {
+		return Ok(Redirect::to(format!("/reset/{token}?err={}", e.code())));
+	}
+	if reset_input.password != reset_input.confirm {
+		return Ok(Redirect::to(format!("/reset/{token}?err=mismatch")));
+	}
+
+	let account = match Account::consume_password_reset(pool, token).await {
+		Ok(Some(acc)) => acc,
+		Ok(None) => return Err(AppError::NotFound),
+		Err(e) => return Err(e.into()),
+	};
+	if let Err(e) = account.set_password(pool, reset_input.password).await {
+		return Err(e.into());
+	}
+	// whoever was locked out by failed attempts can log in with the new password right away
+	let handle_key = account.handle.to_lowercase();
+	if let Err(e) = attempt::clear(pool, AttemptKind::Handle, &handle_key).await {
+		return Err(e.into());
+	}
+
+	Ok(Redirect::to("/login?msg=reset"))
+}
+
+async fn is_throttled(pool: &PgPool, keys: &[(AttemptKind, &str)]) -> sqlx::Result<bool> {
+	for &(kind, key) in keys {
+		if attempt::blocked_until(pool, kind, key).await?.is_some() {
+			return Ok(true);
+		}
 	}
+	Ok(false)
 }
 
-#[get("/signout")]
+#[post("/signout")]
 fn signout(jar: &CookieJar<'_>) -> Redirect {
 	jar.remove_user();
 	Redirect::to("/")
 }
 
 #[post("/delete_account")]
-async fn delete_account(jar: &CookieJar<'_>) -> Result<Redirect, Status> {
+async fn delete_account(jar: &CookieJar<'_>, store: &State<Store>) -> Result<Redirect, AppError> {
 	let SessionData { user } = jar.into();
 
 	let user = match user {
@@ -638,13 +1855,1945 @@ async fn delete_account(jar: &CookieJar<'_>) -> Result<Redirect, Status> {
 		None => return Ok(Redirect::to("/")),
 	};
 
-	let account = match Account::find_by_id(user.id, None::<i32>).await {
+	let account = match store.find_account_by_id(user.id, None).await {
 		Ok(Some(acc)) => acc,
-		Ok(None) => return Err(Status::Unauthorized),
-		Err(e) => return e.print_and_err(),
+		Ok(None) => return Err(AppError::Unauthorized),
+		Err(e) => return Err(e.into()),
 	};
-	match account.delete().await {
+	match store.delete_account(account).await {
 		Ok(_) => Ok(signout(jar)),
-		Err(e) => e.print_and_err(),
+		Err(e) => Err(e.into()),
+	}
+}
+
+#[get("/settings")]
+async fn get_settings(
+	jar: &CookieJar<'_>,
+	origin: &Origin<'_>,
+	store: &State<Store>,
+	tera: &State<Tera>,
+) -> Result<RawHtml<String>, AppError> {
+	let SessionData { user } = jar.into();
+
+	let user = match user {
+		Some(user) => user,
+		None => return Err(AppError::Unauthorized),
+	};
+	let account = match store.find_account_by_id(user.id, None).await {
+		Ok(Some(acc)) => acc,
+		Ok(None) => return Err(AppError::Unauthorized),
+		Err(e) => return Err(e.into()),
+	};
+
+	// creating template context
+	let mut context = Context::new();
+
+	if let Some(q) = origin.query() {
+		for pair in q.segments() {
+			match pair {
+				("err", "email") => {
+					context.insert("error", "Please enter a valid email address");
+					break;
+				}
+				("err", "email_taken") => {
+					context.insert("error", "This email is already used by another account");
+					break;
+				}
+				("err", "unverified") => {
+					context.insert("error", "Please verify your email address before posting");
+					break;
+				}
+				("err", "verification") => {
+					context.insert("error", "This verification link is invalid or has expired");
+					break;
+				}
+				("err", "throttled") => {
+					context.insert("error", "Too many attempts, please try again later");
+					break;
+				}
+				("err", "current") => {
+					context.insert("error", "Your current password is incorrect");
+					break;
+				}
+				("err", "mismatch") => {
+					context.insert("error", "The passwords don't match");
+					break;
+				}
+				("err", "handle_cooldown") => {
+					context.insert(
+						"error",
+						&format!(
+							"You can only change your handle once every {HANDLE_CHANGE_COOLDOWN_DAYS} days"
+						),
+					);
+					break;
+				}
+				("msg", "email") => {
+					context.insert("message", "Your email was saved");
+					break;
+				}
+				("msg", "verification_sent") => {
+					context.insert(
+						"message",
+						"Your email was saved, open the link we sent to verify it",
+					);
+					break;
+				}
+				("msg", "verified") => {
+					context.insert("message", "Your email was verified");
+					break;
+				}
+				("msg", "handle") => {
+					context.insert("message", "Your handle was changed");
+					break;
+				}
+				("msg", "password") => {
+					context.insert(
+						"message",
+						"Your password was changed and your other sessions were signed out",
+					);
+					break;
+				}
+				("err", code) => {
+					if let Some(e) = ValidationError::from_code(code) {
+						context.insert("error", &e.message());
+						break;
+					}
+				}
+				(_, _) => (),
+			};
+		}
+	}
+
+	// inserting user data
+	context.insert("user", &user);
+	context.insert("account", &account);
+	context.insert("two_factor", &account.has_two_factor());
+	context.insert(
+		"next_handle_change",
+		&account
+			.next_handle_change()
+			.map(timestamps::format_timestamp),
+	);
+
+	// rendering the template
+	match metrics::render(tera, "account/settings.html", &context) {
+		Ok(s) => Ok(RawHtml(s)),
+		Err(e) => Err(e.into()),
+	}
+}
+
+#[get("/settings/2fa")]
+async fn get_two_factor(
+	jar: &CookieJar<'_>,
+	origin: &Origin<'_>,
+	pool: &State<PgPool>,
+	tera: &State<Tera>,
+	store: &State<Store>,
+) -> Result<RawHtml<String>, AppError> {
+	let SessionData { user } = jar.into();
+
+	let user = match user {
+		Some(user) => user,
+		None => return Err(AppError::Unauthorized),
+	};
+	let account = match store.find_account_by_id(user.id, None).await {
+		Ok(Some(acc)) => acc,
+		Ok(None) => return Err(AppError::Unauthorized),
+		Err(e) => return Err(e.into()),
+	};
+
+	// creating template context
+	let mut context = Context::new();
+
+	if let Some(q) = origin.query() {
+		for pair in q.segments() {
+			match pair {
+				("err", "code") => {
+					context.insert("error", "Invalid code");
+					break;
+				}
+				("err", "reauth") => {
+					context.insert("error", "Invalid password or code");
+					break;
+				}
+				(_, _) => (),
+			};
+		}
+	}
+
+	// inserting user data
+	context.insert("user", &user);
+
+	if account.has_two_factor() {
+		match account.count_recovery_codes(pool).await {
+			Ok(count) => context.insert("recovery_codes_left", &count),
+			Err(e) => return Err(e.into()),
+		}
+		context.insert("enabled", &true);
+	} else {
+		// keeping the secret in a cookie until it has been confirmed with a code
+		let secret = match jar
+			.get_private("pending_totp")
+			.and_then(|c| totp::decode_secret(c.value()))
+		{
+			Some(secret) => secret,
+			None => {
+				let secret = totp::generate_secret();
+				jar.add_private(Cookie::new("pending_totp", totp::encode_secret(&secret)));
+				secret
+			}
+		};
+		let uri = totp::provisioning_uri(&secret, &account.handle);
+		let qr_code = match QrCode::new(uri.as_bytes()) {
+			Ok(code) => code
+				.render::<svg::Color>()
+				.min_dimensions(200, 200)
+				.quiet_zone(true)
+				.build(),
+			Err(e) => return Err(AppError::Internal(e.to_string())),
+		};
+		context.insert("qr_code", &qr_code);
+		context.insert("secret", &totp::encode_secret(&secret));
+		context.insert("enabled", &false);
+	}
+
+	// rendering the template
+	match metrics::render(tera, "account/two_factor.html", &context) {
+		Ok(s) => Ok(RawHtml(s)),
+		Err(e) => Err(e.into()),
+	}
+}
+
+#[post("/settings/2fa/enable", data = "<two_factor_input>")]
+async fn enable_two_factor(
+	jar: &CookieJar<'_>,
+	two_factor_input: Form<TwoFactorInput<'_>>,
+	pool: &State<PgPool>,
+	tera: &State<Tera>,
+	store: &State<Store>,
+) -> Result<Either<RawHtml<String>, Redirect>, AppError> {
+	let SessionData { user } = jar.into();
+
+	let user = match user {
+		Some(user) => user,
+		None => return Err(AppError::Unauthorized),
+	};
+	let account = match store.find_account_by_id(user.id, None).await {
+		Ok(Some(acc)) => acc,
+		Ok(None) => return Err(AppError::Unauthorized),
+		Err(e) => return Err(e.into()),
+	};
+	if account.has_two_factor() {
+		return Err(AppError::Validation(
+			"Two-factor authentication is already enabled",
+		));
+	}
+
+	let secret = match jar
+		.get_private("pending_totp")
+		.and_then(|c| totp::decode_secret(c.value()))
+	{
+		Some(secret) => secret,
+		None => return Ok(Either::Right(Redirect::to("/settings/2fa"))),
+	};
+	let step = match totp::verify(&secret, two_factor_input.code, totp::current_step()) {
+		Some(step) => step,
+		None => return Ok(Either::Right(Redirect::to("/settings/2fa?err=code"))),
+	};
+
+	let recovery_codes = totp::generate_recovery_codes();
+	let hashes: Vec<[u8; 64]> = recovery_codes
+		.iter()
+		.map(|c| totp::hash_recovery_code(c))
+		.collect();
+	if let Err(e) = account.enable_two_factor(pool, &secret, &hashes).await {
+		return Err(e.into());
+	}
+	// the code used for confirming can't be used to log in again
+	if let Err(e) = account.use_totp_step(pool, step).await {
+		return Err(e.into());
+	}
+	jar.remove_private(Cookie::from("pending_totp"));
+
+	// creating template context
+	let mut context = Context::new();
+
+	// inserting user data
+	context.insert("user", &user);
+
+	// the recovery codes are only ever shown here
+	context.insert("recovery_codes", &recovery_codes);
+
+	// rendering the template
+	match metrics::render(tera, "account/recovery_codes.html", &context) {
+		Ok(s) => Ok(Either::Left(RawHtml(s))),
+		Err(e) => Err(e.into()),
+	}
+}
+
+#[post("/settings/2fa/disable", data = "<disable_input>")]
+async fn disable_two_factor(
+	jar: &CookieJar<'_>,
+	disable_input: Form<DisableTwoFactorInput<'_>>,
+	pool: &State<PgPool>,
+	store: &State<Store>,
+) -> Result<Redirect, AppError> {
+	let SessionData { user } = jar.into();
+
+	let user = match user {
+		Some(user) => user,
+		None => return Err(AppError::Unauthorized),
+	};
+	let account = match store.find_account_by_id(user.id, None).await {
+		Ok(Some(acc)) => acc,
+		Ok(None) => return Err(AppError::Unauthorized),
+		Err(e) => return Err(e.into()),
+	};
+	if !account.has_two_factor() {
+		return Err(AppError::Validation(
+			"Two-factor authentication isn't enabled",
+		));
+	}
+
+	// re-authenticating, so a hijacked session alone can't turn it off
+	let handle_key = account.handle.to_lowercase();
+	match is_throttled(pool, &[(AttemptKind::Handle, &handle_key)]).await {
+		Ok(false) => (),
+		Ok(true) => return Ok(Redirect::to("/settings/2fa?err=reauth")),
+		Err(e) => return Err(e.into()),
+	}
+	let valid = account.check_password(disable_input.password)
+		&& match check_second_factor(pool, &account, disable_input.code).await {
+			Ok(valid) => valid,
+			Err(e) => return Err(e.into()),
+		};
+	if !valid {
+		if let Err(e) = attempt::record_failure(pool, AttemptKind::Handle, &handle_key).await {
+			return Err(e.into());
+		}
+		return Ok(Redirect::to("/settings/2fa?err=reauth"));
+	}
+
+	match account.disable_two_factor(pool).await {
+		Ok(()) => Ok(Redirect::to("/settings/2fa")),
+		Err(e) => Err(e.into()),
+	}
+}
+
+#[post("/settings/handle", data = "<handle_input>")]
+async fn set_handle(
+	jar: &CookieJar<'_>,
+	handle_input: Form<HandleInput<'_>>,
+	pool: &State<PgPool>,
+	store: &State<Store>,
+) -> Result<Redirect, AppError> {
+	let SessionData { user } = jar.into();
+
+	let user = match user {
+		Some(user) => user,
+		None => return Err(AppError::Unauthorized),
+	};
+	let account = match store.find_account_by_id(user.id, None).await {
+		Ok(Some(acc)) => acc,
+		Ok(None) => return Err(AppError::Unauthorized),
+		Err(e) => return Err(e.into()),
+	};
+
+	let handle = handle_input.handle.trim();
+	if handle == account.handle {
+		return Ok(Redirect::to("/settings"));
+	}
+	if account.next_handle_change().is_some() {
+		return Ok(Redirect::to("/settings?err=handle_cooldown"));
+	}
+
+	use AccountError::*;
+	match account.change_handle(pool, handle).await {
+		Ok(()) => Ok(Redirect::to("/settings?msg=handle")),
+		Err(Invalid(e)) => Ok(Redirect::to(format!("/settings?err={}", e.code()))),
+		Err(Handle(_) | Password(_) | Suspended { .. } | Pending) => Err(AppError::Forbidden),
+		Err(Sqlx(e)) => Err(e.into()),
+	}
+}
+
+#[post("/settings/email", data = "<email_input>")]
+async fn set_email(
+	jar: &CookieJar<'_>,
+	email_input: Form<EmailInput<'_>>,
+	pool: &State<PgPool>,
+	store: &State<Store>,
+) -> Result<Redirect, AppError> {
+	let SessionData { user } = jar.into();
+
+	let user = match user {
+		Some(user) => user,
+		None => return Err(AppError::Unauthorized),
+	};
+	let account = match store.find_account_by_id(user.id, None).await {
+		Ok(Some(acc)) => acc,
+		Ok(None) => return Err(AppError::Unauthorized),
+		Err(e) => return Err(e.into()),
+	};
+
+	// an empty address removes it
+	let email = email_input.email.trim();
+	let email = match email.is_empty() {
+		true => None,
+		false => Some(email),
+	};
+	if email.is_some_and(|e| e.len() > MAX_EMAIL_LENGTH || !e.contains('@')) {
+		return Ok(Redirect::to("/settings?err=email"));
+	}
+
+	match account.set_email(pool, email).await {
+		Ok(true) => (),
+		Ok(false) => return Ok(Redirect::to("/settings?err=email_taken")),
+		Err(e) => return Err(e.into()),
+	}
+
+	// a new address gets a verification link right away
+	let account = match store.find_account_by_id(user.id, None).await {
+		Ok(Some(acc)) => acc,
+		Ok(None) => return Err(AppError::Unauthorized),
+		Err(e) => return Err(e.into()),
+	};
+	if account.email.is_none() || account.email_verified {
+		return Ok(Redirect::to("/settings?msg=email"));
+	}
+	send_verification_mail(pool, &account).await
+}
+
+// every mail counts, so the button can't be used to flood an inbox
+async fn send_verification_mail(pool: &PgPool, account: &Account) -> Result<Redirect, AppError> {
+	let key = account.id.0.to_string();
+	match is_throttled(pool, &[(AttemptKind::Verify, &key)]).await {
+		Ok(false) => (),
+		Ok(true) => return Ok(Redirect::to("/settings?err=throttled")),
+		Err(e) => return Err(e.into()),
+	}
+	if let Err(e) = attempt::record_failure(pool, AttemptKind::Verify, &key).await {
+		return Err(e.into());
+	}
+
+	let (email, token) = match (&account.email, account.email_verification_token()) {
+		(Some(email), Some(token)) => (email.clone(), token),
+		_ => return Err(AppError::Validation("There's no email address to verify")),
+	};
+	let mail = mailer::Mail {
+		to: email,
+		subject: "Verify your email address".to_string(),
+		body: format!(
+			"This address was added to @{}.\n\
+			Open this link within {VERIFICATION_LINK_HOURS} hours to verify it:\n\n\
+			{}/verify_email/{token}\n\n\
+			If it wasn't you, you can ignore this mail.",
+			account.handle,
+			mailer::base_url(),
+		),
+	};
+	if let Err(e) = mailer::send(mail).await {
+		return Err(AppError::Internal(format!("Could not send mail: {e}")));
+	}
+
+	Ok(Redirect::to("/settings?msg=verification_sent"))
+}
+
+#[post("/settings/email/verify")]
+async fn resend_verification(
+	jar: &CookieJar<'_>,
+	pool: &State<PgPool>,
+	store: &State<Store>,
+) -> Result<Redirect, AppError> {
+	let SessionData { user } = jar.into();
+
+	let user = match user {
+		Some(user) => user,
+		None => return Err(AppError::Unauthorized),
+	};
+	let account = match store.find_account_by_id(user.id, None).await {
+		Ok(Some(acc)) => acc,
+		Ok(None) => return Err(AppError::Unauthorized),
+		Err(e) => return Err(e.into()),
+	};
+	if account.email_verified {
+		return Ok(Redirect::to("/settings?msg=verified"));
+	}
+
+	send_verification_mail(pool, &account).await
+}
+
+// opened from the mail, possibly in a browser that isn't signed in
+#[get("/verify_email/<token>")]
+async fn verify_email(
+	jar: &CookieJar<'_>,
+	token: &str,
+	pool: &State<PgPool>,
+) -> Result<Redirect, AppError> {
+	let SessionData { user } = jar.into();
+
+	let page = match user {
+		Some(_) => "/settings",
+		None => "/login",
+	};
+	match Account::verify_email(pool, token).await {
+		Ok(true) => Ok(Redirect::to(format!("{page}?msg=verified"))),
+		Ok(false) => Ok(Redirect::to(format!("{page}?err=verification"))),
+		Err(e) => Err(e.into()),
+	}
+}
+
+#[post("/settings/password", data = "<password_input>")]
+async fn change_password(
+	jar: &CookieJar<'_>,
+	password_input: Form<PasswordChangeInput<'_>>,
+	pool: &State<PgPool>,
+	store: &State<Store>,
+) -> Result<Redirect, AppError> {
+	let SessionData { user } = jar.into();
+
+	let user = match user {
+		Some(user) => user,
+		None => return Err(AppError::Unauthorized),
+	};
+	let mut account = match store.find_account_by_id(user.id, None).await {
+		Ok(Some(acc)) => acc,
+		Ok(None) => return Err(AppError::Unauthorized),
+		Err(e) => return Err(e.into()),
+	};
+
+	// guessing the current password here is throttled like logging in
+	let handle_key = account.handle.to_lowercase();
+	match is_throttled(pool, &[(AttemptKind::Handle, &handle_key)]).await {
+		Ok(false) => (),
+		Ok(true) => return Ok(Redirect::to("/settings?err=current")),
+		Err(e) => return Err(e.into()),
+	}
+	if !account.check_password(password_input.current) {
+		if let Err(e) = attempt::record_failure(pool, AttemptKind::Handle, &handle_key).await {
+			return Err(e.into());
+		}
+		return Ok(Redirect::to("/settings?err=current"));
+	}
+	if let Err(e) = validation::validate_password(password_input.password, &account.handle) {
+		return Ok(Redirect::to(format!("/settings?err={}", e.code())));
+	}
+	if password_input.password != password_input.confirm {
+		return Ok(Redirect::to("/settings?err=mismatch"));
+	}
+
+	// every other session is signed out by the new epoch, this one gets it straight away
+	match account.set_password(pool, password_input.password).await {
+		Ok(epoch) => account.session_epoch = epoch,
+		Err(e) => return Err(e.into()),
+	}
+	jar.set_user(&account);
+
+	Ok(Redirect::to("/settings?msg=password"))
+}
+
+#[post("/settings/dm_permission", data = "<permission_input>")]
+async fn set_dm_permission(
+	jar: &CookieJar<'_>,
+	permission_input: Form<DmPermissionInput>,
+	store: &State<Store>,
+) -> Result<Redirect, AppError> {
+	let SessionData { user } = jar.into();
+
+	let user = match user {
+		Some(user) => user,
+		None => return Err(AppError::Unauthorized),
+	};
+	let account = match store.find_account_by_id(user.id, None).await {
+		Ok(Some(acc)) => acc,
+		Ok(None) => return Err(AppError::Unauthorized),
+		Err(e) => return Err(e.into()),
+	};
+
+	match store
+		.set_dm_permission(&account, permission_input.dm_permission)
+		.await
+	{
+		Ok(()) => Ok(Redirect::to("/settings")),
+		Err(e) => Err(e.into()),
+	}
+}
+
+// messages
+
+#[get("/messages")]
+async fn get_inbox(
+	jar: &CookieJar<'_>,
+	origin: &Origin<'_>,
+	pool: &State<PgPool>,
+	tera: &State<Tera>,
+	store: &State<Store>,
+) -> Result<RawHtml<String>, AppError> {
+	let SessionData { user } = jar.into();
+
+	let user = match user {
+		Some(user) => user,
+		None => return Err(AppError::Unauthorized),
+	};
+	let account = match store.find_account_by_id(user.id, None).await {
+		Ok(Some(acc)) => acc,
+		Ok(None) => return Err(AppError::Unauthorized),
+		Err(e) => return Err(e.into()),
+	};
+
+	// creating template context
+	let mut context = Context::new();
+
+	if let Some(q) = origin.query() {
+		for pair in q.segments() {
+			match pair {
+				("err", "handle") => {
+					context.insert("error", "One of these users doesn't exist");
+					break;
+				}
+				("err", "permission") => {
+					context.insert("error", "You can't message one of these users");
+					break;
+				}
+				("err", "size") => {
+					context.insert(
+						"error",
+						&format!(
+							"A conversation can have at most {} members",
+							MAX_CONVERSATION_MEMBERS
+						),
+					);
+					break;
+				}
+				(_, _) => (),
+			};
+		}
+	}
+
+	// inserting user data
+	context.insert("user", &user);
+
+	// inserting conversations
+	let conversations = match account.get_conversations(pool).await {
+		Ok(c) => c,
+		Err(e) => return Err(e.into()),
+	};
+	let conversations: Vec<InboxTemplateConversation> =
+		conversations.into_iter().map(Into::into).collect();
+	context.insert("conversations", &conversations);
+
+	// rendering the template
+	match metrics::render(tera, "messages/inbox.html", &context) {
+		Ok(s) => Ok(RawHtml(s)),
+		Err(e) => Err(e.into()),
+	}
+}
+
+#[post("/messages", data = "<conversation_input>")]
+async fn start_conversation(
+	jar: &CookieJar<'_>,
+	conversation_input: Form<NewConversationInput>,
+	pool: &State<PgPool>,
+	store: &State<Store>,
+) -> Result<Redirect, AppError> {
+	let SessionData { user } = jar.into();
+
+	let handles = match &conversation_input.handles {
+		Some(h) => h,
+		None => return Err(AppError::Validation("Who is the conversation with?")),
+	};
+
+	let user = match user {
+		Some(user) => user,
+		None => return Err(AppError::Unauthorized),
+	};
+	let account = match store.find_account_by_id(user.id, None).await {
+		Ok(Some(acc)) => acc,
+		Ok(None) => return Err(AppError::Unauthorized),
+		Err(e) => return Err(e.into()),
+	};
+
+	// handles can be separated by spaces or commas and optionally start with @
+	let handles: Vec<&str> = handles
+		.split(|c: char| c.is_whitespace() || c == ',')
+		.map(|h| h.trim_start_matches('@'))
+		.filter(|h| !h.is_empty())
+		.collect();
+	if handles.is_empty() {
+		return Err(AppError::Validation("Who is the conversation with?"));
+	}
+	if handles.len() >= MAX_CONVERSATION_MEMBERS {
+		return Ok(Redirect::to("/messages?err=size"));
+	}
+
+	let mut recipients = Vec::with_capacity(handles.len());
+	for handle in handles {
+		match store.find_account_by_handle(handle, None).await {
+			Ok(Some(acc)) if acc.id != account.id => recipients.push(acc.id),
+			Ok(Some(_)) => (),
+			Ok(None) => return Ok(Redirect::to("/messages?err=handle")),
+			Err(e) => return Err(e.into()),
+		}
+	}
+	if recipients.is_empty() {
+		return Err(AppError::Validation(
+			"You can't start a conversation with yourself",
+		));
+	}
+	if account.is_suspended() {
+		return Err(AppError::Forbidden);
+	}
+
+	match account.can_message(pool, &recipients).await {
+		Ok(true) => (),
+		Ok(false) => return Ok(Redirect::to("/messages?err=permission")),
+		Err(e) => return Err(e.into()),
+	}
+
+	match account.start_conversation(pool, &recipients).await {
+		Ok(id) => Ok(Redirect::to(format!("/messages/{}", id.0))),
+		Err(e) => Err(e.into()),
+	}
+}
+
+#[get("/messages/<conversation_id>")]
+async fn get_conversation(
+	jar: &CookieJar<'_>,
+	conversation_id: ConversationId,
+	pool: &State<PgPool>,
+	tera: &State<Tera>,
+	store: &State<Store>,
+) -> Result<RawHtml<String>, AppError> {
+	let SessionData { user } = jar.into();
+
+	let user = match user {
+		Some(user) => user,
+		None => return Err(AppError::Unauthorized),
+	};
+	let account = match store.find_account_by_id(user.id, None).await {
+		Ok(Some(acc)) => acc,
+		Ok(None) => return Err(AppError::Unauthorized),
+		Err(e) => return Err(e.into()),
+	};
+
+	let conversation = match account.find_conversation(pool, conversation_id).await {
+		Ok(Some(c)) => c,
+		Ok(None) => return Err(AppError::NotFound),
+		Err(e) => return Err(e.into()),
+	};
+
+	// creating template context
+	let mut context = Context::new();
+
+	// inserting user data
+	context.insert("user", &user);
+
+	// inserting messages
+	let messages = match conversation.get_messages(pool, 200, account.id).await {
+		Ok(m) => m,
+		Err(e) => return Err(e.into()),
+	};
+	let messages: Vec<TemplateMessage> = messages.into_iter().map(Into::into).collect();
+	context.insert("messages", &messages);
+
+	if let Err(e) = conversation.mark_read(pool, account.id).await {
+		return Err(e.into());
+	}
+
+	let conversation: InboxTemplateConversation = conversation.into();
+	context.insert("conversation", &conversation);
+
+	// rendering the template
+	match metrics::render(tera, "messages/conversation.html", &context) {
+		Ok(s) => Ok(RawHtml(s)),
+		Err(e) => Err(e.into()),
+	}
+}
+
+#[post("/messages/<conversation_id>", data = "<message_input>")]
+async fn send_message(
+	jar: &CookieJar<'_>,
+	conversation_id: ConversationId,
+	message_input: Form<MessageInput>,
+	pool: &State<PgPool>,
+	store: &State<Store>,
+) -> Result<Redirect, AppError> {
+	let SessionData { user } = jar.into();
+
+	let body = match &message_input.body {
+		Some(b) if !b.trim().is_empty() => b.as_ref(),
+		_ => return Err(AppError::Validation("A message needs a body")),
+	};
+
+	let user = match user {
+		Some(user) => user,
+		None => return Err(AppError::Unauthorized),
+	};
+	let account = match store.find_account_by_id(user.id, None).await {
+		Ok(Some(acc)) => acc,
+		Ok(None) => return Err(AppError::Unauthorized),
+		Err(e) => return Err(e.into()),
+	};
+
+	if account.is_suspended() {
+		return Err(AppError::Forbidden);
+	}
+
+	let conversation = match account.find_conversation(pool, conversation_id).await {
+		Ok(Some(c)) => c,
+		Ok(None) => return Err(AppError::NotFound),
+		Err(e) => return Err(e.into()),
+	};
+
+	// permissions and blocks are checked on every message, not only when the conversation starts
+	let members = match conversation.get_member_ids(pool).await {
+		Ok(m) => m,
+		Err(e) => return Err(e.into()),
+	};
+	match account.can_message(pool, &members).await {
+		Ok(true) => (),
+		Ok(false) => return Err(AppError::Forbidden),
+		Err(e) => return Err(e.into()),
+	}
+
+	match conversation.send_message(pool, &account, body).await {
+		Ok(_) => Ok(Redirect::to(format!("/messages/{}", conversation_id.0))),
+		Err(e) => Err(e.into()),
+	}
+}
+
+#[post("/messages/<conversation_id>/delete/<message_id>")]
+async fn delete_message(
+	jar: &CookieJar<'_>,
+	conversation_id: ConversationId,
+	message_id: MessageId,
+	pool: &State<PgPool>,
+	store: &State<Store>,
+) -> Result<Redirect, AppError> {
+	let SessionData { user } = jar.into();
+
+	let user = match user {
+		Some(user) => user,
+		None => return Err(AppError::Unauthorized),
+	};
+	let account = match store.find_account_by_id(user.id, None).await {
+		Ok(Some(acc)) => acc,
+		Ok(None) => return Err(AppError::Unauthorized),
+		Err(e) => return Err(e.into()),
+	};
+
+	let conversation = match account.find_conversation(pool, conversation_id).await {
+		Ok(Some(c)) => c,
+		Ok(None) => return Err(AppError::NotFound),
+		Err(e) => return Err(e.into()),
+	};
+
+	match conversation
+		.delete_message(pool, message_id, account.id)
+		.await
+	{
+		Ok(true) => Ok(Redirect::to(format!("/messages/{}", conversation_id.0))),
+		Ok(false) => Err(AppError::NotFound),
+		Err(e) => Err(e.into()),
+	}
+}
+
+// notifications
+
+#[get("/notifications")]
+async fn get_notifications(
+	jar: &CookieJar<'_>,
+	pool: &State<PgPool>,
+	tera: &State<Tera>,
+	store: &State<Store>,
+) -> Result<RawHtml<String>, AppError> {
+	let SessionData { user } = jar.into();
+
+	let user = match user {
+		Some(user) => user,
+		None => return Err(AppError::Unauthorized),
+	};
+	let account = match store.find_account_by_id(user.id, None).await {
+		Ok(Some(acc)) => acc,
+		Ok(None) => return Err(AppError::Unauthorized),
+		Err(e) => return Err(e.into()),
+	};
+
+	// creating template context
+	let mut context = Context::new();
+
+	// inserting user data
+	context.insert("user", &user);
+
+	// inserting notifications
+	let notifications = match account.get_notifications(pool, 100).await {
+		Ok(n) => n,
+		Err(e) => return Err(e.into()),
+	};
+	let notifications: Vec<TemplateNotification> =
+		notifications.into_iter().map(Into::into).collect();
+	context.insert("notifications", &notifications);
+
+	if let Err(e) = account.mark_notifications_seen(pool).await {
+		return Err(e.into());
+	}
+
+	// rendering the template
+	match metrics::render(tera, "account/notifications.html", &context) {
+		Ok(s) => Ok(RawHtml(s)),
+		Err(e) => Err(e.into()),
+	}
+}
+
+#[get("/events")]
+fn get_events<'a>(
+	jar: &CookieJar<'_>,
+	mut shutdown: Shutdown,
+	pool: &'a State<PgPool>,
+	tera: &'a State<Tera>,
+) -> Result<EventStream![Event + 'a], AppError> {
+	let SessionData { user } = jar.into();
+
+	let user = match user {
+		Some(user) => user,
+		None => return Err(AppError::Unauthorized),
+	};
+	let mut updates = events::subscribe();
+
+	Ok(EventStream! {
+		// sending the initial unread count so the badge doesn't need a separate request
+		match Notification::count_unseen(pool, user.id).await {
+			Ok(count) => yield Event::json(&count).event("notifications"),
+			Err(e) => {
+				e.print_and_status();
+			}
+		}
+
+		loop {
+			let update = select! {
+				update = updates.recv() => match update {
+					Ok(u) => u,
+					Err(RecvError::Lagged(_)) => continue,
+					Err(RecvError::Closed) => break,
+				},
+				_ = &mut shutdown => break,
+			};
+
+			match update {
+				Update::Post(post) => {
+					// muted and limited accounts only hide posts from the timeline, not from threads
+					if post.parent_id.0.is_none() {
+						match post.hidden_from(pool, user.id).await {
+							Ok(false) => (),
+							Ok(true) => continue,
+							Err(e) => {
+								e.print_and_status();
+								continue;
+							}
+						}
+					}
+
+					// rendering the post the same way the feed would for this user
+					let mut context = Context::new();
+					context.insert("user", &user);
+					let base_post = BaseTemplatePost::from(post.as_ref());
+					context.insert("base_post", &base_post);
+
+					match metrics::render(tera, "base/base_post.html", &context) {
+						Ok(html) => yield Event::json(&LivePost {
+							id: base_post.id,
+							parent_id: base_post.parent_id,
+							html,
+						})
+						.event("post"),
+						Err(e) => {
+							e.print_and_status();
+						}
+					}
+				}
+				Update::Votes { post_id, votes } => {
+					yield Event::json(&LiveVotes {
+						id: post_id.0,
+						likes: votes.0,
+					})
+					.event("likes")
+				}
+				Update::Notification { account_id } if account_id == user.id => {
+					match Notification::count_unseen(pool, user.id).await {
+						Ok(count) => yield Event::json(&count).event("notifications"),
+						Err(e) => {
+							e.print_and_status();
+						}
+					}
+				}
+				Update::Notification { .. } => (),
+			}
+		}
+	})
+}
+
+// mutes
+
+async fn mute_or_unmute_user(
+	store: &Store,
+	pool: &PgPool,
+	jar: &CookieJar<'_>,
+	handle: &str,
+	mute: bool,
+) -> Result<Redirect, AppError> {
+	let SessionData { user } = jar.into();
+
+	let user = match user {
+		Some(user) => user,
+		None => return Err(AppError::Unauthorized),
+	};
+	let account = match store.find_account_by_id(user.id, None).await {
+		Ok(Some(acc)) => acc,
+		Ok(None) => return Err(AppError::Unauthorized),
+		Err(e) => return Err(e.into()),
+	};
+
+	let to_mute = match store.find_account_by_handle(handle, None).await {
+		Ok(Some(acc)) => acc,
+		Ok(None) => return Err(AppError::NotFound),
+		Err(e) => return Err(e.into()),
+	};
+
+	if account.id == to_mute.id {
+		return Err(AppError::Forbidden);
+	}
+
+	let result = if mute {
+		account.mute(pool, to_mute.id).await
+	} else {
+		account.unmute(pool, to_mute.id).await
+	};
+
+	match result {
+		Ok(()) => Ok(Redirect::to(format!("/user/{handle}"))),
+		Err(e) => Err(e.into()),
+	}
+}
+
+#[post("/user/<handle>/mute")]
+async fn mute_user(
+	jar: &CookieJar<'_>,
+	handle: &str,
+	pool: &State<PgPool>,
+	store: &State<Store>,
+) -> Result<Redirect, AppError> {
+	mute_or_unmute_user(store, pool, jar, handle, true).await
+}
+
+#[post("/user/<handle>/unmute")]
+async fn unmute_user(
+	jar: &CookieJar<'_>,
+	handle: &str,
+	pool: &State<PgPool>,
+	store: &State<Store>,
+) -> Result<Redirect, AppError> {
+	mute_or_unmute_user(store, pool, jar, handle, false).await
+}
+
+async fn mute_or_unmute_thread(
+	store: &Store,
+	pool: &PgPool,
+	jar: &CookieJar<'_>,
+	post_id: PostId,
+	mute: bool,
+) -> Result<Redirect, AppError> {
+	let SessionData { user } = jar.into();
+
+	let user = match user {
+		Some(user) => user,
+		None => return Err(AppError::Unauthorized),
+	};
+	let account = match store.find_account_by_id(user.id, None).await {
+		Ok(Some(acc)) => acc,
+		Ok(None) => return Err(AppError::Unauthorized),
+		Err(e) => return Err(e.into()),
+	};
+
+	let post = match store.find_post_by_id(post_id, Some(user.id)).await {
+		Ok(Some(post)) => post,
+		Ok(None) => return Err(AppError::NotFound),
+		Err(e) => return Err(e.into()),
+	};
+	// muting any post of a thread mutes the whole thread
+	let root_id = match post.get_root_id(pool).await {
+		Ok(id) => id,
+		Err(e) => return Err(e.into()),
+	};
+
+	let result = if mute {
+		account.mute_thread(pool, root_id).await
+	} else {
+		account.unmute_thread(pool, root_id).await
+	};
+
+	match result {
+		Ok(()) => Ok(Redirect::to(format!("/post/{}", post_id.0))),
+		Err(e) => Err(e.into()),
+	}
+}
+
+#[post("/post/<post_id>/mute")]
+async fn mute_thread(
+	jar: &CookieJar<'_>,
+	post_id: PostId,
+	pool: &State<PgPool>,
+	store: &State<Store>,
+) -> Result<Redirect, AppError> {
+	mute_or_unmute_thread(store, pool, jar, post_id, true).await
+}
+
+#[post("/post/<post_id>/unmute")]
+async fn unmute_thread(
+	jar: &CookieJar<'_>,
+	post_id: PostId,
+	pool: &State<PgPool>,
+	store: &State<Store>,
+) -> Result<Redirect, AppError> {
+	mute_or_unmute_thread(store, pool, jar, post_id, false).await
+}
+
+#[get("/settings/mutes")]
+async fn get_mutes(
+	jar: &CookieJar<'_>,
+	pool: &State<PgPool>,
+	tera: &State<Tera>,
+	store: &State<Store>,
+) -> Result<RawHtml<String>, AppError> {
+	let SessionData { user } = jar.into();
+
+	let user = match user {
+		Some(user) => user,
+		None => return Err(AppError::Unauthorized),
+	};
+	let account = match store.find_account_by_id(user.id, None).await {
+		Ok(Some(acc)) => acc,
+		Ok(None) => return Err(AppError::Unauthorized),
+		Err(e) => return Err(e.into()),
+	};
+
+	// creating template context
+	let mut context = Context::new();
+
+	// inserting user data
+	context.insert("user", &user);
+
+	// inserting muted accounts
+	let accounts = match account.get_muted_accounts(pool).await {
+		Ok(a) => a,
+		Err(e) => return Err(e.into()),
+	};
+	context.insert("accounts", &accounts);
+
+	// inserting muted words
+	let words = match account.get_muted_words(pool).await {
+		Ok(w) => w,
+		Err(e) => return Err(e.into()),
+	};
+	let words: Vec<TemplateMutedWord> = words.into_iter().map(Into::into).collect();
+	context.insert("words", &words);
+
+	// inserting muted threads
+	let threads = match account.get_muted_threads(pool).await {
+		Ok(t) => t,
+		Err(e) => return Err(e.into()),
+	};
+	let threads: Vec<BaseTemplatePost> = threads.into_iter().map(Into::into).collect();
+	context.insert("threads", &threads);
+
+	// rendering the template
+	match metrics::render(tera, "account/mutes.html", &context) {
+		Ok(s) => Ok(RawHtml(s)),
+		Err(e) => Err(e.into()),
+	}
+}
+
+#[post("/settings/mutes/words", data = "<word_input>")]
+async fn add_muted_word(
+	jar: &CookieJar<'_>,
+	word_input: Form<MutedWordInput>,
+	pool: &State<PgPool>,
+	store: &State<Store>,
+) -> Result<Redirect, AppError> {
+	let SessionData { user } = jar.into();
+
+	let phrase = match &word_input.phrase {
+		Some(p) if !p.trim().is_empty() && p.trim().chars().count() <= MAX_PHRASE_LENGTH => {
+			p.trim()
+		}
+		_ => {
+			return Err(AppError::Validation(
+				"Muted words need to be between 1 and 100 characters",
+			))
+		}
+	};
+
+	let user = match user {
+		Some(user) => user,
+		None => return Err(AppError::Unauthorized),
+	};
+	let account = match store.find_account_by_id(user.id, None).await {
+		Ok(Some(acc)) => acc,
+		Ok(None) => return Err(AppError::Unauthorized),
+		Err(e) => return Err(e.into()),
+	};
+
+	match account.mute_word(pool, phrase, word_input.days).await {
+		Ok(_) => Ok(Redirect::to("/settings/mutes")),
+		Err(e) => Err(e.into()),
+	}
+}
+
+#[post("/settings/mutes/words/<word_id>/delete")]
+async fn delete_muted_word(
+	jar: &CookieJar<'_>,
+	word_id: MutedWordId,
+	pool: &State<PgPool>,
+	store: &State<Store>,
+) -> Result<Redirect, AppError> {
+	let SessionData { user } = jar.into();
+
+	let user = match user {
+		Some(user) => user,
+		None => return Err(AppError::Unauthorized),
+	};
+	let account = match store.find_account_by_id(user.id, None).await {
+		Ok(Some(acc)) => acc,
+		Ok(None) => return Err(AppError::Unauthorized),
+		Err(e) => return Err(e.into()),
+	};
+
+	match account.unmute_word(pool, word_id).await {
+		Ok(true) => Ok(Redirect::to("/settings/mutes")),
+		Ok(false) => Err(AppError::NotFound),
+		Err(e) => Err(e.into()),
+	}
+}
+
+#[get("/invites")]
+async fn get_invites(
+	jar: &CookieJar<'_>,
+	origin: &Origin<'_>,
+	pool: &State<PgPool>,
+	tera: &State<Tera>,
+	store: &State<Store>,
+) -> Result<RawHtml<String>, AppError> {
+	let SessionData { user } = jar.into();
+
+	let user = match user {
+		Some(user) => user,
+		None => return Err(AppError::Unauthorized),
+	};
+	let account = match store.find_account_by_id(user.id, None).await {
+		Ok(Some(acc)) => acc,
+		Ok(None) => return Err(AppError::Unauthorized),
+		Err(e) => return Err(e.into()),
+	};
+
+	// creating template context
+	let mut context = Context::new();
+
+	if let Some(q) = origin.query() {
+		if q.segments().any(|pair| pair == ("err", "limit")) {
+			context.insert(
+				"error",
+				&format!("You can only have {USER_MAX_ACTIVE_INVITES} active invites"),
+			);
+		}
+	}
+
+	// inserting user data
+	context.insert("user", &user);
+
+	// inserting invites
+	let invites = match account.get_invites(pool).await {
+		Ok(i) => i,
+		Err(e) => return Err(e.into()),
+	};
+	let invites: Vec<TemplateInvite> = invites.into_iter().map(Into::into).collect();
+	context.insert("invites", &invites);
+
+	// inserting limits
+	let is_admin = account.role == Role::Admin;
+	context.insert("is_admin", &is_admin);
+	context.insert("max_uses", &if is_admin { MAX_USES } else { USER_MAX_USES });
+	context.insert("max_days", &if is_admin { MAX_DAYS } else { USER_MAX_DAYS });
+
+	// rendering the template
+	match metrics::render(tera, "account/invites.html", &context) {
+		Ok(s) => Ok(RawHtml(s)),
+		Err(e) => Err(e.into()),
+	}
+}
+
+#[post("/invites", data = "<invite_input>")]
+async fn create_invite(
+	jar: &CookieJar<'_>,
+	invite_input: Form<InviteInput>,
+	pool: &State<PgPool>,
+	store: &State<Store>,
+) -> Result<Redirect, AppError> {
+	let SessionData { user } = jar.into();
+
+	let user = match user {
+		Some(user) => user,
+		None => return Err(AppError::Unauthorized),
+	};
+	let account = match store.find_account_by_id(user.id, None).await {
+		Ok(Some(acc)) => acc,
+		Ok(None) => return Err(AppError::Unauthorized),
+		Err(e) => return Err(e.into()),
+	};
+	if account.is_suspended() || account.limited {
+		return Err(AppError::Forbidden);
+	}
+
+	// admins can hand out bigger invites and ones that never expire
+	let is_admin = account.role == Role::Admin;
+	let (max_uses, max_days) = match is_admin {
+		true => (MAX_USES, MAX_DAYS),
+		false => (USER_MAX_USES, USER_MAX_DAYS),
+	};
+	if !(1..=max_uses).contains(&invite_input.max_uses) {
+		return Err(AppError::Validation(
+			"That's more uses than an invite can have",
+		));
+	}
+	let days = match invite_input.days {
+		Some(days) if (1..=max_days).contains(&days) => Some(days),
+		None if is_admin => None,
+		_ => {
+			return Err(AppError::Validation(
+				"That's longer than an invite can last",
+			))
+		}
+	};
+
+	if !is_admin {
+		match account.count_active_invites(pool).await {
+			Ok(count) if count >= USER_MAX_ACTIVE_INVITES => {
+				return Ok(Redirect::to("/invites?err=limit"))
+			}
+			Ok(_) => (),
+			Err(e) => return Err(e.into()),
+		}
+	}
+
+	let expire_time = days.map(|d| chrono::Utc::now().naive_utc() + chrono::TimeDelta::days(d));
+	match account
+		.create_invite(pool, invite_input.max_uses, expire_time)
+		.await
+	{
+		Ok(_) => Ok(Redirect::to("/invites")),
+		Err(e) => Err(e.into()),
+	}
+}
+
+#[post("/invites/<code>/delete")]
+async fn delete_invite(
+	jar: &CookieJar<'_>,
+	code: &str,
+	pool: &State<PgPool>,
+	store: &State<Store>,
+) -> Result<Redirect, AppError> {
+	let SessionData { user } = jar.into();
+
+	let user = match user {
+		Some(user) => user,
+		None => return Err(AppError::Unauthorized),
+	};
+	let account = match store.find_account_by_id(user.id, None).await {
+		Ok(Some(acc)) => acc,
+		Ok(None) => return Err(AppError::Unauthorized),
+		Err(e) => return Err(e.into()),
+	};
+
+	match account.delete_invite(pool, code).await {
+		Ok(true) => Ok(Redirect::to("/invites")),
+		Ok(false) => Err(AppError::NotFound),
+		Err(e) => Err(e.into()),
+	}
+}
+
+// reports
+
+#[get("/post/<post_id>/report")]
+async fn get_report_post(
+	jar: &CookieJar<'_>,
+	post_id: PostId,
+	store: &State<Store>,
+	tera: &State<Tera>,
+) -> Result<RawHtml<String>, AppError> {
+	let SessionData { user } = jar.into();
+
+	let user = match user {
+		Some(user) => user,
+		None => return Err(AppError::Unauthorized),
+	};
+
+	// creating template context
+	let mut context = Context::new();
+
+	// inserting user data
+	context.insert("user", &user);
+
+	// inserting post
+	let post = match store.find_post_by_id(post_id, Some(user.id)).await {
+		Ok(Some(p)) => p,
+		Ok(None) => return Err(AppError::NotFound),
+		Err(e) => return Err(e.into()),
+	};
+	if post.author_id == user.id {
+		return Err(AppError::Forbidden);
+	}
+	let base_post: BaseTemplatePost = post.into();
+	context.insert("base_post", &base_post);
+	context.insert("action", &format!("/post/{}/report", post_id.0));
+
+	// rendering the template
+	match metrics::render(tera, "report/index.html", &context) {
+		Ok(s) => Ok(RawHtml(s)),
+		Err(e) => Err(e.into()),
+	}
+}
+
+#[post("/post/<post_id>/report", data = "<report_input>")]
+async fn report_post(
+	jar: &CookieJar<'_>,
+	post_id: PostId,
+	report_input: Form<ReportInput>,
+	pool: &State<PgPool>,
+	store: &State<Store>,
+) -> Result<Redirect, AppError> {
+	let SessionData { user } = jar.into();
+
+	let user = match user {
+		Some(user) => user,
+		None => return Err(AppError::Unauthorized),
+	};
+	let account = match store.find_account_by_id(user.id, None).await {
+		Ok(Some(acc)) => acc,
+		Ok(None) => return Err(AppError::Unauthorized),
+		Err(e) => return Err(e.into()),
+	};
+
+	let post = match store.find_post_by_id(post_id, Some(user.id)).await {
+		Ok(Some(post)) => post,
+		Ok(None) => return Err(AppError::NotFound),
+		Err(e) => return Err(e.into()),
+	};
+	if post.author_id == account.id {
+		return Err(AppError::Forbidden);
+	}
+
+	let details = report_input
+		.details
+		.as_deref()
+		.filter(|d| !d.trim().is_empty());
+	match account
+		.report(
+			pool,
+			post.author_id,
+			Some(post.id),
+			report_input.reason,
+			details,
+		)
+		.await
+	{
+		Ok(_) => Ok(Redirect::to(format!("/post/{}", post_id.0))),
+		Err(e) => Err(e.into()),
+	}
+}
+
+#[get("/user/<handle>/report")]
+async fn get_report_user(
+	jar: &CookieJar<'_>,
+	handle: &str,
+	store: &State<Store>,
+	tera: &State<Tera>,
+) -> Result<RawHtml<String>, AppError> {
+	let SessionData { user } = jar.into();
+
+	let user = match user {
+		Some(user) => user,
+		None => return Err(AppError::Unauthorized),
+	};
+
+	// creating template context
+	let mut context = Context::new();
+
+	// inserting user data
+	context.insert("user", &user);
+
+	// inserting account
+	let account = match store.find_account_by_handle(handle, Some(user.id)).await {
+		Ok(Some(acc)) => acc,
+		Ok(None) => return Err(AppError::NotFound),
+		Err(e) => return Err(e.into()),
+	};
+	if account.id == user.id {
+		return Err(AppError::Forbidden);
+	}
+	context.insert("account", &account);
+	context.insert("action", &format!("/user/{handle}/report"));
+
+	// rendering the template
+	match metrics::render(tera, "report/index.html", &context) {
+		Ok(s) => Ok(RawHtml(s)),
+		Err(e) => Err(e.into()),
+	}
+}
+
+#[post("/user/<handle>/report", data = "<report_input>")]
+async fn report_user(
+	jar: &CookieJar<'_>,
+	handle: &str,
+	report_input: Form<ReportInput>,
+	pool: &State<PgPool>,
+	store: &State<Store>,
+) -> Result<Redirect, AppError> {
+	let SessionData { user } = jar.into();
+
+	let user = match user {
+		Some(user) => user,
+		None => return Err(AppError::Unauthorized),
+	};
+	let account = match store.find_account_by_id(user.id, None).await {
+		Ok(Some(acc)) => acc,
+		Ok(None) => return Err(AppError::Unauthorized),
+		Err(e) => return Err(e.into()),
+	};
+
+	let reported = match store.find_account_by_handle(handle, None).await {
+		Ok(Some(acc)) => acc,
+		Ok(None) => return Err(AppError::NotFound),
+		Err(e) => return Err(e.into()),
+	};
+	if reported.id == account.id {
+		return Err(AppError::Forbidden);
+	}
+
+	let details = report_input
+		.details
+		.as_deref()
+		.filter(|d| !d.trim().is_empty());
+	match account
+		.report(pool, reported.id, None::<i64>, report_input.reason, details)
+		.await
+	{
+		Ok(_) => Ok(Redirect::to(format!("/user/{handle}"))),
+		Err(e) => Err(e.into()),
+	}
+}
+
+// moderation
+
+#[get("/moderation")]
+async fn get_moderation(
+	jar: &CookieJar<'_>,
+	_moderator: Moderator,
+	pool: &State<PgPool>,
+	tera: &State<Tera>,
+) -> Result<RawHtml<String>, AppError> {
+	let SessionData { user } = jar.into();
+
+	// creating template context
+	let mut context = Context::new();
+
+	// inserting user data
+	context.insert("user", &user);
+
+	// inserting reports
+	let open = match Report::get_by_status(pool, ReportStatus::Open, 100).await {
+		Ok(r) => r,
+		Err(e) => return Err(e.into()),
+	};
+	let open: Vec<TemplateReport> = open.into_iter().map(Into::into).collect();
+	context.insert("open_reports", &open);
+
+	let mut closed = Vec::new();
+	for status in [ReportStatus::Resolved, ReportStatus::Dismissed] {
+		match Report::get_by_status(pool, status, 50).await {
+			Ok(r) => closed.extend(r),
+			Err(e) => return Err(e.into()),
+		}
+	}
+	closed.sort_by_key(|r| Reverse(r.resolve_time));
+	let closed: Vec<TemplateReport> = closed.into_iter().map(Into::into).collect();
+	context.insert("closed_reports", &closed);
+
+	// inserting the moderation log
+	let log = match LogEntry::get_recent(pool, 100).await {
+		Ok(l) => l,
+		Err(e) => return Err(e.into()),
+	};
+	let log: Vec<TemplateLogEntry> = log.into_iter().map(Into::into).collect();
+	context.insert("log", &log);
+
+	// rendering the template
+	match metrics::render(tera, "moderation/index.html", &context) {
+		Ok(s) => Ok(RawHtml(s)),
+		Err(e) => Err(e.into()),
+	}
+}
+
+#[post("/moderation/reports/<report_id>", data = "<moderation_input>")]
+async fn moderate_report(
+	moderator: Moderator,
+	report_id: ReportId,
+	moderation_input: Form<ModerationInput>,
+	pool: &State<PgPool>,
+	store: &State<Store>,
+) -> Result<Redirect, AppError> {
+	let Moderator(moderator) = moderator;
+
+	let report = match Report::find_by_id(pool, report_id).await {
+		Ok(Some(r)) => r,
+		Ok(None) => return Err(AppError::NotFound),
+		Err(e) => return Err(e.into()),
+	};
+	if report.status != ReportStatus::Open {
+		return Err(AppError::Validation("This report was already handled"));
+	}
+
+	let note = moderation_input
+		.note
+		.as_deref()
+		.map(str::trim)
+		.filter(|n| !n.is_empty());
+	let with_note = |action: &str| match note {
+		Some(note) => format!("{action}: {note}"),
+		None => action.to_string(),
+	};
+
+	match moderation_input.action {
+		ModerationAction::Dismiss => {
+			let action = with_note("No action taken");
+			if let Err(e) = report
+				.resolve(pool, &moderator, ReportStatus::Dismissed, &action)
+				.await
+			{
+				return Err(e.into());
+			}
+			if let Err(e) = moderator
+				.log_action(
+					pool,
+					LogAction::DismissReport,
+					Some(report.account_id),
+					report.post_id.0,
+					&action,
+				)
+				.await
+			{
+				return Err(e.into());
+			}
+		}
+		ModerationAction::RemovePost => {
+			let post = match report.post_id.0 {
+				Some(post_id) => match store.find_post_by_id(post_id, None).await {
+					Ok(Some(post)) => post,
+					Ok(None) => return Err(AppError::NotFound),
+					Err(e) => return Err(e.into()),
+				},
+				None => return Err(AppError::Validation("This report isn't about a post")),
+			};
+			// resolving before deleting, since deleting unlinks the post from its reports
+			let action = with_note("Post removed");
+			if let Err(e) = report
+				.resolve(pool, &moderator, ReportStatus::Resolved, &action)
+				.await
+			{
+				return Err(e.into());
+			}
+			if let Err(e) = moderator
+				.log_action(
+					pool,
+					LogAction::DeletePost,
+					Some(post.author_id),
+					Some(post.id),
+					&action,
+				)
+				.await
+			{
+				return Err(e.into());
+			}
+			if let Err(e) = store.delete_post(post).await {
+				return Err(e.into());
+			}
+		}
+		ModerationAction::Suspend => {
+			let account = match store.find_account_by_id(report.account_id, None).await {
+				Ok(Some(acc)) => acc,
+				Ok(None) => return Err(AppError::NotFound),
+				Err(e) => return Err(e.into()),
+			};
+			if !outranks(&moderator, &account) {
+				return Err(AppError::Forbidden);
+			}
+			let reason = note.unwrap_or(report.reason.as_str());
+			if let Err(e) = store.suspend(&account, reason, moderation_input.days).await {
+				return Err(e.into());
+			}
+			let action = with_note(&match moderation_input.days {
+				Some(1) => "Account suspended for 1 day".to_string(),
+				Some(days) => format!("Account suspended for {days} days"),
+				None => "Account suspended".to_string(),
+			});
+			if let Err(e) = report
+				.resolve(pool, &moderator, ReportStatus::Resolved, &action)
+				.await
+			{
+				return Err(e.into());
+			}
+			if let Err(e) = moderator
+				.log_action(
+					pool,
+					LogAction::Suspend,
+					Some(account.id),
+					None::<i64>,
+					&action,
+				)
+				.await
+			{
+				return Err(e.into());
+			}
+		}
+		ModerationAction::Limit => {
+			let account = match store.find_account_by_id(report.account_id, None).await {
+				Ok(Some(acc)) => acc,
+				Ok(None) => return Err(AppError::NotFound),
+				Err(e) => return Err(e.into()),
+			};
+			if !outranks(&moderator, &account) {
+				return Err(AppError::Forbidden);
+			}
+			if let Err(e) = store.limit(&account).await {
+				return Err(e.into());
+			}
+			let action = with_note("Account limited");
+			if let Err(e) = report
+				.resolve(pool, &moderator, ReportStatus::Resolved, &action)
+				.await
+			{
+				return Err(e.into());
+			}
+			if let Err(e) = moderator
+				.log_action(
+					pool,
+					LogAction::Limit,
+					Some(account.id),
+					None::<i64>,
+					&action,
+				)
+				.await
+			{
+				return Err(e.into());
+			}
+		}
+	}
+
+	Ok(Redirect::to("/moderation"))
+}
+
+#[post("/moderation/user/<handle>/lift")]
+async fn lift_restrictions(
+	moderator: Moderator,
+	handle: &str,
+	pool: &State<PgPool>,
+	store: &State<Store>,
+) -> Result<Redirect, AppError> {
+	let Moderator(moderator) = moderator;
+
+	let account = match store.find_account_by_handle(handle, None).await {
+		Ok(Some(acc)) => acc,
+		Ok(None) => return Err(AppError::NotFound),
+		Err(e) => return Err(e.into()),
+	};
+
+	// the same roles that can restrict an account can lift it
+	if !outranks(&moderator, &account) {
+		return Err(AppError::Forbidden);
+	}
+	if let Err(e) = store.lift_restrictions(&account).await {
+		return Err(e.into());
+	}
+	match moderator
+		.log_action(
+			pool,
+			LogAction::Lift,
+			Some(account.id),
+			None::<i64>,
+			"Suspension and limits lifted",
+		)
+		.await
+	{
+		Ok(()) => Ok(Redirect::to(format!("/user/{}", account.handle))),
+		Err(e) => Err(e.into()),
+	}
+}
+
+// admin
+
+#[get("/admin?<q>")]
+async fn get_admin(
+	jar: &CookieJar<'_>,
+	_admin: Admin,
+	q: Option<&str>,
+	pool: &State<PgPool>,
+	tera: &State<Tera>,
+) -> Result<RawHtml<String>, AppError> {
+	let SessionData { user } = jar.into();
+
+	// creating template context
+	let mut context = Context::new();
+
+	// inserting user data
+	context.insert("user", &user);
+
+	// inserting accounts
+	let query = q.unwrap_or_default().trim().trim_start_matches('@');
+	let accounts = match Account::search(pool, query, 100).await {
+		Ok(a) => a,
+		Err(e) => return Err(e.into()),
+	};
+	context.insert("accounts", &accounts);
+	context.insert("query", query);
+
+	// rendering the template
+	match metrics::render(tera, "admin/index.html", &context) {
+		Ok(s) => Ok(RawHtml(s)),
+		Err(e) => Err(e.into()),
+	}
+}
+
+#[post("/admin/user/<handle>/role", data = "<role_input>")]
+async fn set_role(
+	admin: Admin,
+	handle: &str,
+	role_input: Form<RoleInput>,
+	pool: &State<PgPool>,
+	store: &State<Store>,
+) -> Result<Redirect, AppError> {
+	let Admin(admin) = admin;
+
+	let account = match store.find_account_by_handle(handle, None).await {
+		Ok(Some(acc)) => acc,
+		Ok(None) => return Err(AppError::NotFound),
+		Err(e) => return Err(e.into()),
+	};
+
+	// admins can't demote themselves, so there is always at least one admin left
+	if account.id == admin.id {
+		return Err(AppError::Forbidden);
+	}
+	if account.role == role_input.role {
+		return Ok(Redirect::to("/admin"));
+	}
+
+	if let Err(e) = account.set_role(pool, role_input.role).await {
+		return Err(e.into());
+	}
+	let details = format!("{} -> {}", account.role.as_str(), role_input.role.as_str());
+	match admin
+		.log_action(
+			pool,
+			LogAction::SetRole,
+			Some(account.id),
+			None::<i64>,
+			&details,
+		)
+		.await
+	{
+		Ok(()) => Ok(Redirect::to("/admin")),
+		Err(e) => Err(e.into()),
+	}
+}
+
+#[get("/admin/pending")]
+async fn get_pending(
+	jar: &CookieJar<'_>,
+	_admin: Admin,
+	mode: &State<RegistrationMode>,
+	pool: &State<PgPool>,
+	tera: &State<Tera>,
+) -> Result<RawHtml<String>, AppError> {
+	let SessionData { user } = jar.into();
+
+	// creating template context
+	let mut context = Context::new();
+
+	// inserting user data
+	context.insert("user", &user);
+
+	// inserting pending accounts
+	let accounts = match Account::get_pending(pool, 100).await {
+		Ok(a) => a,
+		Err(e) => return Err(e.into()),
+	};
+	context.insert("accounts", &accounts);
+	context.insert("mode", mode.inner());
+
+	// rendering the template
+	match metrics::render(tera, "admin/pending.html", &context) {
+		Ok(s) => Ok(RawHtml(s)),
+		Err(e) => Err(e.into()),
+	}
+}
+
+async fn find_pending(store: &Store, handle: &str) -> Result<Account, AppError> {
+	match store.find_account_by_handle(handle, None).await {
+		Ok(Some(acc)) if acc.pending => Ok(acc),
+		Ok(_) => Err(AppError::NotFound),
+		Err(e) => Err(e.into()),
+	}
+}
+
+#[post("/admin/pending/<handle>/approve")]
+async fn approve_account(
+	admin: Admin,
+	handle: &str,
+	pool: &State<PgPool>,
+	store: &State<Store>,
+) -> Result<Redirect, AppError> {
+	let Admin(admin) = admin;
+
+	let account = find_pending(store, handle).await?;
+	if let Err(e) = account.approve(pool).await {
+		return Err(e.into());
+	}
+	match admin
+		.log_action(pool, LogAction::Approve, Some(account.id), None::<i64>, "")
+		.await
+	{
+		Ok(()) => Ok(Redirect::to("/admin/pending")),
+		Err(e) => Err(e.into()),
+	}
+}
+
+#[post("/admin/pending/<handle>/reject")]
+async fn reject_account(
+	admin: Admin,
+	handle: &str,
+	pool: &State<PgPool>,
+	store: &State<Store>,
+) -> Result<Redirect, AppError> {
+	let Admin(admin) = admin;
+
+	let account = find_pending(store, handle).await?;
+	// the account is gone afterwards, so the log only keeps its handle
+	let details = format!("@{}", account.handle);
+	if let Err(e) = store.delete_account(account).await {
+		return Err(e.into());
+	}
+	match admin
+		.log_action(pool, LogAction::Reject, None::<i32>, None::<i64>, &details)
+		.await
+	{
+		Ok(()) => Ok(Redirect::to("/admin/pending")),
+		Err(e) => Err(e.into()),
 	}
 }
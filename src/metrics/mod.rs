@@ -0,0 +1,280 @@
+use std::{sync::LazyLock, time::Instant};
+
+use prometheus::{
+	core::Collector, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec,
+	Opts, Registry, TextEncoder,
+};
+use rocket::{
+	fairing::{Fairing, Info, Kind},
+	http::{ContentType, Status},
+	request::{self, FromRequest},
+	Data, Request, Response,
+};
+use rocket_dyn_templates::tera::{self, Context, Tera};
+use sqlx::PgPool;
+use tracing::{span, Subscriber};
+use tracing_subscriber::{layer::Context as LayerContext, registry::LookupSpan, Layer};
+
+use crate::{config::MetricsConfig, crypto};
+
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+const NAMESPACE: &str = "my_twitter";
+
+/// Everything `/metrics` reports
+pub struct Metrics {
+	registry: Registry,
+	requests: IntCounterVec,
+	request_duration: HistogramVec,
+	query_duration: HistogramVec,
+	render_duration: HistogramVec,
+	pool_connections: IntGaugeVec,
+	pool_max_connections: IntGauge,
+	pub posts_created: IntCounter,
+	pub likes: IntCounter,
+	pub follows: IntCounter,
+	pub registrations: IntCounter,
+	pub failed_logins: IntCounter,
+}
+impl Metrics {
+	fn new() -> Self {
+		let registry = Registry::new();
+		let opts = |name: &str, help: &str| Opts::new(name, help).namespace(NAMESPACE);
+		let histogram_opts =
+			|name: &str, help: &str| HistogramOpts::new(name, help).namespace(NAMESPACE);
+
+		Self {
+			requests: register(
+				&registry,
+				IntCounterVec::new(
+					opts("http_requests_total", "Requests handled"),
+					&["route", "method", "status"],
+				),
+			),
+			request_duration: register(
+				&registry,
+				HistogramVec::new(
+					histogram_opts("http_request_duration_seconds", "Time to handle a request"),
+					&["route", "method"],
+				),
+			),
+			query_duration: register(
+				&registry,
+				HistogramVec::new(
+					histogram_opts(
+						"db_query_duration_seconds",
+						"Time spent in a database function",
+					),
+					&["function"],
+				),
+			),
+			render_duration: register(
+				&registry,
+				HistogramVec::new(
+					histogram_opts(
+						"template_render_duration_seconds",
+						"Time to render a template",
+					),
+					&["template"],
+				),
+			),
+			pool_connections: register(
+				&registry,
+				IntGaugeVec::new(
+					opts("db_pool_connections", "Open database connections"),
+					&["state"],
+				),
+			),
+			pool_max_connections: register(
+				&registry,
+				IntGauge::with_opts(opts(
+					"db_pool_max_connections",
+					"Connections the pool opens at most",
+				)),
+			),
+			posts_created: register(
+				&registry,
+				IntCounter::with_opts(opts("posts_created_total", "Posts and replies created")),
+			),
+			likes: register(
+				&registry,
+				IntCounter::with_opts(opts("likes_total", "Posts liked")),
+			),
+			follows: register(
+				&registry,
+				IntCounter::with_opts(opts("follows_total", "Accounts followed")),
+			),
+			registrations: register(
+				&registry,
+				IntCounter::with_opts(opts("registrations_total", "Accounts registered")),
+			),
+			failed_logins: register(
+				&registry,
+				IntCounter::with_opts(opts(
+					"failed_logins_total",
+					"Logins with a wrong password or code",
+				)),
+			),
+			registry,
+		}
+	}
+
+	fn encode(&self, pool: Option<&PgPool>) -> String {
+		// the pool is only looked at when someone asks, without one its gauges stay at zero
+		if let Some(pool) = pool {
+			let idle = pool.num_idle() as i64;
+			self.pool_connections.with_label_values(&["idle"]).set(idle);
+			self.pool_connections
+				.with_label_values(&["used"])
+				.set(pool.size() as i64 - idle);
+			self.pool_max_connections
+				.set(pool.options().get_max_connections() as i64);
+		}
+
+		TextEncoder::new()
+			.encode_to_string(&self.registry.gather())
+			.unwrap_or_else(|e| format!("# {e}\n"))
+	}
+}
+
+fn register<M: Collector + Clone + 'static>(
+	registry: &Registry,
+	metric: prometheus::Result<M>,
+) -> M {
+	let metric = metric.expect("valid metric");
+	registry
+		.register(Box::new(metric.clone()))
+		.expect("unique metric");
+	metric
+}
+
+/// Renders a template, keeping track of how long it took
+pub fn render(tera: &Tera, template: &str, context: &Context) -> tera::Result<String> {
+	let _timer = METRICS
+		.render_duration
+		.with_label_values(&[template])
+		.start_timer();
+	tera.render(template, context)
+}
+
+// when the request came in
+struct RequestStart(Instant);
+
+/// Counts and times the requests per route
+pub struct RequestMetrics;
+
+#[rocket::async_trait]
+impl Fairing for RequestMetrics {
+	fn info(&self) -> Info {
+		Info {
+			name: "Request metrics",
+			kind: Kind::Request | Kind::Response,
+		}
+	}
+
+	async fn on_request(&self, req: &mut Request<'_>, _: &mut Data<'_>) {
+		req.local_cache(|| RequestStart(Instant::now()));
+	}
+
+	async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+		let RequestStart(start) = req.local_cache(|| RequestStart(Instant::now()));
+		// the route name rather than the path, so ids in it don't make a new series each
+		let route = req
+			.route()
+			.and_then(|r| r.name.as_deref())
+			.unwrap_or("unmatched");
+		let method = req.method().as_str();
+		METRICS
+			.requests
+			.with_label_values(&[route, method, res.status().code.to_string().as_str()])
+			.inc();
+		METRICS
+			.request_duration
+			.with_label_values(&[route, method])
+			.observe(start.elapsed().as_secs_f64());
+	}
+}
+
+// when the span of a database function was opened
+struct QueryStart(Instant);
+
+/// Times the spans of the database functions, added to the subscriber in `telemetry::init`
+pub struct QueryTimer;
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for QueryTimer {
+	fn on_new_span(&self, _: &span::Attributes<'_>, id: &span::Id, ctx: LayerContext<'_, S>) {
+		if let Some(span) = ctx.span(id) {
+			span.extensions_mut().insert(QueryStart(Instant::now()));
+		}
+	}
+
+	fn on_close(&self, id: span::Id, ctx: LayerContext<'_, S>) {
+		let span = match ctx.span(&id) {
+			Some(span) => span,
+			None => return,
+		};
+		let start = match span.extensions().get::<QueryStart>() {
+			Some(QueryStart(start)) => *start,
+			None => return,
+		};
+		// post::find_by_id rather than just find_by_id, the names repeat across types
+		let module = span.metadata().target().rsplit("::").next().unwrap_or("");
+		METRICS
+			.query_duration
+			.with_label_values(&[&format!("{module}::{}", span.name())])
+			.observe(start.elapsed().as_secs_f64());
+	}
+}
+
+/// The pool if one is managed, unlike `&State<PgPool>` this doesn't keep rocket from launching without it
+pub struct PoolIfAny<'r>(Option<&'r PgPool>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for PoolIfAny<'r> {
+	type Error = ();
+
+	async fn from_request(req: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+		request::Outcome::Success(Self(req.rocket().state::<PgPool>()))
+	}
+}
+
+/// A request that is allowed to read the metrics, by its address or the token
+pub struct MetricsAccess;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for MetricsAccess {
+	type Error = ();
+
+	async fn from_request(req: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+		use rocket::outcome::Outcome::*;
+
+		let config = match req.rocket().state::<MetricsConfig>() {
+			Some(config) => config,
+			None => return Error((Status::InternalServerError, ())),
+		};
+
+		// the peer of the connection, a header with the address could be sent by anyone
+		let allowed_ip = req
+			.remote()
+			.is_some_and(|addr| config.allow.contains(&addr.ip()));
+		let token = req
+			.headers()
+			.get_one("Authorization")
+			.and_then(|h| h.strip_prefix("Bearer "));
+		let valid_token = match (&config.token, token) {
+			(Some(expected), Some(token)) => crypto::tokens_match(expected, token),
+			_ => false,
+		};
+
+		match allowed_ip || valid_token {
+			true => Success(Self),
+			false => Error((Status::Forbidden, ())),
+		}
+	}
+}
+
+#[get("/metrics")]
+pub fn get_metrics(_access: MetricsAccess, pool: PoolIfAny<'_>) -> (ContentType, String) {
+	let content_type = ContentType::new("text", "plain").with_params(("version", "0.0.4"));
+	(content_type, METRICS.encode(pool.0))
+}
@@ -0,0 +1,70 @@
+use rocket::{
+	fairing::{Fairing, Info, Kind},
+	http::{uri::Origin, CookieJar, Method},
+	response::Redirect,
+	Data, Request,
+};
+
+use tracing::{error, Instrument};
+
+use crate::{database::storage::Store, helpers::CookieJarHelper, telemetry};
+
+pub const EPOCH_COOKIE: &str = "epoch";
+
+const EXPIRED_PATH: &str = "/session_expired";
+
+/// Signs out sessions that were started before the account's last password change
+pub struct SessionFairing;
+
+#[rocket::async_trait]
+impl Fairing for SessionFairing {
+	fn info(&self) -> Info {
+		Info {
+			name: "Session epoch check",
+			kind: Kind::Request,
+		}
+	}
+
+	async fn on_request(&self, req: &mut Request<'_>, _: &mut Data<'_>) {
+		if req.uri().path().starts_with("/static") {
+			return;
+		}
+
+		let jar = req.cookies();
+		let id = match jar
+			.get_private("id")
+			.and_then(|c| c.value().parse::<i32>().ok())
+		{
+			Some(id) => id,
+			None => return,
+		};
+		let epoch = jar
+			.get_private(EPOCH_COOKIE)
+			.and_then(|c| c.value().parse::<i32>().ok());
+
+		let store = match req.rocket().state::<Store>() {
+			Some(store) => store,
+			None => return,
+		};
+		let current = store
+			.get_session_epoch(id.into())
+			.instrument(telemetry::span(req))
+			.await;
+		match current {
+			Ok(current) if current.is_some() && current == epoch => (),
+			Ok(_) => {
+				// the cookies can't be dropped for this request anymore, so it goes to a route that clears them
+				req.set_method(Method::Get);
+				req.set_uri(Origin::parse(EXPIRED_PATH).expect("valid path"));
+			}
+			// letting the request through rather than signing everyone out while the database is down
+			Err(e) => error!("{e}"),
+		}
+	}
+}
+
+#[get("/session_expired")]
+pub fn expired(jar: &CookieJar<'_>) -> Redirect {
+	jar.remove_user();
+	Redirect::to("/login?err=session")
+}
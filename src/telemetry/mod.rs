@@ -0,0 +1,129 @@
+use std::time::Instant;
+
+use rand::Rng;
+use rocket::{
+	fairing::{Fairing, Info, Kind},
+	http::Header,
+	route::{self, Handler},
+	Data, Request, Response, Route,
+};
+use tracing::{field, info, info_span, Instrument, Level, Span};
+use tracing_subscriber::{
+	filter::Targets, fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer,
+};
+
+use crate::{
+	config::{LogFormat, TracingConfig},
+	metrics::QueryTimer,
+};
+
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+/// Installs the global subscriber, before rocket starts so its own log messages go through it too
+pub fn init(config: &TracingConfig) -> Result<(), String> {
+	let filter = match EnvFilter::try_from_default_env() {
+		Ok(filter) => filter,
+		Err(_) => EnvFilter::try_new(&config.filter)
+			.map_err(|e| format!("Invalid tracing filter {}: {e}", config.filter))?,
+	};
+	let output = match config.format {
+		LogFormat::Pretty => fmt::layer().boxed(),
+		LogFormat::Json => fmt::layer().json().boxed(),
+	};
+	// the database spans are always timed for the metrics, whatever is logged
+	let queries = Targets::new().with_target("my_twitter::database", Level::DEBUG);
+
+	tracing_subscriber::registry()
+		.with(output.with_filter(filter))
+		.with(QueryTimer.with_filter(queries))
+		.try_init()
+		.map_err(|e| format!("Could not set up tracing: {e}"))
+}
+
+// the span everything done for a request is logged in
+struct RequestSpan {
+	span: Span,
+	id: String,
+	start: Instant,
+}
+impl RequestSpan {
+	fn new(req: &Request<'_>) -> Self {
+		// a proxy in front can pass its id along, so both logs can be matched up
+		let id = match req.headers().get_one(REQUEST_ID_HEADER) {
+			Some(id) if !id.is_empty() && id.len() <= 64 => id.to_owned(),
+			_ => format!("{:016x}", rand::thread_rng().gen::<u64>()),
+		};
+		let span = info_span!(
+			"request",
+			id = %id,
+			method = %req.method(),
+			uri = %req.uri(),
+			account = field::Empty,
+		);
+		if let Some(cookie) = req.cookies().get_private("id") {
+			span.record("account", cookie.value());
+		}
+
+		Self {
+			span,
+			id,
+			start: Instant::now(),
+		}
+	}
+}
+
+/// The span of the request, for the work done for it outside of its handler
+pub fn span(req: &Request<'_>) -> Span {
+	req.local_cache(|| RequestSpan::new(req)).span.clone()
+}
+
+/// Gives every request an id and logs how it went
+pub struct RequestTracing;
+
+#[rocket::async_trait]
+impl Fairing for RequestTracing {
+	fn info(&self) -> Info {
+		Info {
+			name: "Request tracing",
+			kind: Kind::Request | Kind::Response,
+		}
+	}
+
+	async fn on_request(&self, req: &mut Request<'_>, _: &mut Data<'_>) {
+		let request = RequestSpan::new(req);
+		req.local_cache(|| request);
+	}
+
+	async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+		let RequestSpan { span, id, start } = req.local_cache(|| RequestSpan::new(req));
+		res.set_header(Header::new(REQUEST_ID_HEADER, id.clone()));
+		span.in_scope(|| {
+			info!(
+				status = res.status().code,
+				duration_ms = start.elapsed().as_secs_f64() * 1000.0,
+				"finished"
+			)
+		});
+	}
+}
+
+/// Runs the handlers of the routes inside the span of their request, so their queries are logged in it
+pub fn traced(routes: Vec<Route>) -> Vec<Route> {
+	routes
+		.into_iter()
+		.map(|mut route| {
+			route.handler = Box::new(Traced(route.handler));
+			route
+		})
+		.collect()
+}
+
+#[derive(Clone)]
+struct Traced(Box<dyn Handler>);
+
+#[rocket::async_trait]
+impl Handler for Traced {
+	async fn handle<'r>(&self, req: &'r Request<'_>, data: Data<'r>) -> route::Outcome<'r> {
+		self.0.handle(req, data).instrument(span(req)).await
+	}
+}
@@ -0,0 +1,1332 @@
+use std::time::Duration;
+
+use rocket::{
+	http::{Header, Status},
+	local::asynchronous::{Client, LocalResponse},
+	tokio::{io::AsyncReadExt, time},
+};
+use sqlx::postgres::PgPoolOptions;
+
+use super::{location, store, test_rocket, Browser, TestApp, PASSWORD};
+use crate::{
+	admin::Command,
+	crypto::totp,
+	database::{
+		account::Account,
+		migrate,
+		storage::{PgStorage, Store},
+		types::Role,
+	},
+	mailer, RegistrationMode,
+};
+
+// every route that needs a session, with a form that would pass validation
+const SIGNED_IN_ROUTES: &[(&str, &str, &str)] = &[
+	("DELETE", "/post/1", ""),
+	("POST", "/like_post/1", ""),
+	("GET", "/profile", ""),
+	("POST", "/user/alice/follow", ""),
+	("POST", "/user/alice/unfollow", ""),
+	("GET", "/settings", ""),
+	("GET", "/settings/2fa", ""),
+	("POST", "/settings/2fa/enable", "code=123456"),
+	("POST", "/settings/2fa/disable", "password=x&code=123456"),
+	("POST", "/settings/handle", "handle=mallory"),
+	("POST", "/settings/email", "email=mallory%40example.com"),
+	("POST", "/settings/email/verify", ""),
+	(
+		"POST",
+		"/settings/password",
+		"current=x&password=Other-pass1&confirm=Other-pass1",
+	),
+	("POST", "/settings/dm_permission", "dm_permission=nobody"),
+	("GET", "/messages", ""),
+	("POST", "/messages", "handles=alice"),
+	("GET", "/messages/1", ""),
+	("POST", "/messages/1", "body=hi"),
+	("POST", "/messages/1/delete/1", ""),
+	("GET", "/notifications", ""),
+	("GET", "/events", ""),
+	("POST", "/user/alice/mute", ""),
+	("POST", "/user/alice/unmute", ""),
+	("POST", "/post/1/mute", ""),
+	("POST", "/post/1/unmute", ""),
+	("GET", "/settings/mutes", ""),
+	("POST", "/settings/mutes/words", "phrase=hi"),
+	("POST", "/settings/mutes/words/1/delete", ""),
+	("GET", "/invites", ""),
+	("POST", "/invites", "max_uses=1"),
+	("POST", "/invites/abc/delete", ""),
+	("GET", "/post/1/report", ""),
+	("POST", "/post/1/report", "reason=spam"),
+	("GET", "/user/alice/report", ""),
+	("POST", "/user/alice/report", "reason=spam"),
+];
+
+const MODERATOR_ROUTES: &[(&str, &str, &str)] = &[
+	("GET", "/moderation", ""),
+	("POST", "/moderation/reports/1", "action=dismiss"),
+	("POST", "/moderation/user/alice/lift", ""),
+	// alice's post, not the one sending it
+	("DELETE", "/post/1", ""),
+];
+
+const ADMIN_ROUTES: &[(&str, &str, &str)] = &[
+	("GET", "/admin", ""),
+	("GET", "/admin?q=alice", ""),
+	("POST", "/admin/user/alice/role", "role=admin"),
+	("GET", "/admin/pending", ""),
+	("POST", "/admin/pending/alice/approve", ""),
+	("POST", "/admin/pending/alice/reject", ""),
+];
+
+async fn send(browser: &Browser, method: &str, uri: &str, form: &str) -> Status {
+	match method {
+		"GET" => browser.get(uri).await.status(),
+		"POST" => browser.post(uri, form).await.status(),
+		"DELETE" => browser.delete(uri).await.status(),
+		_ => unreachable!("no routes use {method}"),
+	}
+}
+
+/// Registers and stays signed in
+async fn signed_up(app: &mut TestApp, handle: &str) -> Browser {
+	let browser = app.browser().await;
+	let res = browser.register(handle, PASSWORD).await;
+	assert_eq!(location(&res), Some("/"), "registering {handle}");
+	drop(res);
+	browser
+}
+
+async fn account(app: &TestApp, handle: &str) -> Account {
+	Account::find_by_handle(app.pool(), handle, None::<i32>)
+		.await
+		.unwrap()
+		.unwrap()
+}
+
+async fn followers(browser: &Browser, handle: &str) -> u64 {
+	let account = store(&browser.client)
+		.find_account_by_handle(handle, None)
+		.await
+		.unwrap()
+		.unwrap();
+	account.followers.0
+}
+
+#[rocket::async_test]
+async fn registering_and_signing_in() {
+	let mut app = TestApp::new().await;
+	let browser = app.browser().await;
+
+	let res = browser.register("alice", "password").await;
+	assert_eq!(location(&res), Some("/register?err=password_weak"));
+	assert!(!browser.signed_in());
+
+	let res = browser.register("alice", PASSWORD).await;
+	assert_eq!(res.status(), Status::SeeOther);
+	assert_eq!(location(&res), Some("/"));
+	assert!(browser.signed_in());
+	let res = browser.get("/profile").await;
+	assert_eq!(location(&res), Some("/user/alice"));
+
+	let res = browser.post("/signout", "").await;
+	assert_eq!(location(&res), Some("/"));
+	assert!(!browser.signed_in());
+	assert_eq!(
+		browser.get("/settings").await.status(),
+		Status::Unauthorized
+	);
+
+	// the handle is taken regardless of case
+	let other = app.browser().await;
+	let res = other.register("ALICE", PASSWORD).await;
+	assert_eq!(location(&res), Some("/register?err=handle_taken"));
+
+	let res = browser.login("alice", "Wrong-pass1").await;
+	assert_eq!(location(&res), Some("/login?err=credentials"));
+	assert!(!browser.signed_in());
+
+	let res = browser.login("alice", PASSWORD).await;
+	assert_eq!(location(&res), Some("/"));
+	assert_eq!(browser.get("/settings").await.status(), Status::Ok);
+}
+
+#[rocket::async_test]
+async fn posting_replying_and_liking() {
+	let mut app = TestApp::new().await;
+	let alice = signed_up(&mut app, "alice").await;
+	let bob = signed_up(&mut app, "bob").await;
+
+	let res = alice.post("/create_post", "body=hello+world").await;
+	assert_eq!(location(&res), Some("/"));
+	let posts = store(&alice.client)
+		.get_recent_posts(10, None)
+		.await
+		.unwrap();
+	assert_eq!(posts.len(), 1);
+	let post = &posts[0];
+	assert_eq!(&*post.body, "hello world");
+	let feed = bob.get("/").await.into_string().await.unwrap();
+	assert!(feed.contains("hello world"));
+
+	let res = bob
+		.post(
+			"/create_post",
+			&format!("body=hi+alice&parent_id={}", post.id.0),
+		)
+		.await;
+	assert_eq!(location(&res), Some(&*format!("/post/{}", post.id.0)));
+	let page = alice
+		.get(&format!("/post/{}", post.id.0))
+		.await
+		.into_string()
+		.await
+		.unwrap();
+	assert!(page.contains("hi alice"));
+
+	let like = format!("/like_post/{}", post.id.0);
+	assert_eq!(bob.post(&like, "").await.status(), Status::Ok);
+	let likes = alice
+		.get(&format!("/post/{}/likes", post.id.0))
+		.await
+		.into_string()
+		.await
+		.unwrap();
+	assert!(likes.contains("bob"));
+	let bob_likes = alice
+		.get("/user/bob/likes")
+		.await
+		.into_string()
+		.await
+		.unwrap();
+	assert!(bob_likes.contains("hello world"));
+
+	// liking again takes it back, your own posts can't be liked
+	assert_eq!(bob.post(&like, "").await.status(), Status::Ok);
+	let post = store(&alice.client)
+		.find_post_by_id(post.id, None)
+		.await
+		.unwrap()
+		.unwrap();
+	assert_eq!(post.votes.0, 0);
+	assert_eq!(alice.post(&like, "").await.status(), Status::Forbidden);
+
+	let res = alice.post("/create_post", "").await;
+	assert_eq!(res.status(), Status::BadRequest);
+	assert_eq!(
+		alice.post("/like_post/999999", "").await.status(),
+		Status::NotFound
+	);
+}
+
+#[rocket::async_test]
+async fn following_and_unfollowing() {
+	let mut app = TestApp::new().await;
+	signed_up(&mut app, "alice").await;
+	let bob = signed_up(&mut app, "bob").await;
+
+	let res = bob.post("/user/alice/follow", "").await;
+	assert_eq!(location(&res), Some("/user/alice"));
+	assert_eq!(followers(&bob, "alice").await, 1);
+
+	let res = bob.post("/user/alice/unfollow", "").await;
+	assert_eq!(location(&res), Some("/user/alice"));
+	assert_eq!(followers(&bob, "alice").await, 0);
+
+	assert_eq!(
+		bob.post("/user/bob/follow", "").await.status(),
+		Status::Forbidden
+	);
+	assert_eq!(
+		bob.post("/user/nobody/follow", "").await.status(),
+		Status::NotFound
+	);
+}
+
+#[rocket::async_test]
+async fn deleting_posts() {
+	let mut app = TestApp::new().await;
+	let alice = signed_up(&mut app, "alice").await;
+	let bob = signed_up(&mut app, "bob").await;
+	let carol = signed_up(&mut app, "carol").await;
+
+	alice.post("/create_post", "body=first").await;
+	alice.post("/create_post", "body=second").await;
+	let posts = store(&alice.client)
+		.get_recent_posts(10, None)
+		.await
+		.unwrap();
+	let (second, first) = (posts[0].id, posts[1].id);
+
+	let res = bob.delete(&format!("/post/{}", first.0)).await;
+	assert_eq!(res.status(), Status::Forbidden);
+
+	let res = alice.delete(&format!("/post/{}", first.0)).await;
+	assert_eq!(location(&res), Some("/"));
+	let res = alice.get(&format!("/post/{}", first.0)).await;
+	assert_eq!(res.status(), Status::NotFound);
+	let res = alice.delete(&format!("/post/{}", first.0)).await;
+	assert_eq!(res.status(), Status::NotFound);
+
+	// moderators can delete anyone's post
+	let carol_account = store(&carol.client)
+		.find_account_by_handle("carol", None)
+		.await
+		.unwrap()
+		.unwrap();
+	carol_account
+		.set_role(app.pool(), Role::Moderator)
+		.await
+		.unwrap();
+	let res = carol.delete(&format!("/post/{}", second.0)).await;
+	assert_eq!(res.status(), Status::SeeOther);
+	assert!(store(&carol.client)
+		.get_recent_posts(10, None)
+		.await
+		.unwrap()
+		.is_empty());
+	let log = carol.get("/moderation").await.into_string().await.unwrap();
+	assert!(log.contains("second"));
+}
+
+#[rocket::async_test]
+async fn deleting_accounts() {
+	let mut app = TestApp::new().await;
+	let alice = signed_up(&mut app, "alice").await;
+	let bob = signed_up(&mut app, "bob").await;
+	alice.post("/create_post", "body=goodbye").await;
+	bob.post("/user/alice/follow", "").await;
+
+	let res = alice.post("/delete_account", "").await;
+	assert_eq!(location(&res), Some("/"));
+	assert!(!alice.signed_in());
+
+	assert_eq!(bob.get("/user/alice").await.status(), Status::NotFound);
+	assert!(store(&bob.client)
+		.get_recent_posts(10, None)
+		.await
+		.unwrap()
+		.is_empty());
+	let res = alice.login("alice", PASSWORD).await;
+	assert_eq!(location(&res), Some("/login?err=credentials"));
+
+	// the handle is free again
+	let again = app.browser().await;
+	let res = again.register("alice", PASSWORD).await;
+	assert_eq!(location(&res), Some("/"));
+}
+
+#[rocket::async_test]
+async fn signed_out_visitors_are_turned_away() {
+	let mut app = TestApp::new().await;
+	let alice = signed_up(&mut app, "alice").await;
+	alice.post("/create_post", "body=hi").await;
+
+	// a page sets the csrf token, so only the missing session stops these
+	let visitor = app.browser().await;
+	visitor.get("/login").await;
+	for &(method, uri, form) in SIGNED_IN_ROUTES
+		.iter()
+		.chain(MODERATOR_ROUTES)
+		.chain(ADMIN_ROUTES)
+	{
+		assert_eq!(
+			send(&visitor, method, uri, form).await,
+			Status::Unauthorized,
+			"{method} {uri}"
+		);
+	}
+	assert_eq!(
+		visitor.post("/create_post", "body=hi").await.status(),
+		Status::Unauthorized
+	);
+
+	for uri in [
+		"/",
+		"/post/1",
+		"/post/1/likes",
+		"/user/alice",
+		"/user/alice/likes",
+	] {
+		assert_eq!(visitor.get(uri).await.status(), Status::Ok, "GET {uri}");
+	}
+}
+
+#[rocket::async_test]
+async fn users_cant_moderate_or_administrate() {
+	let mut app = TestApp::new().await;
+	let alice = signed_up(&mut app, "alice").await;
+	alice.post("/create_post", "body=hi").await;
+	let bob = signed_up(&mut app, "bob").await;
+
+	for &(method, uri, form) in MODERATOR_ROUTES.iter().chain(ADMIN_ROUTES) {
+		assert_eq!(
+			send(&bob, method, uri, form).await,
+			Status::Forbidden,
+			"{method} {uri}"
+		);
+	}
+
+	// moderators still aren't admins
+	let account = store(&bob.client)
+		.find_account_by_handle("bob", None)
+		.await
+		.unwrap()
+		.unwrap();
+	account.set_role(app.pool(), Role::Moderator).await.unwrap();
+	assert_eq!(bob.get("/moderation").await.status(), Status::Ok);
+	for &(method, uri, form) in ADMIN_ROUTES {
+		assert_eq!(
+			send(&bob, method, uri, form).await,
+			Status::Forbidden,
+			"{method} {uri}"
+		);
+	}
+}
+
+#[rocket::async_test]
+async fn moderators_only_restrict_accounts_below_them() {
+	let mut app = TestApp::new().await;
+	let alice = signed_up(&mut app, "alice").await;
+	let bob = signed_up(&mut app, "bob").await;
+	let carol = signed_up(&mut app, "carol").await;
+	let dave = signed_up(&mut app, "dave").await;
+	let admin = account(&app, "alice").await;
+	admin.set_role(app.pool(), Role::Admin).await.unwrap();
+	let moderator = account(&app, "carol").await;
+	moderator
+		.set_role(app.pool(), Role::Moderator)
+		.await
+		.unwrap();
+	for handle in ["alice", "carol", "bob"] {
+		let res = dave
+			.post(&format!("/user/{handle}/report"), "reason=spam")
+			.await;
+		assert_eq!(res.status(), Status::SeeOther, "reporting {handle}");
+	}
+
+	let res = carol.post("/moderation/reports/1", "action=suspend").await;
+	assert_eq!(res.status(), Status::Forbidden);
+	let res = carol.post("/moderation/reports/2", "action=limit").await;
+	assert_eq!(res.status(), Status::Forbidden);
+	let res = carol.post("/moderation/reports/3", "action=suspend").await;
+	assert_eq!(location(&res), Some("/moderation"));
+	drop(res);
+
+	alice.post("/signout", "").await;
+	alice.login("alice", PASSWORD).await;
+	assert!(alice.signed_in());
+	bob.post("/signout", "").await;
+	bob.login("bob", PASSWORD).await;
+	assert!(!bob.signed_in());
+}
+
+#[rocket::async_test]
+async fn lifting_restrictions_needs_a_higher_role() {
+	let mut app = TestApp::new().await;
+	let alice = signed_up(&mut app, "alice").await;
+	let bob = signed_up(&mut app, "bob").await;
+	let carol = signed_up(&mut app, "carol").await;
+	let dave = signed_up(&mut app, "dave").await;
+	let admin = account(&app, "alice").await;
+	admin.set_role(app.pool(), Role::Admin).await.unwrap();
+	for handle in ["bob", "carol"] {
+		let moderator = account(&app, handle).await;
+		moderator
+			.set_role(app.pool(), Role::Moderator)
+			.await
+			.unwrap();
+	}
+	dave.post("/user/carol/report", "reason=spam").await;
+	let res = alice.post("/moderation/reports/1", "action=suspend").await;
+	assert_eq!(location(&res), Some("/moderation"));
+	drop(res);
+
+	// neither another moderator nor the suspended one can undo it
+	let res = bob.post("/moderation/user/carol/lift", "").await;
+	assert_eq!(res.status(), Status::Forbidden);
+	let res = carol.post("/moderation/user/carol/lift", "").await;
+	assert_eq!(res.status(), Status::Forbidden);
+	carol.post("/signout", "").await;
+	carol.login("carol", PASSWORD).await;
+	assert!(!carol.signed_in());
+
+	let res = alice.post("/moderation/user/carol/lift", "").await;
+	assert_eq!(location(&res), Some("/user/carol"));
+	drop(res);
+	carol.login("carol", PASSWORD).await;
+	assert!(carol.signed_in());
+}
+
+#[rocket::async_test]
+async fn the_csrf_token_can_come_in_the_form() {
+	let mut app = TestApp::new().await;
+	let alice = signed_up(&mut app, "alice").await;
+	let token = alice.csrf_token();
+	let submit = |form: String| {
+		alice
+			.client
+			.post("/create_post")
+			.header(rocket::http::ContentType::Form)
+			.body(form)
+			.dispatch()
+	};
+
+	// the way the pages send it, as a hidden field
+	let res = submit(format!("csrf_token={token}&body=first")).await;
+	assert_eq!(location(&res), Some("/"));
+	drop(res);
+
+	// only the start of the body is read for it, which the refusal points out
+	let padding = "x".repeat(512);
+	let res = submit(format!("padding={padding}&body=second&csrf_token={token}")).await;
+	assert_eq!(res.status(), Status::Forbidden);
+	let page = res.into_string().await.unwrap();
+	assert!(page.contains("The csrf_token field has to come before the other fields"));
+	// a short form with the wrong token just gets the plain refusal
+	let res = submit("body=third&csrf_token=wrong".to_string()).await;
+	assert_eq!(res.status(), Status::Forbidden);
+	let page = res.into_string().await.unwrap();
+	assert!(!page.contains("csrf_token field"));
+
+	let posts = store(&alice.client)
+		.get_recent_posts(10, None)
+		.await
+		.unwrap();
+	assert_eq!(posts.len(), 1);
+	assert_eq!(&*posts[0].body, "first");
+}
+
+#[rocket::async_test]
+async fn requests_without_the_csrf_token_are_rejected() {
+	let mut app = TestApp::new().await;
+	let alice = signed_up(&mut app, "alice").await;
+
+	let changes = SIGNED_IN_ROUTES
+		.iter()
+		.chain(MODERATOR_ROUTES)
+		.chain(ADMIN_ROUTES)
+		.filter(|(method, ..)| *method != "GET");
+	for &(method, uri, form) in changes {
+		let req = match method {
+			"POST" => alice.client.post(uri),
+			_ => alice.client.delete(uri),
+		};
+		let res = req
+			.header(rocket::http::ContentType::Form)
+			.body(form)
+			.dispatch()
+			.await;
+		assert_eq!(res.status(), Status::Forbidden, "{method} {uri}");
+	}
+	for uri in ["/create_post", "/signout", "/delete_account"] {
+		let res = alice.client.post(uri).dispatch().await;
+		assert_eq!(res.status(), Status::Forbidden, "POST {uri}");
+	}
+	assert!(alice.signed_in());
+}
+
+#[rocket::async_test]
+async fn ready_once_migrated() {
+	let mut app = TestApp::new().await;
+	let visitor = app.browser().await;
+
+	let res = visitor.get("/readyz").await;
+	assert_eq!(res.status(), Status::Ok);
+	let body: serde_json::Value = res.into_json().await.unwrap();
+	assert_eq!(body["status"], "ready");
+
+	// a migration missing from the database holds the traffic back
+	sqlx::query(
+		"DELETE FROM _sqlx_migrations WHERE version = (SELECT max(version) FROM _sqlx_migrations)",
+	)
+	.execute(app.pool())
+	.await
+	.unwrap();
+	let res = visitor.get("/readyz").await;
+	assert_eq!(res.status(), Status::ServiceUnavailable);
+	let body: serde_json::Value = res.into_json().await.unwrap();
+	assert_eq!(body["database"]["ok"], true);
+	assert_eq!(body["migrations"]["ok"], false);
+	assert_eq!(body["migrations"]["error"], "pending migrations");
+}
+
+#[rocket::async_test]
+async fn readiness_needs_the_database() {
+	// nothing answers at the address of this pool
+	let pool = PgPoolOptions::new()
+		.acquire_timeout(Duration::from_millis(100))
+		.connect_lazy("postgres://127.0.0.1:1/unreachable")
+		.expect("valid url");
+	let store: Store = Box::new(PgStorage::new(pool.clone()));
+	let client = Client::tracked(test_rocket(pool, store, RegistrationMode::Open))
+		.await
+		.expect("valid rocket");
+
+	let res = client.get("/healthz").dispatch().await;
+	assert_eq!(res.status(), Status::Ok);
+	assert_eq!(res.into_string().await.unwrap(), r#"{"status":"ok"}"#);
+
+	let res = client.get("/readyz").dispatch().await;
+	assert_eq!(res.status(), Status::ServiceUnavailable);
+	let body: serde_json::Value = res.into_json().await.unwrap();
+	assert_eq!(body["status"], "unavailable");
+	assert_eq!(body["database"]["ok"], false);
+	assert_eq!(body["database"]["error"], "unavailable");
+	assert_eq!(body["templates"]["ok"], true);
+	assert_eq!(body["migrations"]["ok"], false);
+}
+
+async fn command(app: &TestApp, args: &[&str]) -> Result<String, String> {
+	let command = Command::parse(args, || Ok(PASSWORD.to_string()))?;
+	command.run(app.pool()).await
+}
+
+#[rocket::async_test]
+async fn administrating_from_the_command_line() {
+	let mut app = TestApp::new().await;
+
+	let out = command(&app, &["create-account", "carol", "moderator"]).await;
+	assert!(out.unwrap().starts_with("Created @carol (moderator)"));
+	let carol = app.browser().await;
+	carol.login("carol", PASSWORD).await;
+	assert_eq!(carol.get("/moderation").await.status(), Status::Ok);
+	let taken = command(&app, &["create-account", "Carol"]).await;
+	assert_eq!(
+		taken.unwrap_err(),
+		"An account with this handle already exists"
+	);
+
+	let alice = signed_up(&mut app, "alice").await;
+	alice.post("/create_post", "body=spam").await;
+	let post = store(&alice.client)
+		.get_recent_posts(1, None)
+		.await
+		.unwrap()
+		.remove(0);
+	let id = post.id.0.to_string();
+	command(&app, &["delete-post", &id]).await.unwrap();
+	let res = alice.get(&format!("/post/{id}")).await;
+	assert_eq!(res.status(), Status::NotFound);
+	drop(res);
+	let log = carol.get("/moderation").await.into_string().await.unwrap();
+	assert!(log.contains("spam") && log.contains("command line"));
+	assert!(log.contains("user -&gt; moderator"), "{log}");
+
+	command(&app, &["suspend", "alice", "3", "too", "much", "spam"])
+		.await
+		.unwrap();
+	alice.post("/signout", "").await;
+	alice.login("alice", PASSWORD).await;
+	assert!(!alice.signed_in());
+	command(&app, &["unsuspend", "alice"]).await.unwrap();
+	alice.login("alice", PASSWORD).await;
+	assert!(alice.signed_in());
+
+	// a new password signs the account out everywhere
+	command(&app, &["reset-password", "nobody"])
+		.await
+		.unwrap_err();
+	command(&app, &["reset-password", "alice"]).await.unwrap();
+	let res = alice.get("/settings").await;
+	assert_eq!(location(&res), Some("/login?err=session"));
+	drop(res);
+
+	command(&app, &["set-role", "alice", "admin"])
+		.await
+		.unwrap();
+	alice.login("alice", PASSWORD).await;
+	assert_eq!(alice.get("/admin").await.status(), Status::Ok);
+
+	let stats = command(&app, &["stats"]).await.unwrap();
+	assert!(stats.contains("accounts      2"), "{stats}");
+	assert!(stats.contains("admins      1"), "{stats}");
+	assert!(stats.contains("posts         0"), "{stats}");
+	command(&app, &["prune-attempts"]).await.unwrap();
+	command(&app, &["migrate", "status"]).await.unwrap();
+
+	assert!(command(&app, &["set-role", "alice", "owner"])
+		.await
+		.is_err());
+	assert!(command(&app, &["suspend", "alice", "soon", "x"])
+		.await
+		.is_err());
+	assert!(command(&app, &["nothing"]).await.is_err());
+}
+
+// everything the seed made, in the order it was made
+async fn seeded(app: &TestApp) -> Vec<(String, String, Option<i64>, i64)> {
+	sqlx::query_as(
+		"SELECT a.handle, p.body, p.parent_id, (SELECT COUNT(*) FROM vote WHERE post_id = p.id)
+		FROM post p JOIN account a ON a.id = p.author_id ORDER BY p.id",
+	)
+	.fetch_all(app.pool())
+	.await
+	.unwrap()
+}
+
+#[rocket::async_test]
+async fn seeding_is_deterministic() {
+	let args = [
+		"seed",
+		"--accounts",
+		"8",
+		"--follows",
+		"20",
+		"--posts",
+		"10",
+		"--replies",
+		"15",
+		"--likes",
+		"40",
+		"--seed",
+		"7",
+	];
+	let first = TestApp::new().await;
+	let out = command(&first, &args).await.unwrap();
+	assert!(out.starts_with("Seeded 8 accounts, 20 follows, 10 posts, 15 replies and 40 likes"));
+	let second = TestApp::new().await;
+	command(&second, &args).await.unwrap();
+
+	let posts = seeded(&first).await;
+	assert_eq!(posts.len(), 25);
+	assert_eq!(posts, seeded(&second).await);
+	assert!(
+		posts
+			.iter()
+			.any(|(.., parent, _)| parent.is_some_and(|p| p > 10)),
+		"some replies are to replies"
+	);
+
+	let self_likes: i64 = sqlx::query_scalar(
+		"SELECT COUNT(*) FROM vote v JOIN post p ON p.id = v.post_id WHERE p.author_id = v.voter_id",
+	)
+	.fetch_one(first.pool())
+	.await
+	.unwrap();
+	assert_eq!(self_likes, 0);
+
+	// the same handles again
+	assert!(command(&first, &args).await.is_err());
+	assert!(command(&first, &["seed", "--accounts"]).await.is_err());
+}
+
+#[rocket::async_test]
+async fn only_verified_emails_belong_to_an_account() {
+	let mut app = TestApp::new().await;
+	signed_up(&mut app, "alice").await;
+	signed_up(&mut app, "bob").await;
+	let account = |handle| Account::find_by_handle(app.pool(), handle, None::<i32>);
+	let alice = account("alice").await.unwrap().unwrap();
+	let bob = account("bob").await.unwrap().unwrap();
+
+	// alice typing in bob's address neither gets her his resets nor keeps him from adding it
+	assert!(alice
+		.set_email(app.pool(), Some("bob@example.com"))
+		.await
+		.unwrap());
+	let found = Account::find_by_verified_email(app.pool(), "bob@example.com").await;
+	assert!(found.unwrap().is_none());
+	assert!(bob
+		.set_email(app.pool(), Some("Bob@example.com"))
+		.await
+		.unwrap());
+
+	let bob = account("bob").await.unwrap().unwrap();
+	let token = bob.email_verification_token().unwrap();
+	assert!(Account::verify_email(app.pool(), &token).await.unwrap());
+	let found = Account::find_by_verified_email(app.pool(), "bob@example.com").await;
+	assert_eq!(found.unwrap().unwrap().handle, "bob");
+	assert_eq!(account("alice").await.unwrap().unwrap().email, None);
+
+	assert!(!alice
+		.set_email(app.pool(), Some("bob@example.com"))
+		.await
+		.unwrap());
+}
+
+#[rocket::async_test]
+async fn a_made_up_address_header_doesnt_get_around_the_throttle() {
+	let mut app = TestApp::new().await;
+	let browser = app.browser().await;
+	browser.get("/register").await;
+
+	let mut throttled = false;
+	for i in 0..5 {
+		let res = browser
+			.client
+			.post("/register")
+			.remote("10.0.0.99:8000".parse().unwrap())
+			.header(rocket::http::ContentType::Form)
+			.header(Header::new("X-CSRF-Token", browser.csrf_token()))
+			.header(Header::new("X-Real-IP", format!("192.0.2.{i}")))
+			.body(format!("username=spammer{i}&password={PASSWORD}"))
+			.dispatch()
+			.await;
+		throttled |= location(&res) == Some("/register?err=throttled");
+	}
+	assert!(throttled);
+}
+
+#[rocket::async_test]
+async fn init_sql_databases_are_brought_up_to_date() {
+	let mut app = TestApp::new().await;
+	signed_up(&mut app, "alice").await;
+
+	// back to the schema of the old init.sql, with the migrations table gone as if it had never been there
+	loop {
+		let status = migrate::status(app.pool()).await.unwrap();
+		if status.iter().filter(|m| m.applied).count() == 1 {
+			break;
+		}
+		migrate::revert_last(app.pool()).await.unwrap();
+	}
+	sqlx::query("DROP TABLE _sqlx_migrations")
+		.execute(app.pool())
+		.await
+		.unwrap();
+
+	migrate::run(app.pool()).await.unwrap();
+	assert!(migrate::pending(app.pool()).await.unwrap().is_empty());
+	let alice = Account::find_by_handle(app.pool(), "alice", None::<i32>)
+		.await
+		.unwrap();
+	assert!(alice.is_some());
+
+	// only some of the tables isn't a database init.sql made
+	sqlx::query("DROP TABLE _sqlx_migrations, vote")
+		.execute(app.pool())
+		.await
+		.unwrap();
+	let e = migrate::run(app.pool()).await.unwrap_err();
+	assert!(e.to_string().contains("no vote"), "{e}");
+}
+
+async fn page(browser: &Browser, uri: &str) -> String {
+	browser
+		.get(uri)
+		.await
+		.into_string()
+		.await
+		.unwrap_or_default()
+}
+
+async fn post_id(app: &TestApp, body: &str) -> i64 {
+	sqlx::query_scalar("SELECT id FROM post WHERE body = $1")
+		.bind(body)
+		.fetch_one(app.pool())
+		.await
+		.unwrap()
+}
+
+/// The path of the link to the page in the last mail sent to the address
+fn mailed_link(to: &str, page: &str) -> String {
+	let mails = mailer::sent_to(to);
+	let body = &mails
+		.last()
+		.unwrap_or_else(|| panic!("no mail to {to}"))
+		.body;
+	let base_url = mailer::base_url();
+	let start = body
+		.find(&format!("{base_url}{page}"))
+		.unwrap_or_else(|| panic!("no link to {page} in {body}"));
+	let link = body[start + base_url.len()..].split_whitespace().next();
+	link.unwrap().to_string()
+}
+
+#[rocket::async_test]
+async fn messaging_follows_permissions_and_blocks() {
+	let mut app = TestApp::new().await;
+	let alice = signed_up(&mut app, "alice").await;
+	let bob = signed_up(&mut app, "bob").await;
+	let carol = signed_up(&mut app, "carol").await;
+
+	let res = alice.post("/messages", "handles=%40bob").await;
+	let conversation = location(&res).unwrap().to_string();
+	drop(res);
+	let res = alice.post(&conversation, "body=hi+bob").await;
+	assert_eq!(location(&res), Some(&*conversation));
+	drop(res);
+	assert!(page(&bob, &conversation).await.contains("hi bob"));
+	// nobody else can read or write in it
+	assert_eq!(carol.get(&conversation).await.status(), Status::NotFound);
+	let res = carol.post(&conversation, "body=hello").await;
+	assert_eq!(res.status(), Status::NotFound);
+
+	// following only lets in the accounts bob follows, also in conversations that already exist
+	bob.post("/settings/dm_permission", "dm_permission=following")
+		.await;
+	let res = carol.post("/messages", "handles=bob").await;
+	assert_eq!(location(&res), Some("/messages?err=permission"));
+	drop(res);
+	let res = alice.post(&conversation, "body=still+there").await;
+	assert_eq!(res.status(), Status::Forbidden);
+	bob.post("/user/alice/follow", "").await;
+	let res = alice.post(&conversation, "body=still+there").await;
+	assert_eq!(location(&res), Some(&*conversation));
+	drop(res);
+
+	bob.post("/settings/dm_permission", "dm_permission=nobody")
+		.await;
+	let res = alice.post(&conversation, "body=and+now").await;
+	assert_eq!(res.status(), Status::Forbidden);
+	bob.post("/settings/dm_permission", "dm_permission=everyone")
+		.await;
+
+	// a block stops messages both ways, whatever the permission
+	let (alice_id, bob_id) = (
+		account(&app, "alice").await.id,
+		account(&app, "bob").await.id,
+	);
+	sqlx::query("INSERT INTO block (user_id, blocked_id) VALUES ($1, $2)")
+		.bind(i32::from(bob_id))
+		.bind(i32::from(alice_id))
+		.execute(app.pool())
+		.await
+		.unwrap();
+	let res = alice.post(&conversation, "body=hello%3F").await;
+	assert_eq!(res.status(), Status::Forbidden);
+	let res = bob.post(&conversation, "body=bye").await;
+	assert_eq!(res.status(), Status::Forbidden);
+	let res = alice.post("/messages", "handles=bob").await;
+	assert_eq!(location(&res), Some("/messages?err=permission"));
+	drop(res);
+
+	// deleting a message only hides it from whoever deleted it
+	let message: i64 = sqlx::query_scalar("SELECT id FROM message WHERE body = 'hi bob'")
+		.fetch_one(app.pool())
+		.await
+		.unwrap();
+	let res = bob
+		.post(&format!("{conversation}/delete/{message}"), "")
+		.await;
+	assert_eq!(location(&res), Some(&*conversation));
+	drop(res);
+	assert!(!page(&bob, &conversation).await.contains("hi bob"));
+	assert!(page(&alice, &conversation).await.contains("hi bob"));
+}
+
+/// Reads the event stream until an event with the name and data starting with the given comes up
+async fn read_event(events: &mut LocalResponse<'_>, read: &mut String, event: &str, data: &str) {
+	let (event, data) = (format!("event:{event}"), format!("data:{data}"));
+	let found = |read: &str| {
+		read.split("\n\n").any(|e| {
+			let mut lines = e.lines();
+			lines.clone().any(|l| l == event) && lines.any(|l| l.starts_with(&data))
+		})
+	};
+	let mut buf = [0; 4096];
+	let reading = async {
+		while !found(read) {
+			match events.read(&mut buf).await {
+				Ok(0) | Err(_) => break,
+				Ok(n) => read.push_str(&String::from_utf8_lossy(&buf[..n])),
+			}
+		}
+	};
+	time::timeout(Duration::from_secs(10), reading).await.ok();
+	assert!(found(read), "no {event} with {data} in {read:?}");
+}
+
+#[rocket::async_test]
+async fn events_stream_posts_likes_and_notifications() {
+	let mut app = TestApp::new().await;
+	signed_up(&mut app, "alice").await;
+	let bob = signed_up(&mut app, "bob").await;
+	let alice = app.live_browser().await;
+	alice.login("alice", PASSWORD).await;
+
+	// the listener has to be in place before anything happens
+	let listening = async {
+		loop {
+			let listening: bool = sqlx::query_scalar(
+				"SELECT EXISTS(SELECT * FROM pg_stat_activity
+				WHERE datname = current_database() AND query LIKE 'LISTEN%')",
+			)
+			.fetch_one(app.pool())
+			.await
+			.unwrap();
+			if listening {
+				break;
+			}
+			time::sleep(Duration::from_millis(50)).await;
+		}
+	};
+	time::timeout(Duration::from_secs(10), listening)
+		.await
+		.expect("the event listener started");
+
+	let mut events = alice.get("/events").await;
+	let mut read = String::new();
+	read_event(&mut events, &mut read, "notifications", "0").await;
+
+	bob.post("/create_post", "body=fresh+from+bob").await;
+	let post = post_id(&app, "fresh from bob").await;
+	let live = format!("{{\"id\":{post},\"parent_id\":null,\"html\":");
+	read_event(&mut events, &mut read, "post", &live).await;
+	assert!(read.contains("fresh from bob"));
+
+	alice.post("/create_post", "body=like+this").await;
+	let post = post_id(&app, "like this").await;
+	bob.post(&format!("/like_post/{post}"), "").await;
+	let likes = format!("{{\"id\":{post},\"likes\":1}}");
+	read_event(&mut events, &mut read, "likes", &likes).await;
+	read_event(&mut events, &mut read, "notifications", "1").await;
+}
+
+#[rocket::async_test]
+async fn muting_hides_accounts_words_and_threads() {
+	let mut app = TestApp::new().await;
+	let alice = signed_up(&mut app, "alice").await;
+	let bob = signed_up(&mut app, "bob").await;
+	let carol = signed_up(&mut app, "carol").await;
+	bob.post("/create_post", "body=spoilers+for+the+finale")
+		.await;
+	carol.post("/create_post", "body=carol+says+hi").await;
+
+	let res = alice
+		.post("/settings/mutes/words", "phrase=Spoilers&days=7")
+		.await;
+	assert_eq!(location(&res), Some("/settings/mutes"));
+	drop(res);
+	let feed = page(&alice, "/").await;
+	assert!(!feed.contains("spoilers for the finale"));
+	assert!(feed.contains("carol says hi"));
+
+	alice.post("/user/carol/mute", "").await;
+	assert!(!page(&alice, "/").await.contains("carol says hi"));
+	let feed = page(&bob, "/").await;
+	assert!(feed.contains("spoilers for the finale") && feed.contains("carol says hi"));
+
+	// replies in a muted thread don't notify
+	alice.post("/create_post", "body=a+thread").await;
+	let post = post_id(&app, "a thread").await;
+	alice.post(&format!("/post/{post}/mute"), "").await;
+	let reply = format!("body=muted+reply&parent_id={post}");
+	bob.post("/create_post", &reply).await;
+	assert!(!page(&alice, "/notifications").await.contains("replied"));
+	alice.post(&format!("/post/{post}/unmute"), "").await;
+	let reply = format!("body=heard+reply&parent_id={post}");
+	bob.post("/create_post", &reply).await;
+	assert!(page(&alice, "/notifications").await.contains("replied"));
+}
+
+#[rocket::async_test]
+async fn reports_reach_moderators_and_get_back_to_the_reporter() {
+	let mut app = TestApp::new().await;
+	let alice = signed_up(&mut app, "alice").await;
+	let bob = signed_up(&mut app, "bob").await;
+	let carol = signed_up(&mut app, "carol").await;
+	let moderator = account(&app, "alice").await;
+	moderator
+		.set_role(app.pool(), Role::Moderator)
+		.await
+		.unwrap();
+	carol.post("/create_post", "body=cheap+watches").await;
+	let post = post_id(&app, "cheap watches").await;
+
+	let res = bob
+		.post(
+			&format!("/post/{post}/report"),
+			"reason=spam&details=selling+things",
+		)
+		.await;
+	assert_eq!(res.status(), Status::SeeOther);
+	drop(res);
+	assert!(page(&alice, "/moderation").await.contains("selling things"));
+	assert_eq!(bob.get("/moderation").await.status(), Status::Forbidden);
+
+	let res = alice
+		.post("/moderation/reports/1", "action=remove_post&note=no+ads")
+		.await;
+	assert_eq!(location(&res), Some("/moderation"));
+	drop(res);
+	let res = bob.get(&format!("/post/{post}")).await;
+	assert_eq!(res.status(), Status::NotFound);
+	let notifications = page(&bob, "/notifications").await;
+	assert!(notifications.contains("your report was resolved: Post removed: no ads"));
+
+	let res = alice.post("/moderation/reports/1", "action=dismiss").await;
+	assert_eq!(res.status(), Status::BadRequest);
+}
+
+#[rocket::async_test]
+async fn suspended_accounts_cant_log_in_and_limited_ones_leave_the_feeds() {
+	let mut app = TestApp::new().await;
+	let alice = signed_up(&mut app, "alice").await;
+	let bob = signed_up(&mut app, "bob").await;
+	let carol = signed_up(&mut app, "carol").await;
+	let dave = signed_up(&mut app, "dave").await;
+	let moderator = account(&app, "alice").await;
+	moderator
+		.set_role(app.pool(), Role::Moderator)
+		.await
+		.unwrap();
+	carol.post("/create_post", "body=limited+reach").await;
+
+	dave.post("/user/carol/report", "reason=spam").await;
+	alice.post("/moderation/reports/1", "action=limit").await;
+	assert!(!page(&dave, "/").await.contains("limited reach"));
+	let visitor = app.browser().await;
+	assert!(!page(&visitor, "/").await.contains("limited reach"));
+	// followers and carol still see the posts
+	assert!(page(&carol, "/").await.contains("limited reach"));
+	bob.post("/user/carol/follow", "").await;
+	assert!(page(&bob, "/").await.contains("limited reach"));
+
+	dave.post("/user/bob/report", "reason=spam").await;
+	alice
+		.post("/moderation/reports/2", "action=suspend&days=3")
+		.await;
+	bob.post("/signout", "").await;
+	let res = bob.login("bob", PASSWORD).await;
+	assert_eq!(location(&res), Some("/login"));
+	drop(res);
+	assert!(!bob.signed_in());
+	assert!(page(&bob, "/login")
+		.await
+		.contains("This account is suspended until"));
+}
+
+#[rocket::async_test]
+async fn two_factor_logins_take_a_code_or_a_recovery_code() {
+	let mut app = TestApp::new().await;
+	let alice = signed_up(&mut app, "alice").await;
+
+	alice.get("/settings/2fa").await;
+	let secret = alice
+		.client
+		.cookies()
+		.get_private("pending_totp")
+		.and_then(|c| totp::decode_secret(c.value()))
+		.unwrap();
+	let code = totp::code(&secret, totp::current_step());
+	let res = alice
+		.post("/settings/2fa/enable", &format!("code={code}"))
+		.await;
+	let html = res.into_string().await.unwrap();
+	let (_, codes) = html.split_once("<pre class=\"recovery_codes\">").unwrap();
+	let (codes, _) = codes.split_once("</pre>").unwrap();
+	let codes: Vec<&str> = codes.lines().collect();
+	assert_eq!(codes.len(), totp::RECOVERY_CODE_COUNT);
+
+	alice.post("/signout", "").await;
+	let res = alice.login("alice", PASSWORD).await;
+	assert_eq!(location(&res), Some("/login/2fa"));
+	drop(res);
+	assert!(!alice.signed_in());
+	let res = alice.post("/login/2fa", "code=12345a").await;
+	assert_eq!(location(&res), Some("/login/2fa?err=code"));
+	drop(res);
+	// the step used for enabling is spent, so the next one is what the app would show
+	let code = totp::code(&secret, totp::current_step() + 1);
+	let res = alice.post("/login/2fa", &format!("code={code}")).await;
+	assert_eq!(location(&res), Some("/"));
+	drop(res);
+	assert!(alice.signed_in());
+
+	// recovery codes only work once
+	for signed_in in [true, false] {
+		alice.post("/signout", "").await;
+		alice.login("alice", PASSWORD).await;
+		alice
+			.post("/login/2fa", &format!("code={}", codes[0]))
+			.await;
+		assert_eq!(alice.signed_in(), signed_in);
+	}
+}
+
+#[rocket::async_test]
+async fn changing_and_resetting_passwords() {
+	let mut app = TestApp::new().await;
+	let alice = signed_up(&mut app, "alice").await;
+	let laptop = app.browser().await;
+	laptop.login("alice", PASSWORD).await;
+
+	let res = alice
+		.post(
+			"/settings/password",
+			"current=Wrong-pass1&password=Other-pass1&confirm=Other-pass1",
+		)
+		.await;
+	assert_eq!(location(&res), Some("/settings?err=current"));
+	drop(res);
+	let res = alice
+		.post(
+			"/settings/password",
+			&format!("current={PASSWORD}&password=Other-pass1&confirm=Other-pass1"),
+		)
+		.await;
+	assert_eq!(location(&res), Some("/settings?msg=password"));
+	drop(res);
+	// the other session is signed out, this one isn't
+	assert_eq!(alice.get("/settings").await.status(), Status::Ok);
+	let res = laptop.get("/settings").await;
+	assert_eq!(location(&res), Some("/login?err=session"));
+	drop(res);
+	assert!(!laptop.signed_in());
+	let res = laptop.login("alice", PASSWORD).await;
+	assert_eq!(location(&res), Some("/login?err=credentials"));
+	drop(res);
+
+	// resets are only mailed to verified addresses
+	let email = "alice@passwords.example";
+	alice
+		.post("/settings/email", "email=alice%40passwords.example")
+		.await;
+	let res = alice.get(&mailed_link(email, "/verify_email/")).await;
+	assert_eq!(location(&res), Some("/settings?msg=verified"));
+	drop(res);
+
+	let visitor = app.browser().await;
+	visitor.get("/reset").await;
+	let res = visitor
+		.post("/reset", "email=alice%40passwords.example")
+		.await;
+	assert_eq!(location(&res), Some("/reset?msg=sent"));
+	drop(res);
+	let reset = mailed_link(email, "/reset/");
+	assert_eq!(visitor.get(&reset).await.status(), Status::Ok);
+	let res = visitor
+		.post(&reset, "password=Reset-pass1&confirm=Reset-pass2")
+		.await;
+	assert_eq!(location(&res), Some(&*format!("{reset}?err=mismatch")));
+	drop(res);
+	let res = visitor
+		.post(&reset, "password=Reset-pass1&confirm=Reset-pass1")
+		.await;
+	assert_eq!(location(&res), Some("/login?msg=reset"));
+	drop(res);
+	let res = visitor
+		.post(&reset, "password=Reset-pass2&confirm=Reset-pass2")
+		.await;
+	assert_eq!(res.status(), Status::NotFound);
+	drop(res);
+
+	let res = visitor.login("alice", "Reset-pass1").await;
+	assert_eq!(location(&res), Some("/"));
+}
+
+#[rocket::async_test]
+async fn handles_and_passwords_are_validated() {
+	let mut app = TestApp::new().await;
+	let cases = [
+		("al", PASSWORD, "handle_length"),
+		("bad-handle", PASSWORD, "handle_charset"),
+		("admin", PASSWORD, "handle_reserved"),
+		("Login", PASSWORD, "handle_reserved"),
+		("alice", "Sh0rt", "password_length"),
+		("alice", "password", "password_weak"),
+		("alice", "Alice-pass1", "password_handle"),
+		("alice", "password1", "password_breached"),
+	];
+	for (handle, password, error) in cases {
+		// a browser each, since every registration counts against the address
+		let browser = app.browser().await;
+		let res = browser.register(handle, password).await;
+		let expected = format!("/register?err={error}");
+		assert_eq!(location(&res), Some(&*expected), "{handle} {password}");
+	}
+
+	let visitor = app.browser().await;
+	let form = page(&visitor, "/register?err=handle_reserved").await;
+	assert!(form.contains("This handle is reserved"));
+
+	// changing to a reserved handle is refused the same way
+	let alice = signed_up(&mut app, "alice").await;
+	let res = alice.post("/settings/handle", "handle=api").await;
+	assert_eq!(location(&res), Some("/settings?err=handle_reserved"));
+}
+
+#[rocket::async_test]
+async fn old_handles_redirect_and_changes_cool_down() {
+	let mut app = TestApp::new().await;
+	let alice = signed_up(&mut app, "alice").await;
+	let bob = signed_up(&mut app, "bob").await;
+	alice.post("/create_post", "body=still+me").await;
+
+	let res = alice.post("/settings/handle", "handle=alicia").await;
+	assert_eq!(location(&res), Some("/settings?msg=handle"));
+	drop(res);
+	let res = bob.get("/user/alice").await;
+	assert_eq!(location(&res), Some("/user/alicia"));
+	drop(res);
+	assert!(page(&bob, "/user/alicia").await.contains("still me"));
+	let res = bob.get("/user/alice/likes").await;
+	assert_eq!(location(&res), Some("/user/alicia/likes"));
+	drop(res);
+
+	// the old handle stays with the account, so nobody else can pick it up right away
+	let other = app.browser().await;
+	let res = other.register("alice", PASSWORD).await;
+	assert_eq!(location(&res), Some("/register?err=handle_reserved"));
+	drop(res);
+
+	let res = alice.post("/settings/handle", "handle=alison").await;
+	assert_eq!(location(&res), Some("/settings?err=handle_cooldown"));
+}
+
+#[rocket::async_test]
+async fn invite_only_registration() {
+	let mut app = TestApp::with_registration(RegistrationMode::Invite).await;
+	command(&app, &["create-account", "alice"]).await.unwrap();
+	let alice = app.browser().await;
+	alice.login("alice", PASSWORD).await;
+	alice.post("/invites", "max_uses=1&days=7").await;
+	let code: String = sqlx::query_scalar("SELECT code FROM invite")
+		.fetch_one(app.pool())
+		.await
+		.unwrap();
+
+	let bob = app.browser().await;
+	let res = bob.register("bob", PASSWORD).await;
+	assert_eq!(location(&res), Some("/register?err=invite_required"));
+	drop(res);
+	let form = format!("username=bob&password={PASSWORD}&invite={code}");
+	let res = bob.post("/register", &form).await;
+	assert_eq!(location(&res), Some("/"));
+	drop(res);
+	assert!(bob.signed_in());
+
+	// the invite was for one account
+	let carol = app.browser().await;
+	carol.get("/register").await;
+	let form = format!("username=carol&password={PASSWORD}&invite={code}");
+	let res = carol.post("/register", &form).await;
+	let expected = format!("/register?invite={code}&err=invite");
+	assert_eq!(location(&res), Some(&*expected));
+}
+
+#[rocket::async_test]
+async fn closed_registration() {
+	let mut app = TestApp::with_registration(RegistrationMode::Closed).await;
+	let browser = app.browser().await;
+	let res = browser.register("alice", PASSWORD).await;
+	assert_eq!(res.status(), Status::Forbidden);
+	drop(res);
+	assert!(!browser.signed_in());
+}
+
+#[rocket::async_test]
+async fn registrations_wait_for_approval() {
+	let mut app = TestApp::with_registration(RegistrationMode::Approval).await;
+	command(&app, &["create-account", "alice", "admin"])
+		.await
+		.unwrap();
+	let alice = app.browser().await;
+	alice.login("alice", PASSWORD).await;
+
+	let bob = app.browser().await;
+	let res = bob.register("bob", PASSWORD).await;
+	assert_eq!(location(&res), Some("/login?msg=pending"));
+	drop(res);
+	let res = bob.login("bob", PASSWORD).await;
+	assert_eq!(location(&res), Some("/login?err=pending"));
+	drop(res);
+
+	assert!(page(&alice, "/admin/pending").await.contains("bob"));
+	alice.post("/admin/pending/bob/approve", "").await;
+	let res = bob.login("bob", PASSWORD).await;
+	assert_eq!(location(&res), Some("/"));
+}
@@ -0,0 +1,246 @@
+mod e2e;
+mod routes;
+
+use std::{
+	net::{Ipv4Addr, SocketAddr},
+	str::FromStr,
+};
+
+use rand::{distributions::Alphanumeric, Rng};
+use rocket::{
+	http::{ContentType, Cookie, Header},
+	local::asynchronous::{Client, LocalRequest, LocalResponse},
+	tokio, Build, Rocket,
+};
+use sqlx::{
+	postgres::{PgConnectOptions, PgPoolOptions},
+	ConnectOptions, Connection, PgPool,
+};
+
+use crate::{
+	csrf,
+	database::{
+		account::Account,
+		memory::MemoryStorage,
+		migrate,
+		storage::{PgStorage, Store},
+	},
+	events, session, RegistrationMode,
+};
+
+pub const PASSWORD: &str = "Test-pass1";
+const CSRF_TOKEN: &str = "test-token";
+
+/// A client for the core routes with everything in memory and no postgres at all
+pub async fn memory_client() -> Client {
+	let store: Store = Box::<MemoryStorage>::default();
+	Client::tracked(crate::core(silent_rocket()).manage(store))
+		.await
+		.expect("valid rocket")
+}
+
+fn silent_rocket() -> Rocket<Build> {
+	let figment = rocket::Config::figment().merge(("log_level", "off"));
+	rocket::custom(figment)
+}
+
+fn test_rocket(pool: PgPool, store: Store, registration: RegistrationMode) -> Rocket<Build> {
+	crate::app(silent_rocket())
+		.manage(pool)
+		.manage(store)
+		.manage(registration)
+}
+
+pub fn store(client: &Client) -> &Store {
+	client.rocket().state::<Store>().expect("managed store")
+}
+
+/// Registers an account straight through the storage
+pub async fn account(client: &Client, handle: &str) -> Account {
+	store(client)
+		.register(handle, PASSWORD, None, false)
+		.await
+		.unwrap_or_else(|_| panic!("couldn't register {handle}"))
+}
+
+pub trait SignedIn {
+	/// Sends the cookies signing in would have set, and the csrf token along with them
+	fn signed_in(self, account: &Account) -> Self;
+}
+impl SignedIn for LocalRequest<'_> {
+	fn signed_in(self, account: &Account) -> Self {
+		self.private_cookie(Cookie::new("id", account.id.0.to_string()))
+			.private_cookie(Cookie::new("role", account.role.as_str()))
+			.private_cookie(Cookie::new(
+				session::EPOCH_COOKIE,
+				account.session_epoch.to_string(),
+			))
+			.private_cookie(Cookie::new(csrf::TOKEN_COOKIE, CSRF_TOKEN))
+			.header(Header::new(csrf::TOKEN_HEADER, CSRF_TOKEN))
+	}
+}
+
+// the server the test databases are created on, needs to allow creating databases
+fn server_options() -> PgConnectOptions {
+	let url = dotenvy::var("TEST_DATABASE_URL")
+		.or_else(|_| dotenvy::var("DATABASE_URL"))
+		.expect("TEST_DATABASE_URL or DATABASE_URL");
+	PgConnectOptions::from_str(&url)
+		.expect("valid database url")
+		.disable_statement_logging()
+}
+
+/// The whole app on a migrated database of its own, which is dropped again with it
+pub struct TestApp {
+	pool: PgPool,
+	database: String,
+	browsers: u8,
+	registration: RegistrationMode,
+}
+impl TestApp {
+	pub async fn new() -> Self {
+		Self::with_registration(RegistrationMode::Open).await
+	}
+	pub async fn with_registration(registration: RegistrationMode) -> Self {
+		let suffix: String = rand::thread_rng()
+			.sample_iter(Alphanumeric)
+			.take(12)
+			.map(|c| (c as char).to_ascii_lowercase())
+			.collect();
+		let database = format!("my_twitter_test_{suffix}");
+
+		let mut conn = server_options().connect().await.expect("database server");
+		sqlx::query(&format!("CREATE DATABASE {database}"))
+			.execute(&mut conn)
+			.await
+			.expect("created database");
+		conn.close().await.ok();
+
+		let pool = PgPoolOptions::new()
+			.connect_with(server_options().database(&database))
+			.await
+			.expect("test database");
+		migrate::run(&pool).await.expect("migrated database");
+
+		Self {
+			pool,
+			database,
+			browsers: 0,
+			registration,
+		}
+	}
+
+	pub fn pool(&self) -> &PgPool {
+		&self.pool
+	}
+
+	/// A client with cookies and an address of its own, like a separate browser
+	pub async fn browser(&mut self) -> Browser {
+		self.launch(self.rocket()).await
+	}
+	/// A browser whose app also forwards the database's notifications to its event streams
+	pub async fn live_browser(&mut self) -> Browser {
+		let rocket = self.rocket().attach(events::fairing());
+		self.launch(rocket).await
+	}
+
+	fn rocket(&self) -> Rocket<Build> {
+		let store: Store = Box::new(PgStorage::new(self.pool.clone()));
+		test_rocket(self.pool.clone(), store, self.registration)
+	}
+	async fn launch(&mut self, rocket: Rocket<Build>) -> Browser {
+		self.browsers += 1;
+		let client = Client::tracked(rocket).await.expect("valid rocket");
+		Browser {
+			client,
+			remote: SocketAddr::from((Ipv4Addr::new(10, 0, 0, self.browsers), 8000)),
+		}
+	}
+}
+impl Drop for TestApp {
+	fn drop(&mut self) {
+		// drop can't wait on the test's runtime, so the database is dropped from a thread with its own
+		let database = self.database.clone();
+		let dropped = std::thread::spawn(move || {
+			let runtime = tokio::runtime::Builder::new_current_thread()
+				.enable_all()
+				.build()?;
+			runtime.block_on(async {
+				let mut conn = server_options().connect().await?;
+				sqlx::query(&format!("DROP DATABASE IF EXISTS {database} WITH (FORCE)"))
+					.execute(&mut conn)
+					.await?;
+				conn.close().await
+			})?;
+			Ok::<_, Box<dyn std::error::Error + Send + Sync>>(())
+		})
+		.join();
+		if let Ok(Err(e)) = dropped {
+			eprintln!("Couldn't drop test database {}: {e}", self.database);
+		}
+	}
+}
+
+pub struct Browser {
+	pub client: Client,
+	remote: SocketAddr,
+}
+impl Browser {
+	/// The csrf token of the current session, pages set one when there isn't any
+	pub fn csrf_token(&self) -> String {
+		self.client
+			.cookies()
+			.get_private(csrf::TOKEN_COOKIE)
+			.map(|c| c.value().to_owned())
+			.unwrap_or_default()
+	}
+	pub fn signed_in(&self) -> bool {
+		self.client.cookies().get_private("id").is_some()
+	}
+
+	pub async fn get(&self, uri: &str) -> LocalResponse<'_> {
+		self.client
+			.get(uri.to_owned())
+			.remote(self.remote)
+			.dispatch()
+			.await
+	}
+	/// Submits the form the way the pages do, with the token
+	pub async fn post(&self, uri: &str, form: &str) -> LocalResponse<'_> {
+		self.client
+			.post(uri.to_owned())
+			.remote(self.remote)
+			.header(ContentType::Form)
+			.header(Header::new(csrf::TOKEN_HEADER, self.csrf_token()))
+			.body(form)
+			.dispatch()
+			.await
+	}
+	pub async fn delete(&self, uri: &str) -> LocalResponse<'_> {
+		self.client
+			.delete(uri.to_owned())
+			.remote(self.remote)
+			.header(Header::new(csrf::TOKEN_HEADER, self.csrf_token()))
+			.dispatch()
+			.await
+	}
+
+	pub async fn register(&self, handle: &str, password: &str) -> LocalResponse<'_> {
+		self.get("/register").await;
+		self.post(
+			"/register",
+			&format!("username={handle}&password={password}"),
+		)
+		.await
+	}
+	pub async fn login(&self, handle: &str, password: &str) -> LocalResponse<'_> {
+		self.get("/login").await;
+		self.post("/login", &format!("username={handle}&password={password}"))
+			.await
+	}
+}
+
+/// Where a redirect goes
+pub fn location<'a>(res: &'a LocalResponse<'_>) -> Option<&'a str> {
+	res.headers().get_one("Location")
+}
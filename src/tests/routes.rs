@@ -0,0 +1,329 @@
+use rocket::http::{Accept, ContentType, Cookie, Header, Status};
+
+use super::{account, memory_client, store, SignedIn};
+
+#[rocket::async_test]
+async fn posts_show_up_in_the_feed() {
+	let client = memory_client().await;
+	let alice = account(&client, "alice").await;
+
+	let res = client
+		.post("/create_post")
+		.header(ContentType::Form)
+		.body("body=hello+there")
+		.signed_in(&alice)
+		.dispatch()
+		.await;
+	assert_eq!(res.status(), Status::SeeOther);
+
+	let feed = client
+		.get("/")
+		.dispatch()
+		.await
+		.into_string()
+		.await
+		.unwrap();
+	assert!(feed.contains("hello there"));
+}
+
+#[rocket::async_test]
+async fn replies_show_up_under_their_post() {
+	let client = memory_client().await;
+	let alice = account(&client, "alice").await;
+	let post = store(&client)
+		.create_post(&alice, "first", None)
+		.await
+		.unwrap();
+
+	let res = client
+		.post("/create_post")
+		.header(ContentType::Form)
+		.body(format!("body=second&parent_id={}", post.id.0))
+		.signed_in(&alice)
+		.dispatch()
+		.await;
+	assert_eq!(
+		res.headers().get_one("Location"),
+		Some(&*format!("/post/{}", post.id.0))
+	);
+
+	let replies = store(&client).get_replies(&post, 10, None).await.unwrap();
+	assert_eq!(replies.len(), 1);
+	assert_eq!(&*replies[0].body, "second");
+}
+
+#[rocket::async_test]
+async fn posting_needs_a_session_and_the_csrf_token() {
+	let client = memory_client().await;
+	let alice = account(&client, "alice").await;
+
+	// without any cookies the csrf check fails first
+	let res = client
+		.post("/create_post")
+		.header(ContentType::Form)
+		.body("body=hi")
+		.dispatch()
+		.await;
+	assert_eq!(res.status(), Status::Forbidden);
+
+	let res = client
+		.post("/create_post")
+		.header(ContentType::Form)
+		.body("body=hi&csrf_token=wrong")
+		.private_cookie(Cookie::new("id", alice.id.0.to_string()))
+		.private_cookie(Cookie::new("epoch", alice.session_epoch.to_string()))
+		.private_cookie(Cookie::new("csrf_token", "right"))
+		.dispatch()
+		.await;
+	assert_eq!(res.status(), Status::Forbidden);
+	assert!(store(&client)
+		.get_recent_posts(10, None)
+		.await
+		.unwrap()
+		.is_empty());
+}
+
+#[rocket::async_test]
+async fn liking_toggles_the_vote() {
+	let client = memory_client().await;
+	let alice = account(&client, "alice").await;
+	let bob = account(&client, "bob").await;
+	let post = store(&client)
+		.create_post(&alice, "like me", None)
+		.await
+		.unwrap();
+	let uri = format!("/like_post/{}", post.id.0);
+
+	let res = client.post(&uri).signed_in(&bob).dispatch().await;
+	assert_eq!(res.status(), Status::Ok);
+	assert!(store(&client).voted_by(&post, bob.id).await.unwrap());
+
+	let likes = client
+		.get(format!("/post/{}/likes", post.id.0))
+		.dispatch()
+		.await
+		.into_string()
+		.await
+		.unwrap();
+	assert!(likes.contains("bob"));
+
+	client.post(&uri).signed_in(&bob).dispatch().await;
+	assert!(!store(&client).voted_by(&post, bob.id).await.unwrap());
+
+	// liking your own post isn't allowed
+	let res = client.post(&uri).signed_in(&alice).dispatch().await;
+	assert_eq!(res.status(), Status::Forbidden);
+}
+
+#[rocket::async_test]
+async fn following_and_unfollowing() {
+	let client = memory_client().await;
+	let alice = account(&client, "alice").await;
+	let bob = account(&client, "bob").await;
+
+	let res = client
+		.post("/user/alice/follow")
+		.signed_in(&bob)
+		.dispatch()
+		.await;
+	assert_eq!(res.status(), Status::SeeOther);
+	let alice_now = store(&client)
+		.find_account_by_id(alice.id, Some(bob.id))
+		.await
+		.unwrap()
+		.unwrap();
+	assert_eq!(alice_now.followers.0, 1);
+	assert!(alice_now.followed_by_user);
+
+	client
+		.post("/user/alice/unfollow")
+		.signed_in(&bob)
+		.dispatch()
+		.await;
+	let alice_now = store(&client)
+		.find_account_by_id(alice.id, None)
+		.await
+		.unwrap()
+		.unwrap();
+	assert_eq!(alice_now.followers.0, 0);
+}
+
+#[rocket::async_test]
+async fn only_the_author_deletes_a_post() {
+	let client = memory_client().await;
+	let alice = account(&client, "alice").await;
+	let bob = account(&client, "bob").await;
+	let post = store(&client)
+		.create_post(&alice, "mine", None)
+		.await
+		.unwrap();
+	let uri = format!("/post/{}", post.id.0);
+
+	let res = client.delete(&uri).signed_in(&bob).dispatch().await;
+	assert_eq!(res.status(), Status::Forbidden);
+
+	let res = client.delete(&uri).signed_in(&alice).dispatch().await;
+	assert_eq!(res.status(), Status::SeeOther);
+	assert!(store(&client)
+		.find_post_by_id(post.id, None)
+		.await
+		.unwrap()
+		.is_none());
+}
+
+#[rocket::async_test]
+async fn deleting_the_account_removes_its_posts() {
+	let client = memory_client().await;
+	let alice = account(&client, "alice").await;
+	let post = store(&client)
+		.create_post(&alice, "bye", None)
+		.await
+		.unwrap();
+
+	let res = client
+		.post("/delete_account")
+		.signed_in(&alice)
+		.dispatch()
+		.await;
+	assert_eq!(res.status(), Status::SeeOther);
+	assert!(store(&client)
+		.find_account_by_id(alice.id, None)
+		.await
+		.unwrap()
+		.is_none());
+	assert!(store(&client)
+		.find_post_by_id(post.id, None)
+		.await
+		.unwrap()
+		.is_none());
+}
+
+#[rocket::async_test]
+async fn old_sessions_are_signed_out() {
+	let client = memory_client().await;
+	let mut alice = account(&client, "alice").await;
+	alice.session_epoch += 1;
+
+	let res = client.get("/profile").signed_in(&alice).dispatch().await;
+	assert_eq!(res.status(), Status::SeeOther);
+	assert_eq!(
+		res.headers().get_one("Location"),
+		Some("/login?err=session")
+	);
+}
+
+#[rocket::async_test]
+async fn errors_get_a_page() {
+	let client = memory_client().await;
+	let alice = account(&client, "alice").await;
+
+	let res = client.get("/nothing/here").dispatch().await;
+	assert_eq!(res.status(), Status::NotFound);
+	assert_eq!(res.content_type(), Some(ContentType::HTML));
+	assert!(res.into_string().await.unwrap().contains("Page not found"));
+
+	let res = client.get("/profile").dispatch().await;
+	assert_eq!(res.status(), Status::Unauthorized);
+	assert!(res.into_string().await.unwrap().contains("href=\"/login\""));
+
+	// validation errors say what was wrong
+	let res = client
+		.post("/create_post")
+		.header(ContentType::Form)
+		.signed_in(&alice)
+		.dispatch()
+		.await;
+	assert_eq!(res.status(), Status::BadRequest);
+	assert!(res
+		.into_string()
+		.await
+		.unwrap()
+		.contains("A post needs a body"));
+}
+
+#[rocket::async_test]
+async fn api_errors_are_json() {
+	let client = memory_client().await;
+	let alice = account(&client, "alice").await;
+
+	let res = client
+		.post("/like_post/1")
+		.signed_in(&alice)
+		.dispatch()
+		.await;
+	assert_eq!(res.status(), Status::NotFound);
+	assert_eq!(res.content_type(), Some(ContentType::JSON));
+	let body: serde_json::Value = res.into_json().await.unwrap();
+	assert_eq!(body["error"], 404);
+
+	// other routes answer with json when it's asked for
+	let res = client.get("/profile").header(Accept::JSON).dispatch().await;
+	assert_eq!(res.status(), Status::Unauthorized);
+	assert_eq!(res.content_type(), Some(ContentType::JSON));
+}
+
+#[rocket::async_test]
+async fn responses_carry_the_request_id() {
+	let client = memory_client().await;
+
+	let res = client.get("/").dispatch().await;
+	let id = res.headers().get_one("X-Request-Id").unwrap();
+	assert_eq!(id.len(), 16);
+
+	// an id from a proxy is kept
+	let res = client
+		.get("/nothing/here")
+		.header(Header::new("X-Request-Id", "from-the-proxy"))
+		.dispatch()
+		.await;
+	assert_eq!(
+		res.headers().get_one("X-Request-Id"),
+		Some("from-the-proxy")
+	);
+}
+
+#[rocket::async_test]
+async fn metrics_are_only_for_allowed_addresses() {
+	let client = memory_client().await;
+	let alice = account(&client, "alice").await;
+	client
+		.post("/create_post")
+		.header(ContentType::Form)
+		.body("body=counted")
+		.signed_in(&alice)
+		.dispatch()
+		.await;
+
+	let res = client
+		.get("/metrics")
+		.remote("127.0.0.1:9000".parse().unwrap())
+		.dispatch()
+		.await;
+	assert_eq!(res.status(), Status::Ok);
+	let metrics = res.into_string().await.unwrap();
+	assert!(metrics.contains(
+		r#"my_twitter_http_requests_total{method="POST",route="create_post",status="303"}"#
+	));
+	assert!(metrics.contains("my_twitter_posts_created_total"));
+
+	let res = client
+		.get("/metrics")
+		.remote("10.0.0.1:9000".parse().unwrap())
+		.dispatch()
+		.await;
+	assert_eq!(res.status(), Status::Forbidden);
+	let res = client
+		.get("/metrics")
+		.remote("10.0.0.1:9000".parse().unwrap())
+		.header(Header::new("Authorization", "Bearer guess"))
+		.dispatch()
+		.await;
+	assert_eq!(res.status(), Status::Forbidden);
+	let res = client
+		.get("/metrics")
+		.remote("10.0.0.1:9000".parse().unwrap())
+		.header(Header::new("X-Real-IP", "127.0.0.1"))
+		.dispatch()
+		.await;
+	assert_eq!(res.status(), Status::Forbidden);
+}
@@ -0,0 +1,149 @@
+use std::{collections::HashSet, fs, sync::LazyLock};
+
+use tracing::warn;
+
+pub const MIN_HANDLE_LENGTH: usize = 3;
+pub const MAX_HANDLE_LENGTH: usize = 25;
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+// long enough for any passphrase, short enough that hashing it stays cheap
+pub const MAX_PASSWORD_LENGTH: usize = 128;
+// a password needs characters from at least this many of lowercase, uppercase, digits and symbols
+const MIN_CHARACTER_CLASSES: usize = 2;
+
+// handles that would be confused with the site's own pages
+const RESERVED_HANDLES: [&str; 5] = ["login", "register", "api", "admin", "static"];
+
+// one password per line, picked with PASSWORD_BLOCKLIST
+static BLOCKLIST: LazyLock<HashSet<String>> = LazyLock::new(|| {
+	let path =
+		dotenvy::var("PASSWORD_BLOCKLIST").unwrap_or_else(|_| "breached_passwords.txt".into());
+	match fs::read_to_string(&path) {
+		Ok(s) => s
+			.lines()
+			.map(str::trim)
+			.filter(|l| !l.is_empty() && !l.starts_with('#'))
+			.map(str::to_lowercase)
+			.collect(),
+		Err(e) => {
+			warn!("Couldn't read password blocklist {path}: {e}");
+			HashSet::new()
+		}
+	}
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValidationError {
+	HandleLength,
+	HandleCharset,
+	HandleReserved,
+	HandleTaken,
+	PasswordLength,
+	PasswordWeak,
+	PasswordHandle,
+	PasswordBreached,
+	Invite,
+}
+impl ValidationError {
+	const ALL: [Self; 9] = [
+		Self::HandleLength,
+		Self::HandleCharset,
+		Self::HandleReserved,
+		Self::HandleTaken,
+		Self::PasswordLength,
+		Self::PasswordWeak,
+		Self::PasswordHandle,
+		Self::PasswordBreached,
+		Self::Invite,
+	];
+
+	/// The code passed around in `?err=`
+	pub fn code(&self) -> &'static str {
+		match self {
+			Self::HandleLength => "handle_length",
+			Self::HandleCharset => "handle_charset",
+			Self::HandleReserved => "handle_reserved",
+			Self::HandleTaken => "handle_taken",
+			Self::PasswordLength => "password_length",
+			Self::PasswordWeak => "password_weak",
+			Self::PasswordHandle => "password_handle",
+			Self::PasswordBreached => "password_breached",
+			Self::Invite => "invite",
+		}
+	}
+	pub fn from_code(code: &str) -> Option<Self> {
+		Self::ALL.into_iter().find(|e| e.code() == code)
+	}
+
+	pub fn message(&self) -> String {
+		match self {
+			Self::HandleLength => format!(
+				"Your handle has to be between {MIN_HANDLE_LENGTH} and {MAX_HANDLE_LENGTH} characters long"
+			),
+			Self::HandleCharset => {
+				"Your handle can only contain latin letters, digits and underscores".into()
+			}
+			Self::HandleReserved => "This handle is reserved".into(),
+			Self::HandleTaken => "An account with this handle already exists".into(),
+			Self::PasswordLength => format!(
+				"Your password has to be between {MIN_PASSWORD_LENGTH} and {MAX_PASSWORD_LENGTH} characters long"
+			),
+			Self::PasswordWeak => {
+				"Your password has to mix at least two of lowercase letters, uppercase letters, digits and symbols".into()
+			}
+			Self::PasswordHandle => "Your password can't contain your handle".into(),
+			Self::PasswordBreached => {
+				"This password appeared in a data breach, please choose another one".into()
+			}
+			Self::Invite => "This invite code is invalid, used up or expired".into(),
+		}
+	}
+}
+
+pub fn validate_handle(handle: &str) -> Result<(), ValidationError> {
+	use ValidationError::*;
+
+	// the charset is checked first so the length is in bytes and characters alike
+	if !handle
+		.bytes()
+		.all(|b| b.is_ascii_alphanumeric() || b == b'_')
+	{
+		return Err(HandleCharset);
+	}
+	if !(MIN_HANDLE_LENGTH..=MAX_HANDLE_LENGTH).contains(&handle.len()) {
+		return Err(HandleLength);
+	}
+	if RESERVED_HANDLES
+		.iter()
+		.any(|r| r.eq_ignore_ascii_case(handle))
+	{
+		return Err(HandleReserved);
+	}
+	Ok(())
+}
+
+pub fn validate_password(password: &str, handle: &str) -> Result<(), ValidationError> {
+	use ValidationError::*;
+
+	if !(MIN_PASSWORD_LENGTH..=MAX_PASSWORD_LENGTH).contains(&password.chars().count()) {
+		return Err(PasswordLength);
+	}
+
+	let classes = [
+		password.chars().any(char::is_lowercase),
+		password.chars().any(char::is_uppercase),
+		password.chars().any(|c| c.is_ascii_digit()),
+		password.chars().any(|c| !c.is_alphanumeric()),
+	];
+	if classes.iter().filter(|&&c| c).count() < MIN_CHARACTER_CLASSES {
+		return Err(PasswordWeak);
+	}
+
+	let lowercase = password.to_lowercase();
+	if !handle.is_empty() && lowercase.contains(&handle.to_lowercase()) {
+		return Err(PasswordHandle);
+	}
+	if BLOCKLIST.contains(&lowercase) {
+		return Err(PasswordBreached);
+	}
+	Ok(())
+}
@@ -1,30 +1,87 @@
+let csrf_token = () => {
+	let meta = document.querySelector("meta[name=csrf-token]");
+	return meta ? meta.content : "";
+};
+
 let like_post = (post_id) => {
 	let xmlHttp = new XMLHttpRequest();
-	xmlHttp.open("GET", `/like_post/${post_id}`, true);
+	xmlHttp.open("POST", `/like_post/${post_id}`, true);
+	xmlHttp.setRequestHeader("X-CSRF-Token", csrf_token());
 	xmlHttp.send(null);
 };
 
+let bind_like_button = (b) => {
+	b.addEventListener("click", (e) => {
+		e.stopPropagation();
+
+		like_post(b.dataset.id);
+
+		// updating icon and counter
+		let icon = b.querySelector(".like_icon");
+		let text = b.querySelector(".like_count");
+		if (icon.src.includes("/static/like_hollow.png")) {
+			icon.src = "/static/like_filled.png";
+			text.innerHTML = parseInt(text.innerHTML) + 1;
+		} else {
+			icon.src = "/static/like_hollow.png";
+			text.innerHTML = parseInt(text.innerHTML) - 1;
+		}
+	});
+};
+
+let insert_post = (post) => {
+	// the post might already be on the page if it was rendered after the event was sent
+	if (document.querySelector(`button.like_button[data-id="${post.id}"]`)) {
+		return;
+	}
+
+	let container = post.parent_id === null
+		? document.getElementById("feed")
+		: document.querySelector(`.replies[data-parent-id="${post.parent_id}"]`);
+	if (!container) {
+		return;
+	}
+
+	let template = document.createElement("template");
+	template.innerHTML = post.html + "<hr>";
+	template.content.querySelectorAll("button.like_button").forEach(bind_like_button);
+
+	if (post.parent_id === null) {
+		container.prepend(template.content);
+	} else {
+		container.append(template.content);
+	}
+};
+
+let update_likes = (post) => {
+	document.querySelectorAll(`button.like_button[data-id="${post.id}"] .like_count`).forEach((text) => {
+		text.innerHTML = post.likes;
+	});
+};
+
+let update_notifications = (count) => {
+	let badge = document.getElementById("notification_count");
+	if (badge) {
+		badge.innerHTML = count > 0 ? count : "";
+	}
+};
+
+let listen_for_updates = () => {
+	let events = new EventSource("/events");
+	events.addEventListener("post", (e) => insert_post(JSON.parse(e.data)));
+	events.addEventListener("likes", (e) => update_likes(JSON.parse(e.data)));
+	events.addEventListener("notifications", (e) => update_notifications(JSON.parse(e.data)));
+};
+
 window.onload = () => {
 	let like_buttons = document.querySelectorAll("button.like_button");
 
 	for (let i = 0; i < like_buttons.length; i++) {
-		let b = like_buttons.item(i);
-
-		b.addEventListener("click", (e) => {
-			e.stopPropagation();
-
-			like_post(b.dataset.id);
-
-			// updating icon and counter
-			let icon = b.querySelector(".like_icon");
-			let text = b.querySelector(".like_count");
-			if (icon.src.includes("/static/like_hollow.png")) {
-				icon.src = "/static/like_filled.png";
-				text.innerHTML = parseInt(text.innerHTML) + 1;
-			} else {
-				icon.src = "/static/like_hollow.png";
-				text.innerHTML = parseInt(text.innerHTML) - 1;
-			}
-		});
+		bind_like_button(like_buttons.item(i));
+	}
+
+	// only logged in users get live updates
+	if (document.body.dataset.live !== undefined) {
+		listen_for_updates();
 	}
 };